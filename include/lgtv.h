@@ -0,0 +1,60 @@
+/*
+ * C interface to lgtv, built with the `ffi` feature:
+ *
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * Functions returning int give 0 on success and -1 on failure, with the
+ * reason from lgtv_last_error(). Strings passed in are UTF-8 and only
+ * borrowed for the call; strings returned must be released with
+ * lgtv_string_free().
+ */
+
+#ifndef LGTV_H
+#define LGTV_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct LgtvHandle LgtvHandle;
+
+/* Called with each message of a subscription, as JSON, from one of the
+ * handle's threads. `message` is only valid during the call. The callback
+ * must not wait on the TV: any lgtv_* call made from it fails (returning
+ * -1 or NULL, see lgtv_last_error) and lgtv_free does nothing. Hand the
+ * work to another thread instead. */
+typedef void (*lgtv_callback)(const char *message, void *user_data);
+
+/* Why the last call on this thread failed, or NULL. */
+const char *lgtv_last_error(void);
+
+/* Connect and register using a client key from pairing; NULL on failure. */
+LgtvHandle *lgtv_connect(const char *host, const char *client_key, bool ssl);
+/* Close the connection and release the handle. Not from a callback. */
+void lgtv_free(LgtvHandle *handle);
+
+int lgtv_power_off(LgtvHandle *handle);
+int lgtv_set_volume(LgtvHandle *handle, int volume);
+int lgtv_volume_up(LgtvHandle *handle);
+int lgtv_volume_down(LgtvHandle *handle);
+int lgtv_mute(LgtvHandle *handle, bool muted);
+int lgtv_launch_app(LgtvHandle *handle, const char *app_id);
+/* Press a remote button by name, e.g. "home". */
+int lgtv_send_button(LgtvHandle *handle, const char *button);
+
+/* Any SSAP request; `payload` is JSON or NULL. Returns the answer's
+ * payload as JSON (free with lgtv_string_free), or NULL on failure. */
+char *lgtv_request(LgtvHandle *handle, const char *uri, const char *payload);
+/* Call `callback` with every message until the connection ends or the
+ * handle is freed. */
+int lgtv_subscribe(LgtvHandle *handle, const char *uri, lgtv_callback callback, void *user_data);
+
+void lgtv_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
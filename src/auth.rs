@@ -1,14 +1,69 @@
+use crate::config::TvConfigEntry;
 use crate::error::{LgtvError, Result};
-use crate::payload;
+use crate::neighbor;
+use crate::payload::{self, PermissionSet};
+use crate::tls::{self, TlsOptions, WsStream};
+use crate::trace;
+use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
 use serde_json::{json, Value};
+use std::future::Future;
 use std::net::{IpAddr, ToSocketAddrs};
 use std::str::FromStr;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tokio_tungstenite::{
-    connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
-};
+use tokio::time::{timeout_at, Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+const DEFAULT_PAIRING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The sending half of the pairing connection.
+struct WsWriter {
+    sink: SplitSink<WsStream, Message>,
+    url: String,
+}
+
+impl WsWriter {
+    async fn send(&mut self, message: &Value) -> Result<()> {
+        let message = Message::Text(message.to_string());
+        trace::sent(&self.url, &message);
+        self.sink.send(message).await?;
+        Ok(())
+    }
+}
+
+/// How the TV should confirm a new pairing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PairingType {
+    /// The user accepts a prompt on the TV.
+    Prompt,
+    /// The TV shows a PIN that has to be entered on this side.
+    Pin,
+}
+
+impl PairingType {
+    fn as_str(self) -> &'static str {
+        match self {
+            PairingType::Prompt => "PROMPT",
+            PairingType::Pin => "PIN",
+        }
+    }
+}
+
+/// Progress of a pairing, reported to the callback given to
+/// [`LgtvAuth::on_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairingEvent {
+    /// The TV is showing the pairing prompt or PIN and waits for the user.
+    PromptDisplayed,
+    /// The TV accepted the pairing and handed out a client key.
+    Accepted,
+    /// The TV or the user refused the pairing.
+    Rejected,
+    /// Registration finished and the device details have been read.
+    Registered,
+}
+
+type ProgressCallback = Box<dyn Fn(PairingEvent) + Send + Sync>;
 
 pub struct LgtvAuth {
     client_key: Option<String>,
@@ -16,7 +71,16 @@ pub struct LgtvAuth {
     ip: String,
     hostname: Option<String>,
     ssl: bool,
+    require_ssl: bool,
     handshake_done: bool,
+    permissions: PermissionSet,
+    timeout: Duration,
+    cert_fingerprint: Option<String>,
+    tls: TlsOptions,
+    model_name: Option<String>,
+    firmware: Option<String>,
+    friendly_name: Option<String>,
+    progress: Option<ProgressCallback>,
 }
 
 impl LgtvAuth {
@@ -25,6 +89,7 @@ impl LgtvAuth {
         let hostname: Option<String>;
 
         // Check if host is an IP address or hostname
+        let host = host.trim_start_matches('[').trim_end_matches(']');
         if let Ok(ip_addr) = IpAddr::from_str(host) {
             ip = ip_addr.to_string();
 
@@ -41,70 +106,322 @@ impl LgtvAuth {
             ip = socket_addr.ip().to_string();
         }
 
-        // MAC address retrieval is tricky in pure Rust
-        // For now, just leave it as None, but in production code
-        // you might want to use a platform-specific solution
-        let mac_address = None;
-
         Ok(Self {
             client_key: None,
-            mac_address,
+            mac_address: None,
             ip,
             hostname,
-            ssl,
+            ssl: true,
+            require_ssl: ssl,
             handshake_done: false,
+            permissions: PermissionSet::all(),
+            timeout: DEFAULT_PAIRING_TIMEOUT,
+            cert_fingerprint: None,
+            tls: TlsOptions::default(),
+            model_name: None,
+            firmware: None,
+            friendly_name: None,
+            progress: None,
         })
     }
 
+    /// How long to wait for the user to respond on the TV.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Check the TV's certificate on the secure port as `options` say,
+    /// instead of accepting any.
+    pub fn set_tls_options(&mut self, options: TlsOptions) {
+        self.tls = options;
+    }
+
+    /// Choose which permissions to request when pairing.
+    pub fn set_permissions(&mut self, permissions: PermissionSet) {
+        self.permissions = permissions;
+    }
+
+    /// Call `callback` as the pairing progresses, e.g. to tell the user to
+    /// look at the TV once the prompt is shown.
+    pub fn on_progress<F>(&mut self, callback: F)
+    where
+        F: Fn(PairingEvent) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+    }
+
+    fn report(&self, event: PairingEvent) {
+        log::debug!("Pairing progress: {:?}", event);
+        if let Some(callback) = &self.progress {
+            callback(event);
+        }
+    }
+
+    /// Pair by asking the user to accept a prompt on the TV.
     pub async fn connect(&mut self) -> Result<()> {
-        let ws_url = if self.ssl {
-            format!("wss://{}:3001/", self.ip)
-        } else {
-            format!("ws://{}:3000/", self.ip)
-        };
+        let (mut ws_writer, mut rx) = self.open(PairingType::Prompt).await?;
+
+        self.wait_for_key(&mut rx, true).await?;
+        self.fetch_device_info(&mut ws_writer, &mut rx).await;
+        self.report(PairingEvent::Registered);
+        Ok(())
+    }
 
-        let (ws_stream, _) = connect_async(ws_url).await?;
+    /// Register using the key given to [`set_client_key`](Self::set_client_key)
+    /// without involving the user: fails with
+    /// [`LgtvError::ConfirmationRequired`] instead of waiting whenever the TV
+    /// would ask for confirmation.
+    pub async fn connect_non_interactive(&mut self) -> Result<()> {
+        if self.client_key.is_none() {
+            return Err(LgtvError::ConfirmationRequired);
+        }
+
+        let (mut ws_writer, mut rx) = self.open(PairingType::Prompt).await?;
+        self.wait_for_key(&mut rx, false).await?;
+        self.fetch_device_info(&mut ws_writer, &mut rx).await;
+        self.report(PairingEvent::Registered);
+        Ok(())
+    }
+
+    /// Store this MAC address, e.g. one found by a scan, instead of looking
+    /// it up after pairing.
+    pub fn set_mac_address(&mut self, mac: &str) {
+        self.mac_address = Some(mac.to_string());
+    }
+
+    /// Present an existing client key when registering, so a TV that
+    /// already trusts it doesn't prompt again.
+    pub fn set_client_key(&mut self, key: &str) {
+        self.client_key = Some(key.to_string());
+    }
+
+    /// Pair by having the TV display a PIN, which `read_pin` must obtain
+    /// from the user.
+    pub async fn connect_with_pin<F, Fut>(&mut self, read_pin: F) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        let (mut ws_writer, mut rx) = self.open(PairingType::Pin).await?;
+
+        // The TV acknowledges the registration once the PIN is on screen
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let response = self.next_response(&mut rx, deadline).await?;
+            let pairing_type = response
+                .get("payload")
+                .and_then(|p| p.get("pairingType"))
+                .and_then(|v| v.as_str());
+            if pairing_type == Some("PIN") {
+                self.report(PairingEvent::PromptDisplayed);
+                break;
+            }
+        }
 
-        let (tx, mut rx) = mpsc::channel::<Value>(32);
+        let pin = read_pin().await?;
+        let set_pin = json!({
+            "id": "pin_0",
+            "type": "request",
+            "uri": "ssap://pairing/setPin",
+            "payload": {"pin": pin.trim()}
+        });
+        ws_writer.send(&set_pin).await?;
 
-        // Handle the WebSocket connection in a separate task
-        self.handle_connection(ws_stream, tx).await?;
+        self.wait_for_key(&mut rx, true).await?;
+        self.fetch_device_info(&mut ws_writer, &mut rx).await;
+        self.report(PairingEvent::Registered);
+        Ok(())
+    }
 
-        // Wait for pairing response
-        println!("Please accept the pairing request on your LG TV");
-        while let Some(response) = rx.recv().await {
+    async fn wait_for_key(
+        &mut self,
+        rx: &mut mpsc::Receiver<Value>,
+        allow_prompt: bool,
+    ) -> Result<()> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let response = self.next_response(rx, deadline).await?;
+            let prompted = response
+                .get("payload")
+                .and_then(|p| p.get("pairingType"))
+                .is_some();
+            if prompted {
+                if !allow_prompt {
+                    return Err(LgtvError::ConfirmationRequired);
+                }
+                self.report(PairingEvent::PromptDisplayed);
+            }
             if let Some(payload) = response.get("payload") {
                 if let Some(client_key) = payload.get("client-key") {
                     if let Some(key) = client_key.as_str() {
                         self.client_key = Some(key.to_string());
                         self.handshake_done = true;
-                        break;
+                        self.report(PairingEvent::Accepted);
+                        // Having just talked to the TV, it is now in the
+                        // neighbor table
+                        if self.mac_address.is_none() {
+                            self.mac_address = neighbor::lookup_mac(&self.ip);
+                        }
+                        return Ok(());
                     }
                 }
             }
         }
+    }
 
-        if self.client_key.is_none() {
-            return Err(LgtvError::AuthError("Pairing failed".to_string()));
+    /// Record the model, firmware version and friendly name over the newly
+    /// registered connection. Missing details are left empty rather than
+    /// failing the pairing.
+    async fn fetch_device_info(
+        &mut self,
+        ws_writer: &mut WsWriter,
+        rx: &mut mpsc::Receiver<Value>,
+    ) {
+        if let Some(info) = Self::query(ws_writer, rx, "ssap://system/getSystemInfo", None).await {
+            self.model_name = info
+                .get("modelName")
+                .and_then(|v| v.as_str())
+                .map(String::from);
         }
 
-        Ok(())
+        if let Some(sw) = Self::query(
+            ws_writer,
+            rx,
+            "ssap://com.webos.service.update/getCurrentSWInformation",
+            None,
+        )
+        .await
+        {
+            let major = sw.get("major_ver").and_then(|v| v.as_str());
+            let minor = sw.get("minor_ver").and_then(|v| v.as_str());
+            self.firmware = match (major, minor) {
+                (Some(major), Some(minor)) => Some(format!("{}.{}", major, minor)),
+                _ => None,
+            };
+        }
+
+        if let Some(settings) = Self::query(
+            ws_writer,
+            rx,
+            "ssap://settings/getSystemSettings",
+            Some(json!({"category": "network", "keys": ["deviceName"]})),
+        )
+        .await
+        {
+            self.friendly_name = settings
+                .get("settings")
+                .and_then(|s| s.get("deviceName"))
+                .and_then(|v| v.as_str())
+                .map(String::from);
+        }
     }
 
-    async fn handle_connection(
+    async fn query(
+        ws_writer: &mut WsWriter,
+        rx: &mut mpsc::Receiver<Value>,
+        uri: &str,
+        payload: Option<Value>,
+    ) -> Option<Value> {
+        let id = format!("info_{}", uri);
+        let mut request = json!({"id": id, "type": "request", "uri": uri});
+        if let Some(payload) = payload {
+            request["payload"] = payload;
+        }
+        ws_writer.send(&request).await.ok()?;
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        loop {
+            let response = timeout_at(deadline, rx.recv()).await.ok()??;
+            if response.get("id").and_then(|v| v.as_str()) != Some(id.as_str()) {
+                continue;
+            }
+            let payload = response.get("payload")?;
+            if payload.get("returnValue").and_then(|v| v.as_bool()) == Some(false) {
+                log::debug!("{} failed: {}", uri, response);
+                return None;
+            }
+            return Some(payload.clone());
+        }
+    }
+
+    /// Wait for the next message from the TV, turning error responses,
+    /// closed connections and the deadline passing into errors.
+    async fn next_response(
         &self,
-        mut ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
-        tx: mpsc::Sender<Value>,
-    ) -> Result<()> {
+        rx: &mut mpsc::Receiver<Value>,
+        deadline: Instant,
+    ) -> Result<Value> {
+        let response = timeout_at(deadline, rx.recv())
+            .await
+            .map_err(|_| LgtvError::PairingTimeout(self.timeout))?
+            .ok_or_else(|| {
+                LgtvError::AuthError("Connection closed before pairing completed".to_string())
+            })?;
+
+        log::debug!("Pairing response: {}", response);
+        if response.get("type").and_then(|v| v.as_str()) == Some("error") {
+            let reason = response
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("pairing rejected");
+            self.report(PairingEvent::Rejected);
+            return Err(LgtvError::PairingDenied(reason.to_string()));
+        }
+
+        Ok(response)
+    }
+
+    /// Connect on the secure port, falling back to the plain one unless
+    /// SSL was required. Newer TVs only accept the former, older ones only
+    /// have the latter; whichever worked is stored with the pairing.
+    /// Returns the URL that worked, the stream and, on the secure port, the
+    /// fingerprint of the TV's certificate.
+    async fn negotiate(&mut self) -> Result<(String, WsStream, Option<String>)> {
+        let secure_url = tls::ws_url(&self.ip, 3001, true);
+        match tls::connect(&secure_url, &self.tls).await {
+            Ok((stream, fingerprint)) => {
+                self.ssl = true;
+                return Ok((secure_url, stream, fingerprint));
+            }
+            Err(e) if self.require_ssl => return Err(e),
+            Err(e) => log::debug!("{} failed ({}), trying the plain port", secure_url, e),
+        }
+
+        let plain_url = tls::ws_url(&self.ip, 3000, false);
+        let (stream, fingerprint) = tls::connect(&plain_url, &self.tls).await?;
+        self.ssl = false;
+        Ok((plain_url, stream, fingerprint))
+    }
+
+    /// Connect, send the registration request, and forward every response
+    /// to the returned receiver.
+    async fn open(
+        &mut self,
+        pairing_type: PairingType,
+    ) -> Result<(WsWriter, mpsc::Receiver<Value>)> {
+        let (url, ws_stream, fingerprint) = self.negotiate().await?;
+        self.cert_fingerprint = fingerprint;
+        let (sink, mut ws_reader) = ws_stream.split();
+        let mut ws_writer = WsWriter {
+            sink,
+            url: url.clone(),
+        };
+
         // Send hello data
-        let hello_data = payload::hello_data();
-        ws_stream
-            .send(Message::Text(hello_data.to_string()))
-            .await?;
+        let mut hello_data = payload::hello_data_with(&self.permissions);
+        hello_data["payload"]["pairingType"] = json!(pairing_type.as_str());
+        if let Some(key) = &self.client_key {
+            hello_data["payload"]["client-key"] = json!(key);
+        }
+        ws_writer.send(&hello_data).await?;
 
         // Process responses
+        let (tx, rx) = mpsc::channel::<Value>(32);
         tokio::spawn(async move {
-            while let Some(msg) = ws_stream.next().await {
+            while let Some(msg) = ws_reader.next().await {
+                if let Ok(msg) = &msg {
+                    trace::received(&url, msg);
+                }
                 match msg {
                     Ok(Message::Text(text)) => {
                         if let Ok(json) = serde_json::from_str::<Value>(&text) {
@@ -123,15 +440,51 @@ impl LgtvAuth {
             }
         });
 
-        Ok(())
+        Ok((ws_writer, rx))
+    }
+
+    /// Update an existing config entry with the result of re-pairing,
+    /// keeping fields this pairing didn't learn (MAC address, hostname,
+    /// per-TV options) as they were.
+    pub fn merge_into(&self, entry: &mut TvConfigEntry) {
+        let paired = self.config_entry();
+        let keep = |new: Option<String>, old: &mut Option<String>| {
+            if new.is_some() {
+                *old = new;
+            }
+        };
+        keep(paired.key, &mut entry.key);
+        keep(paired.ip, &mut entry.ip);
+        keep(paired.hostname, &mut entry.hostname);
+        keep(paired.mac, &mut entry.mac);
+        keep(paired.cert_fingerprint, &mut entry.cert_fingerprint);
+        keep(paired.model, &mut entry.model);
+        keep(paired.firmware, &mut entry.firmware);
+        keep(paired.friendly_name, &mut entry.friendly_name);
+        entry.ssl = paired.ssl;
+        entry.port = paired.port;
+        entry.permissions = paired.permissions;
+    }
+
+    pub fn config_entry(&self) -> TvConfigEntry {
+        TvConfigEntry {
+            key: self.client_key.clone(),
+            ip: Some(self.ip.clone()),
+            hostname: self.hostname.clone(),
+            mac: self.mac_address.clone(),
+            ssl: self.ssl,
+            port: Some(if self.ssl { 3001 } else { 3000 }),
+            cert_fingerprint: self.cert_fingerprint.clone(),
+            model: self.model_name.clone(),
+            firmware: self.firmware.clone(),
+            friendly_name: self.friendly_name.clone(),
+            permissions: (!self.permissions.is_all())
+                .then(|| self.permissions.iter().map(String::from).collect()),
+            ..Default::default()
+        }
     }
 
     pub fn serialise(&self) -> Value {
-        json!({
-            "key": self.client_key,
-            "mac": self.mac_address,
-            "ip": self.ip,
-            "hostname": self.hostname
-        })
+        self.config_entry().to_value()
     }
 }
@@ -0,0 +1,347 @@
+//! [`LgtvClient`], one entry point to everything a TV does, sorted by area:
+//! `client.audio().set_volume(20)`, `client.apps().launch("netflix")`,
+//! `client.pointer().button("HOME")`.
+//!
+//! It is a thin layer over [`LgtvRemote`] and [`LgtvCursor`]: commands go
+//! over the remote's connection, and the pointer socket, which the TV
+//! hands out over that connection, is opened on first use and kept.
+
+use crate::config;
+use crate::cursor::{ButtonResult, ButtonTiming, LgtvCursor};
+use crate::error::Result;
+use crate::remote::{ConnectionState, LgtvRemote};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+/// A TV's remote and pointer behind one connection. Methods take `&self`,
+/// so a client can be shared between tasks like a remote can.
+pub struct LgtvClient {
+    remote: LgtvRemote,
+    cursor: Mutex<Option<LgtvCursor>>,
+    timing: ButtonTiming,
+}
+
+impl LgtvClient {
+    /// A client for `remote`, connected or not; see [`connect`](Self::connect).
+    pub fn new(remote: LgtvRemote) -> Self {
+        Self {
+            remote,
+            cursor: Mutex::new(None),
+            timing: ButtonTiming::default(),
+        }
+    }
+
+    /// The TV stored as `name` in the config file, or the default TV for
+    /// `None`, with the entry's `button_delays` for the pointer. Not yet
+    /// connected.
+    pub fn from_config<'a>(name: impl Into<Option<&'a str>>) -> Result<Self> {
+        let (name, entry) = config::load_entry(name.into())?;
+        let mut client = Self::new(LgtvRemote::from_entry(&name, &entry)?);
+        if let Some(timing) = &entry.button_delays {
+            client.timing = timing.clone();
+        }
+        Ok(client)
+    }
+
+    /// Use these delays between presses in [`Pointer::execute`].
+    pub fn with_timing(mut self, timing: ButtonTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub async fn connect(&self) -> Result<()> {
+        self.remote.connect().await
+    }
+
+    /// Close the pointer socket and the connection, see
+    /// [`LgtvRemote::close`].
+    pub async fn close(&self) {
+        self.cursor.lock().await.take();
+        self.remote.close().await;
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.remote.state()
+    }
+
+    /// The remote underneath, for what the areas below don't cover.
+    pub fn remote(&self) -> &LgtvRemote {
+        &self.remote
+    }
+
+    /// Any SSAP request, as [`LgtvRemote::request`].
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        uri: &str,
+        payload: Option<Value>,
+    ) -> Result<T> {
+        self.remote.request(uri, payload).await
+    }
+
+    pub fn power(&self) -> Power<'_> {
+        Power(&self.remote)
+    }
+
+    pub fn audio(&self) -> Audio<'_> {
+        Audio(&self.remote)
+    }
+
+    pub fn apps(&self) -> Apps<'_> {
+        Apps(&self.remote)
+    }
+
+    pub fn channels(&self) -> Channels<'_> {
+        Channels(&self.remote)
+    }
+
+    pub fn inputs(&self) -> Inputs<'_> {
+        Inputs(&self.remote)
+    }
+
+    pub fn media(&self) -> Media<'_> {
+        Media(&self.remote)
+    }
+
+    /// Buttons and pointer movement, over the pointer socket.
+    pub fn pointer(&self) -> Pointer<'_> {
+        Pointer(self)
+    }
+}
+
+/// Turning the TV and its screen on and off, from [`LgtvClient::power`].
+pub struct Power<'a>(&'a LgtvRemote);
+
+impl Power<'_> {
+    /// Wake the TV with Wake-on-LAN, which needs its MAC address.
+    pub async fn on(&self) -> Result<()> {
+        self.0.on().await
+    }
+
+    pub async fn off(&self) -> Result<()> {
+        self.0.off().await
+    }
+
+    pub async fn screen_off(&self) -> Result<()> {
+        self.0.screen_off().await
+    }
+
+    pub async fn screen_on(&self) -> Result<()> {
+        self.0.screen_on().await
+    }
+
+    pub async fn state(&self) -> Result<Value> {
+        self.0.get_power_state().await
+    }
+
+    /// See [`LgtvRemote::set_sleep_timer`].
+    pub async fn sleep_timer(&self, minutes: u32) -> Result<()> {
+        self.0.set_sleep_timer(minutes).await
+    }
+}
+
+/// Volume, mute and sound output, from [`LgtvClient::audio`].
+pub struct Audio<'a>(&'a LgtvRemote);
+
+impl Audio<'_> {
+    /// Set the volume, returning the level the TV reports after.
+    pub async fn set_volume(&self, level: u32) -> Result<u32> {
+        self.0.set_volume(level).await
+    }
+
+    pub async fn volume_up(&self) -> Result<u32> {
+        self.0.volume_up().await
+    }
+
+    pub async fn volume_down(&self) -> Result<u32> {
+        self.0.volume_down().await
+    }
+
+    /// Mute or unmute, returning whether the TV reports being muted after.
+    pub async fn mute(&self, muted: bool) -> Result<bool> {
+        self.0.mute(muted).await
+    }
+
+    pub async fn volume(&self) -> Result<Value> {
+        self.0.audio_volume().await
+    }
+
+    pub async fn status(&self) -> Result<Value> {
+        self.0.audio_status().await
+    }
+
+    pub async fn sound_output(&self) -> Result<Value> {
+        self.0.get_sound_output().await
+    }
+
+    pub async fn set_sound_output(&self, output: &str) -> Result<()> {
+        self.0.set_sound_output(output).await
+    }
+}
+
+/// Launching and closing apps, from [`LgtvClient::apps`].
+pub struct Apps<'a>(&'a LgtvRemote);
+
+impl Apps<'_> {
+    pub async fn list(&self) -> Result<Value> {
+        self.0.list_apps().await
+    }
+
+    pub async fn launch_points(&self) -> Result<Value> {
+        self.0.list_launch_points().await
+    }
+
+    /// The app ID for an ID or (part of) an app's title, see
+    /// [`LgtvRemote::find_app`].
+    pub async fn find(&self, name: &str) -> Result<String> {
+        self.0.find_app(name).await
+    }
+
+    pub async fn launch(&self, app_id: &str) -> Result<Value> {
+        self.0.start_app(app_id).await
+    }
+
+    /// Launch with a full `applicationManager/launch` payload, e.g. to pass
+    /// the app parameters.
+    pub async fn launch_with(&self, payload: Value) -> Result<Value> {
+        self.0.open_app_with_payload(payload).await
+    }
+
+    pub async fn close(&self, app_id: &str) -> Result<()> {
+        self.0.close_app(app_id).await
+    }
+
+    pub async fn foreground(&self) -> Result<Value> {
+        self.0.get_foreground_app_info().await
+    }
+}
+
+/// Live TV channels, from [`LgtvClient::channels`].
+pub struct Channels<'a>(&'a LgtvRemote);
+
+impl Channels<'_> {
+    pub async fn current(&self) -> Result<Value> {
+        self.0.get_tv_channel().await
+    }
+
+    pub async fn list(&self) -> Result<Value> {
+        self.0.list_channels().await
+    }
+
+    /// The channel ID for a number or name, see
+    /// [`LgtvRemote::find_channel`].
+    pub async fn find(&self, name: &str) -> Result<String> {
+        self.0.find_channel(name).await
+    }
+
+    pub async fn set(&self, channel_id: &str) -> Result<()> {
+        self.0.set_tv_channel(channel_id).await
+    }
+
+    pub async fn up(&self) -> Result<()> {
+        self.0.input_channel_up().await
+    }
+
+    pub async fn down(&self) -> Result<()> {
+        self.0.input_channel_down().await
+    }
+}
+
+/// External inputs such as HDMI ports, from [`LgtvClient::inputs`].
+pub struct Inputs<'a>(&'a LgtvRemote);
+
+impl Inputs<'_> {
+    pub async fn list(&self) -> Result<Value> {
+        self.0.list_inputs().await
+    }
+
+    /// The input ID for an ID, a looser spelling or a label, see
+    /// [`LgtvRemote::find_input`].
+    pub async fn find(&self, name: &str) -> Result<String> {
+        self.0.find_input(name).await
+    }
+
+    pub async fn set(&self, input_id: &str) -> Result<()> {
+        self.0.set_input(input_id).await
+    }
+}
+
+/// Playback of whatever is playing, from [`LgtvClient::media`].
+pub struct Media<'a>(&'a LgtvRemote);
+
+impl Media<'_> {
+    pub async fn play(&self) -> Result<()> {
+        self.0.input_media_play().await
+    }
+
+    pub async fn pause(&self) -> Result<()> {
+        self.0.input_media_pause().await
+    }
+
+    pub async fn stop(&self) -> Result<()> {
+        self.0.input_media_stop().await
+    }
+
+    pub async fn rewind(&self) -> Result<()> {
+        self.0.input_media_rewind().await
+    }
+
+    pub async fn fast_forward(&self) -> Result<()> {
+        self.0.input_media_fast_forward().await
+    }
+}
+
+/// Remote buttons and the pointer, from [`LgtvClient::pointer`]. The
+/// pointer socket is opened over the client's connection on first use;
+/// presses from several tasks take turns on it.
+pub struct Pointer<'a>(&'a LgtvClient);
+
+impl<'a> Pointer<'a> {
+    /// The client's cursor, opened if it isn't yet.
+    async fn cursor(&self) -> Result<MappedMutexGuard<'a, LgtvCursor>> {
+        let mut slot = self.0.cursor.lock().await;
+        let cursor = match slot.take() {
+            Some(cursor) => cursor,
+            None => {
+                let mut cursor = LgtvCursor::from_connected(self.0.remote.clone()).await?;
+                cursor.set_timing(self.0.timing.clone());
+                cursor
+            }
+        };
+        Ok(MutexGuard::map(slot, |slot| slot.insert(cursor)))
+    }
+
+    /// Press a button by its pointer-socket name (e.g. `UP`, `HOME`, `5`).
+    pub async fn button(&self, name: &str) -> Result<()> {
+        self.cursor().await?.button(name).await
+    }
+
+    /// Press buttons by their names in [`LgtvCursor::possible_buttons`],
+    /// see [`LgtvCursor::execute`].
+    pub async fn execute(&self, buttons: Vec<&str>) -> Result<Vec<ButtonResult>> {
+        self.cursor().await?.execute(buttons).await
+    }
+
+    pub async fn click(&self) -> Result<()> {
+        self.cursor().await?.click().await
+    }
+
+    /// Move the pointer relative to its current position.
+    pub async fn move_by(&self, dx: i32, dy: i32) -> Result<()> {
+        self.cursor().await?.move_by(dx, dy).await
+    }
+
+    /// See [`LgtvCursor::drag`].
+    pub async fn drag(&self, dx: i32, dy: i32) -> Result<()> {
+        self.cursor().await?.drag(dx, dy).await
+    }
+
+    pub async fn scroll(&self, dx: i32, dy: i32) -> Result<()> {
+        self.cursor().await?.scroll(dx, dy).await
+    }
+
+    /// See [`LgtvCursor::enter_number`].
+    pub async fn enter_number(&self, number: u32) -> Result<()> {
+        self.cursor().await?.enter_number(number).await
+    }
+}
@@ -1,24 +1,503 @@
+use crate::crypt::{self, PassphraseSource};
+use crate::cursor::{edit_distance, ButtonTiming};
 use crate::error::{LgtvError, Result};
-use serde_json::Value;
+use crate::keyring;
+use crate::payload::PermissionSet;
+use crate::rate::RateLimit;
+use crate::remote::LgtvRemote;
+use crate::scan::TvDevice;
+use crate::toml;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::env;
-use std::fs::{self, File};
+use std::fs;
 use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 
+/// One paired TV as stored in the config file, keyed by its name. Fields
+/// not listed here are refused, so a misspelt option isn't ignored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TvConfigEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Where the key is kept instead of `key`, e.g. `keyring:living-room`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    /// UPnP UUID, used to recognise the TV after its IP changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(default)]
+    pub ssl: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub friendly_name: Option<String>,
+    /// Where to send Wake-on-LAN packets, e.g. the subnet's broadcast
+    /// address when 255.255.255.255 doesn't reach the TV.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broadcast: Option<String>,
+    /// Seconds to wait for the TV to answer each command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Permissions requested when pairing; absent means all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<Vec<String>>,
+    /// Milliseconds to pause after pointer buttons, by button name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub button_delays: Option<ButtonTiming>,
+    /// How fast to send, e.g. `{"min_gap_ms": 100, "burst": 3}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Every setting a [`TvConfigEntry`] has, as spelt in the file.
+const ENTRY_FIELDS: &[&str] = &[
+    "key",
+    "key_ref",
+    "ip",
+    "hostname",
+    "mac",
+    "uuid",
+    "ssl",
+    "port",
+    "cert_fingerprint",
+    "model",
+    "firmware",
+    "friendly_name",
+    "broadcast",
+    "timeout",
+    "permissions",
+    "button_delays",
+    "rate_limit",
+];
+
+/// The first setting of an entry that a [`TvConfigEntry`] doesn't have,
+/// described with the known ones closest to it, e.g. "unknown setting
+/// 'buton_delays' (did you mean button_delays?)".
+fn unknown_field(entry: &Map<String, Value>) -> Option<String> {
+    let field = entry
+        .keys()
+        .find(|key| !ENTRY_FIELDS.contains(&key.as_str()))?;
+    let mut candidates: Vec<(usize, &str)> = ENTRY_FIELDS
+        .iter()
+        .map(|known| (edit_distance(field, known), *known))
+        .filter(|(d, known)| *d <= 2.max(known.len() / 3))
+        .collect();
+    candidates.sort();
+    let suggestions: Vec<&str> = candidates.into_iter().take(3).map(|(_, k)| k).collect();
+    Some(if suggestions.is_empty() {
+        format!("unknown setting '{}'", field)
+    } else {
+        format!(
+            "unknown setting '{}' (did you mean {}?)",
+            field,
+            suggestions.join(", ")
+        )
+    })
+}
+
+impl TvConfigEntry {
+    pub fn from_value(value: &Value) -> Result<Self> {
+        Ok(serde_json::from_value(value.clone())?)
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+
+    /// Check the fields that are only used later (addresses, permissions),
+    /// so mistakes are reported when the config is read.
+    pub fn validate(&self, name: &str) -> Result<()> {
+        let invalid = |reason: String| LgtvError::ConfigError(format!("tv '{}': {}", name, reason));
+        let reason = |e: LgtvError| match e {
+            LgtvError::ConfigError(reason) => reason,
+            other => other.to_string(),
+        };
+
+        if let Some(ip) = &self.ip {
+            let bare = ip.trim_start_matches('[').trim_end_matches(']');
+            if bare.parse::<IpAddr>().is_err() {
+                return Err(invalid(format!(
+                    "ip '{}' is not an IP address (put host names in hostname)",
+                    ip
+                )));
+            }
+        }
+        if let Some(mac) = &self.mac {
+            if LgtvRemote::parse_mac_address(mac).is_err() {
+                return Err(invalid(format!("mac '{}' is not a valid MAC address", mac)));
+            }
+        }
+        if self.port == Some(0) {
+            return Err(invalid("port 0 is not a valid port".to_string()));
+        }
+        if let Some(broadcast) = &self.broadcast {
+            if broadcast.parse::<IpAddr>().is_err() && broadcast.parse::<SocketAddr>().is_err() {
+                return Err(invalid(format!(
+                    "broadcast '{}' is not an IP address",
+                    broadcast
+                )));
+            }
+        }
+        if let Some(fingerprint) = &self.cert_fingerprint {
+            let bytes: Vec<&str> = fingerprint.split(':').collect();
+            if bytes.len() != 32 || bytes.iter().any(|b| u8::from_str_radix(b, 16).is_err()) {
+                return Err(invalid(format!(
+                    "cert_fingerprint '{}' is not a SHA-256 fingerprint",
+                    fingerprint
+                )));
+            }
+        }
+        if let Some(reference) = &self.key_ref {
+            if !reference.starts_with(keyring::REF_PREFIX) {
+                return Err(invalid(format!(
+                    "key_ref '{}' should start with '{}'",
+                    reference,
+                    keyring::REF_PREFIX
+                )));
+            }
+        }
+        for permission in self.permissions.iter().flatten() {
+            PermissionSet::empty()
+                .with(permission)
+                .map_err(|e| invalid(reason(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Take every field `other` has, keeping the ones it doesn't.
+    pub fn merge_from(&mut self, other: &TvConfigEntry) -> Result<()> {
+        let mut merged = self.to_value();
+        if let (Some(fields), Value::Object(new)) = (merged.as_object_mut(), other.to_value()) {
+            fields.extend(new);
+        }
+        *self = Self::from_value(&merged)?;
+        Ok(())
+    }
+
+    /// The client key, decrypted if it is stored encrypted and read from
+    /// the keyring if `key_ref` points there.
+    pub fn client_key(&self) -> Result<Option<String>> {
+        if let Some(key) = &self.key {
+            if crypt::is_encrypted(key) {
+                return crypt::decrypt(key).map(Some);
+            }
+            return Ok(Some(key.clone()));
+        }
+        match &self.key_ref {
+            Some(reference) => match reference.strip_prefix(keyring::REF_PREFIX) {
+                Some(account) => keyring::load(account).map(Some),
+                None => Err(LgtvError::ConfigError(format!(
+                    "Unsupported key_ref '{}'",
+                    reference
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Move the client key into the keyring under `account`, leaving only a
+    /// reference to it in the entry.
+    pub fn move_key_to_keyring(&mut self, account: &str) -> Result<()> {
+        if self.key.is_some() {
+            let key = self.client_key()?.unwrap_or_default();
+            keyring::store(account, &key)?;
+            self.key = None;
+            self.key_ref = Some(format!("{}{}", keyring::REF_PREFIX, account));
+        }
+        Ok(())
+    }
+
+    /// Forget the keyring entry this entry's key lives in, if any.
+    pub fn delete_keyring_key(&self) -> Result<()> {
+        match self
+            .key_ref
+            .as_deref()
+            .and_then(|r| r.strip_prefix(keyring::REF_PREFIX))
+        {
+            Some(account) => keyring::delete(account),
+            None => Ok(()),
+        }
+    }
+
+    /// Point the entry at `host`, an IP address (IPv6 possibly in brackets)
+    /// or a hostname.
+    pub fn set_host(&mut self, host: &str) {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.parse::<IpAddr>().is_ok() {
+            self.ip = Some(host.to_string());
+            self.hostname = None;
+        } else {
+            self.ip = None;
+            self.hostname = Some(host.to_string());
+        }
+    }
+
+    /// Override fields from `LGTV_HOST`, `LGTV_KEY`, `LGTV_MAC` and
+    /// `LGTV_SSL`, for running without a config file.
+    pub fn apply_env(&mut self) -> Result<()> {
+        if let Some(host) = env_var("LGTV_HOST") {
+            self.set_host(&host);
+        }
+        if let Some(key) = env_var("LGTV_KEY") {
+            self.key = Some(key);
+        }
+        if let Some(mac) = env_var("LGTV_MAC") {
+            self.mac = Some(mac);
+        }
+        if let Some(ssl) = env_var("LGTV_SSL") {
+            self.ssl = match ssl.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => {
+                    return Err(LgtvError::ConfigError(format!(
+                        "LGTV_SSL must be true or false, not '{}'",
+                        ssl
+                    )))
+                }
+            };
+            // The stored port belongs to the stored scheme
+            self.port = None;
+        }
+        Ok(())
+    }
+}
+
+/// The value of an environment variable, treating empty as unset.
+pub fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The whole config file: the paired TVs by name, plus the name of the one
+/// used when none is given.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(rename = "_default", default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// How many earlier versions of the file to keep as backups when it is
+    /// rewritten (default [`DEFAULT_BACKUPS`]).
+    #[serde(rename = "_backups", default, skip_serializing_if = "Option::is_none")]
+    pub backups: Option<usize>,
+    /// Store client keys encrypted, with the passphrase taken from
+    /// `"passphrase"` (`LGTV_PASSPHRASE`) or `"keyring"`.
+    #[serde(
+        rename = "_encrypt_keys",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub encrypt_keys: Option<String>,
+    #[serde(flatten)]
+    pub tvs: BTreeMap<String, TvConfigEntry>,
+}
+
+impl Config {
+    /// Build a config from a parsed file, checking every entry and naming
+    /// the one that is wrong.
+    pub fn from_value(value: Value) -> Result<Self> {
+        let Value::Object(object) = value else {
+            return Err(LgtvError::ConfigError(
+                "expected TV names mapped to their settings".to_string(),
+            ));
+        };
+        let mut config = Config::default();
+        for (name, value) in object {
+            if name == "_default" {
+                match value {
+                    Value::String(default) => config.default = Some(default),
+                    _ => {
+                        return Err(LgtvError::ConfigError(
+                            "_default must be the name of a TV".to_string(),
+                        ))
+                    }
+                }
+                continue;
+            }
+            if name == "_encrypt_keys" {
+                let source = value.as_str().ok_or_else(|| {
+                    LgtvError::ConfigError(
+                        "_encrypt_keys must be \"passphrase\" or \"keyring\"".to_string(),
+                    )
+                })?;
+                PassphraseSource::from_name(source)?;
+                config.encrypt_keys = Some(source.to_string());
+                continue;
+            }
+            if name == "_backups" {
+                let count = value.as_u64().ok_or_else(|| {
+                    LgtvError::ConfigError("_backups must be a number of files".to_string())
+                })?;
+                config.backups = Some(count as usize);
+                continue;
+            }
+            let Some(fields) = value.as_object() else {
+                return Err(LgtvError::ConfigError(format!(
+                    "tv '{}': expected a table of settings",
+                    name
+                )));
+            };
+            if let Some(reason) = unknown_field(fields) {
+                return Err(LgtvError::ConfigError(format!("tv '{}': {}", name, reason)));
+            }
+            let entry: TvConfigEntry = match serde_json::from_value(value.clone()) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    return Err(LgtvError::ConfigError(format!(
+                        "tv '{}': {}",
+                        name,
+                        field_error(&value).unwrap_or_else(|| e.to_string())
+                    )))
+                }
+            };
+            entry.validate(&name)?;
+            config.tvs.insert(name, entry);
+        }
+        Ok(config)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TvConfigEntry> {
+        self.tvs.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut TvConfigEntry> {
+        self.tvs.get_mut(name)
+    }
+
+    /// Add the TVs of `other`. A TV in both keeps the fields only this
+    /// config has and takes the rest from `other`, or is replaced by
+    /// `other`'s entry outright with `overwrite`.
+    pub fn merge(&mut self, other: Config, overwrite: bool) -> Result<()> {
+        for (name, entry) in other.tvs {
+            match self.tvs.get_mut(&name) {
+                Some(existing) if !overwrite => existing.merge_from(&entry)?,
+                _ => {
+                    self.tvs.insert(name, entry);
+                }
+            }
+        }
+        if overwrite || self.default.is_none() {
+            if let Some(default) = other.default {
+                self.default = Some(default);
+            }
+        }
+        Ok(())
+    }
+
+    /// Which TV to use, in order of precedence: `name` (from `-n`), the
+    /// `LGTV_NAME` environment variable, `_default`, the only TV if there
+    /// is just one, and finally the address in `LGTV_HOST`, which then
+    /// serves as the name.
+    pub fn select(&self, name: Option<&str>) -> Option<String> {
+        name.map(str::to_string)
+            .or_else(|| env_var("LGTV_NAME"))
+            .or_else(|| self.default.clone())
+            .or_else(|| match self.tvs.keys().collect::<Vec<_>>()[..] {
+                [only] => Some(only.clone()),
+                _ => None,
+            })
+            .or_else(|| env_var("LGTV_HOST"))
+    }
+
+    /// The settings of the TV `name`. With `LGTV_HOST` set, a TV the
+    /// config doesn't have gets empty settings, to be filled in by
+    /// [`TvConfigEntry::apply_env`].
+    pub fn entry(&self, name: &str) -> Result<TvConfigEntry> {
+        match self.tvs.get(name) {
+            Some(entry) => Ok(entry.clone()),
+            None if env_var("LGTV_HOST").is_some() => Ok(TvConfigEntry::default()),
+            None => Err(LgtvError::TvNotFound(name.to_string())),
+        }
+    }
+
+    /// The name [`select`](Self::select) picks and its [`entry`](Self::entry).
+    /// Fails with an error naming what was missing.
+    pub fn resolve(&self, name: Option<&str>) -> Result<(String, TvConfigEntry)> {
+        let name = self.select(name).ok_or_else(|| {
+            LgtvError::ConfigError(
+                "A TV name is required. Set one with -n/--name or the set-default command."
+                    .to_string(),
+            )
+        })?;
+        let entry = self.entry(&name)?;
+        Ok((name, entry))
+    }
+}
+
+/// Turn "Living Room TV" into "living-room-tv" for use as a config name.
+pub fn config_name(label: &str) -> String {
+    label
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Which field of an entry that failed to deserialize is at fault, found
+/// by trying them one at a time, e.g. "ssl: invalid type: string "yes",
+/// expected a boolean".
+fn field_error(entry: &Value) -> Option<String> {
+    entry.as_object()?.iter().find_map(|(field, value)| {
+        let single = Value::Object(Map::from_iter([(field.clone(), value.clone())]));
+        serde_json::from_value::<TvConfigEntry>(single)
+            .err()
+            .map(|e| format!("{}: {}", field, e))
+    })
+}
+
 fn home_dir() -> Option<PathBuf> {
     env::var_os("HOME")
         .or_else(|| env::var_os("USERPROFILE"))
         .map(PathBuf::from)
 }
 
+/// The per-user config directory of this platform: `%APPDATA%` on Windows,
+/// `~/Library/Application Support` on macOS, `$XDG_CONFIG_HOME` (or
+/// `~/.config`) elsewhere.
+fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library/Application Support"))
+    } else {
+        xdg_config_home()
+    }
+}
+
+fn xdg_config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")))
+}
+
 fn config_search_paths() -> Vec<PathBuf> {
-    let mut paths = vec![PathBuf::from("/etc/lgtv/config.json")];
+    let mut paths = Vec::new();
+    if cfg!(unix) {
+        paths.push(PathBuf::from("/etc/lgtv/config.json"));
+    }
 
-    // XDG_CONFIG_HOME or ~/.config
-    if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
-        paths.push(PathBuf::from(xdg).join("lgtv/config.json"));
-    } else if let Some(home) = home_dir() {
-        paths.push(home.join(".config/lgtv/config.json"));
+    if let Some(dir) = user_config_dir() {
+        paths.push(dir.join("lgtv/config.json"));
+    }
+    // Earlier versions used the XDG location on macOS too
+    if cfg!(target_os = "macos") {
+        if let Some(dir) = xdg_config_home() {
+            paths.push(dir.join("lgtv/config.json"));
+        }
     }
 
     // Legacy ~/.lgtv
@@ -26,21 +505,30 @@ fn config_search_paths() -> Vec<PathBuf> {
         paths.push(home.join(".lgtv/config.json"));
     }
 
-    paths.push(PathBuf::from("/opt/venvs/lgtv/config/config.json"));
+    if cfg!(unix) {
+        paths.push(PathBuf::from("/opt/venvs/lgtv/config/config.json"));
+    }
 
     paths
 }
 
+/// Where a new per-user config goes on this platform.
+pub fn user_config_path() -> Option<PathBuf> {
+    user_config_dir().map(|dir| dir.join("lgtv/config.toml"))
+}
+
 pub fn find_config() -> Result<PathBuf> {
     let search_paths = config_search_paths();
     let mut writable_path = None;
 
-    // First, try to find an existing config file
+    // First, try to find an existing config file, TOML before JSON
     for path in &search_paths {
-        if path.exists() && path.is_file() {
-            if let Ok(meta) = fs::metadata(path) {
-                if !meta.permissions().readonly() {
-                    return Ok(path.clone());
+        for path in [path.with_extension("toml"), path.clone()] {
+            if path.exists() && path.is_file() {
+                if let Ok(meta) = fs::metadata(&path) {
+                    if !meta.permissions().readonly() {
+                        return Ok(path);
+                    }
                 }
             }
         }
@@ -77,33 +565,399 @@ pub fn find_config() -> Result<PathBuf> {
         }
     }
 
-    writable_path.ok_or_else(|| {
-        let paths_str: Vec<String> = search_paths
+    // New configs are written as TOML
+    writable_path
+        .map(|p| p.with_extension("toml"))
+        .ok_or_else(|| {
+            let paths_str: Vec<String> = search_paths
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            LgtvError::ConfigError(format!(
+                "Cannot find suitable config path to write, create one in {}",
+                paths_str.join(" or ")
+            ))
+        })
+}
+
+/// Find the configured TV a scanned device is, by UUID, then MAC address,
+/// then IP address. Returns its name and entry.
+pub fn known_tv<'a>(config: &'a Config, device: &TvDevice) -> Option<(&'a str, &'a TvConfigEntry)> {
+    let same = |a: &Option<String>, b: &Option<String>| match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    };
+    let find = |matches: &dyn Fn(&TvConfigEntry) -> bool| {
+        config
+            .tvs
             .iter()
-            .map(|p| p.display().to_string())
-            .collect();
-        LgtvError::ConfigError(format!(
-            "Cannot find suitable config path to write, create one in {}",
-            paths_str.join(" or ")
-        ))
-    })
+            .find(|(_, entry)| matches(entry))
+            .map(|(name, entry)| (name.as_str(), entry))
+    };
+    find(&|e| same(&e.uuid, &device.uuid))
+        .or_else(|| find(&|e| same(&e.mac, &device.mac)))
+        .or_else(|| find(&|e| e.ip.as_deref() == Some(device.address.as_str())))
 }
 
-pub fn read_config(path: &Path) -> Result<Value> {
+/// The config file of profile `name`: `profiles/<name>.toml` (or `.json`)
+/// beside wherever a config is found, or in the per-user config directory
+/// when the profile doesn't exist yet.
+pub fn find_profile(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(LgtvError::ConfigError(format!(
+            "Invalid profile name '{}'",
+            name
+        )));
+    }
+    for path in config_search_paths() {
+        let Some(dir) = path.parent() else {
+            continue;
+        };
+        let path = dir.join("profiles").join(name).with_extension("json");
+        for path in [path.with_extension("toml"), path] {
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+    }
+    user_config_dir()
+        .map(|dir| dir.join("lgtv/profiles").join(name).with_extension("toml"))
+        .ok_or_else(|| LgtvError::ConfigError("No config directory found".to_string()))
+}
+
+/// Where scan results are cached: next to the config file.
+pub fn scan_cache_path() -> Result<PathBuf> {
+    Ok(find_config()?.with_file_name("scan-cache.json"))
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// Read a config file, as TOML if it has a `.toml` extension and as JSON
+/// otherwise.
+pub fn read_config(path: &Path) -> Result<Config> {
     let config_str = fs::read_to_string(path)?;
-    let config: Value = serde_json::from_str(&config_str)?;
-    Ok(config)
+    let parsed = if is_toml(path) {
+        toml::from_str(&config_str)
+    } else {
+        serde_json::from_str(&config_str).map_err(LgtvError::from)
+    };
+    let parsed = parsed.and_then(Config::from_value);
+    parsed.map_err(|e| {
+        let reason = match e {
+            LgtvError::ConfigError(reason) => reason,
+            other => other.to_string(),
+        };
+        LgtvError::ConfigError(format!("{}: {}", path.display(), reason))
+    })
 }
 
-pub fn write_config(path: &Path, config: &Value) -> Result<()> {
-    let config_str = serde_json::to_string_pretty(config)?;
+/// The config found in the usual locations, which must exist.
+pub fn load_config() -> Result<Config> {
+    read_existing_config(&find_config()?)
+}
+
+/// Like [`read_config`], but a missing file is a config error.
+pub fn read_existing_config(path: &Path) -> Result<Config> {
+    read_config(path).map_err(|e| match e {
+        LgtvError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            LgtvError::ConfigError(format!("No config file at {}", path.display()))
+        }
+        other => other,
+    })
+}
+
+/// The config to pick a TV from: the file at `path`, which must exist
+/// unless `LGTV_HOST` is set, as that is enough to reach a TV on its own.
+pub fn read_config_for_tv(path: &Path) -> Result<Config> {
+    if env_var("LGTV_HOST").is_some() {
+        read_config_or_default(path)
+    } else {
+        read_existing_config(path)
+    }
+}
+
+/// The TV `name`, or the one [`Config::resolve`] picks for `None`, from
+/// the config found in the usual locations, with the `LGTV_*` overrides of
+/// [`TvConfigEntry::apply_env`] applied as on the command line.
+pub fn load_entry(name: Option<&str>) -> Result<(String, TvConfigEntry)> {
+    let config = read_config_for_tv(&find_config()?)?;
+    let (name, mut entry) = config.resolve(name)?;
+    entry.apply_env()?;
+    Ok((name, entry))
+}
+
+/// Like [`read_config`], but a missing file is an empty config. A file
+/// that exists but doesn't parse is still an error, so it isn't
+/// overwritten.
+pub fn read_config_or_default(path: &Path) -> Result<Config> {
+    match read_config(path) {
+        Err(LgtvError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(Config::default())
+        }
+        other => other,
+    }
+}
+
+/// First line of every TOML config written, as comments don't survive.
+const TOML_HEADER: &str =
+    "# Written by lgtv, which rewrites this file: comments and layout are not kept.\n";
+
+/// Write `config` to `path`, replacing the whole file. A TOML file is
+/// written afresh from the config, so comments added by hand are lost.
+pub fn write_config(path: &Path, config: &Config) -> Result<()> {
+    let encrypted;
+    let config = match &config.encrypt_keys {
+        Some(source) => {
+            encrypted = encrypt_keys(config, PassphraseSource::from_name(source)?)?;
+            &encrypted
+        }
+        None => config,
+    };
+    let config_str = if is_toml(path) {
+        TOML_HEADER.to_string() + &toml::to_string(&serde_json::to_value(config)?)?
+    } else {
+        serde_json::to_string_pretty(config)?
+    };
 
     // Ensure directory exists
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
 
-    let mut file = File::create(path)?;
-    file.write_all(config_str.as_bytes())?;
+    // Replace the file a symlink points to rather than the link itself
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if path.exists() {
+        back_up(&path, config.backups.unwrap_or(DEFAULT_BACKUPS))?;
+    }
+    write_atomic(&path, config_str.as_bytes())
+}
+
+/// A copy of `config` with every key that is still in plain text
+/// encrypted.
+fn encrypt_keys(config: &Config, source: PassphraseSource) -> Result<Config> {
+    let mut config = config.clone();
+    for entry in config.tvs.values_mut() {
+        if let Some(key) = &entry.key {
+            if !crypt::is_encrypted(key) {
+                entry.key = Some(crypt::encrypt(key, source)?);
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// Held while a config file is being read, changed and written back.
+/// Released when dropped.
+pub struct ConfigLock {
+    _file: fs::File,
+}
+
+/// Wait for exclusive access to the config at `path`. The lock is taken on
+/// a `.lock` file beside it, since the config itself is replaced on every
+/// write.
+pub fn lock_config(path: &Path) -> Result<ConfigLock> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let lock_path = path.with_file_name(format!(".{}.lock", file_name));
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    file.lock()?;
+    Ok(ConfigLock { _file: file })
+}
+
+/// Read the config at `path` (empty if there is none), let `change` modify
+/// it and write it back, locked against other `lgtv` processes doing the
+/// same so neither change is lost.
+pub fn update_config<T>(path: &Path, change: impl FnOnce(&mut Config) -> Result<T>) -> Result<T> {
+    let _lock = lock_config(path)?;
+    let mut config = read_config_or_default(path)?;
+    let result = change(&mut config)?;
+    write_config(path, &config)?;
+    Ok(result)
+}
+
+/// Backups kept of the config file unless `_backups` says otherwise.
+pub const DEFAULT_BACKUPS: usize = 3;
+
+/// The `generation`th most recent backup of `path`: `config.toml.bak`,
+/// then `config.toml.bak.2`, `config.toml.bak.3` and so on.
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    if generation > 1 {
+        name.push(format!(".{}", generation));
+    }
+    path.with_file_name(name)
+}
+
+/// Copy `path` to its first backup, shifting older backups along and
+/// dropping the one beyond `keep`.
+fn back_up(path: &Path, keep: usize) -> Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+    for generation in (1..keep).rev() {
+        let older = backup_path(path, generation);
+        if older.exists() {
+            fs::rename(&older, backup_path(path, generation + 1))?;
+        }
+    }
+    // Copied rather than moved, so the config never goes missing
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Write `contents` to a temporary file beside `path`, readable only by the
+/// owner, and rename it over `path` once it is on disk. A crash part way
+/// through leaves either the old file or the new one, never a torn one.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let written = options.open(&tmp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp_path, path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
     Ok(())
 }
+
+/// Remove a TV entry, clearing `_default` if it pointed at it. Returns
+/// whether an entry was removed.
+pub fn remove_tv(config: &mut Config, name: &str) -> bool {
+    if config.tvs.remove(name).is_none() {
+        return false;
+    }
+    if config.default.as_deref() == Some(name) {
+        config.default = None;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn entry_fields_match_the_struct() {
+        let value = |v: &str| Some(v.to_string());
+        let entry = TvConfigEntry {
+            key: value("k"),
+            key_ref: value("keyring:tv"),
+            ip: value("10.0.0.2"),
+            hostname: value("tv.local"),
+            mac: value("00:11:22:33:44:55"),
+            uuid: value("u"),
+            ssl: true,
+            port: Some(3001),
+            cert_fingerprint: value("f"),
+            model: value("m"),
+            firmware: value("f"),
+            friendly_name: value("n"),
+            broadcast: value("10.0.0.255"),
+            timeout: Some(5),
+            permissions: Some(vec![]),
+            button_delays: Some(ButtonTiming::default()),
+            rate_limit: Some(RateLimit::new(Duration::from_millis(100))),
+        };
+        let mut fields: Vec<String> = entry
+            .to_value()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        let mut known: Vec<&str> = ENTRY_FIELDS.to_vec();
+        fields.sort();
+        known.sort();
+        assert_eq!(fields, known);
+    }
+
+    #[test]
+    fn unknown_fields_are_named_with_suggestions() {
+        let entry = json_object(r#"{"ip": "10.0.0.2", "buton_delays": {}}"#);
+        assert_eq!(
+            unknown_field(&entry).as_deref(),
+            Some("unknown setting 'buton_delays' (did you mean button_delays?)")
+        );
+        let entry = json_object(r#"{"colour": 1}"#);
+        assert_eq!(
+            unknown_field(&entry).as_deref(),
+            Some("unknown setting 'colour'")
+        );
+        assert_eq!(unknown_field(&json_object(r#"{"ip": "10.0.0.2"}"#)), None);
+    }
+
+    #[test]
+    fn button_delays_and_rate_limit_are_read_typed() {
+        let config = Config::from_value(serde_json::json!({
+            "tv": {
+                "button_delays": {"default": 50, "channel_up": 500},
+                "rate_limit": {"min_gap_ms": 150, "burst": 3}
+            }
+        }))
+        .unwrap();
+        let entry = config.get("tv").unwrap();
+        let timing = entry.button_delays.as_ref().unwrap();
+        assert_eq!(timing.delay_for("channel_up"), Duration::from_millis(500));
+        assert_eq!(timing.delay_for("up"), Duration::from_millis(50));
+        assert_eq!(
+            entry.rate_limit,
+            Some(RateLimit::new(Duration::from_millis(150)).burst(3))
+        );
+        assert_eq!(
+            TvConfigEntry::from_value(&entry.to_value()).unwrap(),
+            *entry
+        );
+    }
+
+    #[test]
+    fn misspelt_delays_and_limits_are_refused() {
+        let error = |tv: Value| {
+            Config::from_value(serde_json::json!({ "tv": tv }))
+                .unwrap_err()
+                .to_string()
+        };
+        let message = error(serde_json::json!({"rate_limit": {"min_gap": 100}}));
+        assert!(message.contains("unknown field `min_gap`"), "{}", message);
+        let message = error(serde_json::json!({"button_delays": {"chanel_up": 500}}));
+        assert!(
+            message.contains("unknown button 'chanel_up' (did you mean channel_up"),
+            "{}",
+            message
+        );
+        let message = error(serde_json::json!({"button_delays": {"up": "fast"}}));
+        assert!(message.contains("button_delays"), "{}", message);
+    }
+
+    fn json_object(text: &str) -> Map<String, Value> {
+        serde_json::from_str(text).unwrap()
+    }
+}
@@ -0,0 +1,395 @@
+//! Encryption of client keys at rest, for configs kept somewhere others can
+//! read them (such as a dotfiles repository).
+//!
+//! Keys are encrypted with ChaCha20 and authenticated with HMAC-SHA256
+//! (encrypt-then-MAC), under a key derived from a passphrase with
+//! PBKDF2-HMAC-SHA256. The passphrase comes from `LGTV_PASSPHRASE`, or is a
+//! random one kept in the system keyring. An encrypted key is stored as
+//! `enc:<source>:<salt>:<nonce, ciphertext and tag>`, all base64.
+//!
+//! The primitives come from OpenSSL with the `native-tls` feature, and
+//! from the plain Rust versions below otherwise; both give the same output.
+
+use crate::error::{LgtvError, Result};
+use crate::keyring;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use imp::{chacha20, hmac_sha256, pbkdf2_sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Prefix of encrypted key values.
+pub const PREFIX: &str = "enc:";
+
+/// Keyring account holding the generated passphrase.
+const KEYRING_ACCOUNT: &str = "_passphrase";
+
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 32;
+
+/// Where the passphrase for encrypted keys comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassphraseSource {
+    /// The `LGTV_PASSPHRASE` environment variable.
+    Env,
+    /// A random passphrase in the system keyring, created when the first
+    /// key is encrypted.
+    Keyring,
+}
+
+impl PassphraseSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            PassphraseSource::Env => "passphrase",
+            PassphraseSource::Keyring => "keyring",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "passphrase" => Ok(PassphraseSource::Env),
+            "keyring" => Ok(PassphraseSource::Keyring),
+            _ => Err(LgtvError::ConfigError(format!(
+                "Unknown key encryption '{}' (expected passphrase or keyring)",
+                name
+            ))),
+        }
+    }
+
+    /// The passphrase, with a new one put in the keyring if it has none and
+    /// `create` is set. Only encrypting creates one: making a new one while
+    /// decrypting would replace the passphrase existing keys need.
+    fn passphrase(self, create: bool) -> Result<String> {
+        match self {
+            PassphraseSource::Env => std::env::var("LGTV_PASSPHRASE")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| {
+                    LgtvError::ConfigError(
+                        "Client keys are encrypted; set LGTV_PASSPHRASE to decrypt them"
+                            .to_string(),
+                    )
+                }),
+            PassphraseSource::Keyring => match keyring::find(KEYRING_ACCOUNT)? {
+                Some(passphrase) => Ok(passphrase),
+                None if create => {
+                    let passphrase = STANDARD.encode(random_bytes::<32>()?);
+                    keyring::store(KEYRING_ACCOUNT, &passphrase)?;
+                    Ok(passphrase)
+                }
+                None => Err(LgtvError::ConfigError(
+                    "Client keys are encrypted, but the keyring no longer has their passphrase"
+                        .to_string(),
+                )),
+            },
+        }
+    }
+}
+
+/// Whether a stored key value is encrypted.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Encrypt a client key for storage.
+pub fn encrypt(plaintext: &str, source: PassphraseSource) -> Result<String> {
+    // One salt per process, so encrypting several keys derives the master
+    // key only once; the nonce still differs for every key
+    static SALT: Mutex<Option<[u8; SALT_LEN]>> = Mutex::new(None);
+    let salt = {
+        let mut salt = SALT.lock().unwrap_or_else(|e| e.into_inner());
+        match *salt {
+            Some(salt) => salt,
+            None => *salt.insert(random_bytes::<SALT_LEN>()?),
+        }
+    };
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let (enc_key, mac_key) = keys(source, &salt, true)?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(plaintext.as_bytes());
+    chacha20(&enc_key, &nonce, &mut sealed[NONCE_LEN..])?;
+    let tag = hmac_sha256(&mac_key, &sealed)?;
+    sealed.extend_from_slice(&tag);
+
+    Ok(format!(
+        "{}{}:{}:{}",
+        PREFIX,
+        source.name(),
+        STANDARD.encode(salt),
+        STANDARD.encode(sealed)
+    ))
+}
+
+/// Decrypt a value written by [`encrypt`].
+pub fn decrypt(value: &str) -> Result<String> {
+    let invalid = || LgtvError::ConfigError("Malformed encrypted key".to_string());
+    let mut parts = value.strip_prefix(PREFIX).ok_or_else(invalid)?.split(':');
+    let (Some(source), Some(salt), Some(sealed), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let source = PassphraseSource::from_name(source)?;
+    let salt = STANDARD.decode(salt).map_err(|_| invalid())?;
+    let mut sealed = STANDARD.decode(sealed).map_err(|_| invalid())?;
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(invalid());
+    }
+
+    let (enc_key, mac_key) = keys(source, &salt, false)?;
+    let tag = sealed.split_off(sealed.len() - TAG_LEN);
+    if !constant_time_eq(&hmac_sha256(&mac_key, &sealed)?, &tag) {
+        return Err(LgtvError::ConfigError(
+            "Could not decrypt the client key: wrong passphrase?".to_string(),
+        ));
+    }
+    let (nonce, ciphertext) = sealed.split_at_mut(NONCE_LEN);
+    let nonce: [u8; NONCE_LEN] = (&*nonce).try_into().map_err(|_| invalid())?;
+    chacha20(&enc_key, &nonce, ciphertext)?;
+    String::from_utf8(ciphertext.to_vec()).map_err(|_| invalid())
+}
+
+/// Master keys by passphrase and salt.
+type DerivedKeys = HashMap<(String, Vec<u8>), [u8; 32]>;
+
+/// The encryption and MAC keys for `salt`. Derivation is deliberately
+/// slow, so the result is remembered for the rest of the process. A
+/// missing keyring passphrase is only created when `encrypting`.
+fn keys(source: PassphraseSource, salt: &[u8], encrypting: bool) -> Result<([u8; 32], [u8; 32])> {
+    static DERIVED: Mutex<Option<DerivedKeys>> = Mutex::new(None);
+
+    let passphrase = source.passphrase(encrypting)?;
+    let mut derived = DERIVED.lock().unwrap_or_else(|e| e.into_inner());
+    let derived = derived.get_or_insert_with(HashMap::new);
+    let id = (passphrase, salt.to_vec());
+    let master = match derived.get(&id) {
+        Some(master) => *master,
+        None => {
+            let master = pbkdf2_sha256(id.0.as_bytes(), salt, PBKDF2_ITERATIONS)?;
+            *derived.entry(id).or_insert(master)
+        }
+    };
+    Ok((
+        hmac_sha256(&master, b"lgtv encryption")?,
+        hmac_sha256(&master, b"lgtv authentication")?,
+    ))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| LgtvError::ConfigError(format!("No random numbers available: {}", e)))?;
+    Ok(bytes)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// HMAC-SHA256, PBKDF2-HMAC-SHA256 and ChaCha20 from OpenSSL, which TLS
+/// links anyway.
+#[cfg(feature = "native-tls")]
+mod imp {
+    use super::NONCE_LEN;
+    use crate::error::{LgtvError, Result};
+    use openssl::error::ErrorStack;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
+    use openssl::symm::{self, Cipher};
+
+    fn crypto_error(e: ErrorStack) -> LgtvError {
+        LgtvError::ConfigError(format!("Encryption failed: {}", e))
+    }
+
+    pub(super) fn hmac_sha256(key: &[u8], message: &[u8]) -> Result<[u8; 32]> {
+        let run = || -> std::result::Result<[u8; 32], ErrorStack> {
+            let key = PKey::hmac(key)?;
+            let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+            signer.update(message)?;
+            let mut tag = [0u8; 32];
+            signer.sign(&mut tag)?;
+            Ok(tag)
+        };
+        run().map_err(crypto_error)
+    }
+
+    /// PBKDF2-HMAC-SHA256 with a 32-byte output.
+    pub(super) fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> Result<[u8; 32]> {
+        let mut output = [0u8; 32];
+        openssl::pkcs5::pbkdf2_hmac(
+            password,
+            salt,
+            iterations as usize,
+            MessageDigest::sha256(),
+            &mut output,
+        )
+        .map_err(crypto_error)?;
+        Ok(output)
+    }
+
+    /// XOR `data` with the ChaCha20 keystream (RFC 8439), counting blocks
+    /// from 1.
+    pub(super) fn chacha20(key: &[u8; 32], nonce: &[u8; NONCE_LEN], data: &mut [u8]) -> Result<()> {
+        // OpenSSL takes the block counter, little-endian, before the nonce
+        let mut iv = 1u32.to_le_bytes().to_vec();
+        iv.extend_from_slice(nonce);
+        let output =
+            symm::encrypt(Cipher::chacha20(), key, Some(&iv), data).map_err(crypto_error)?;
+        data.copy_from_slice(&output);
+        Ok(())
+    }
+}
+
+/// The same primitives in plain Rust, for builds without OpenSSL.
+#[cfg(not(feature = "native-tls"))]
+mod imp {
+    use super::NONCE_LEN;
+    use crate::error::Result;
+    use crate::tls::sha256;
+
+    pub(super) fn hmac_sha256(key: &[u8], message: &[u8]) -> Result<[u8; 32]> {
+        let mut block = [0u8; 64];
+        if key.len() > 64 {
+            block[..32].copy_from_slice(&sha256(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+        inner.extend_from_slice(message);
+        let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+        outer.extend_from_slice(&sha256(&inner));
+        Ok(sha256(&outer))
+    }
+
+    /// PBKDF2-HMAC-SHA256 with a single 32-byte output block.
+    pub(super) fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> Result<[u8; 32]> {
+        let mut first = salt.to_vec();
+        first.extend_from_slice(&1u32.to_be_bytes());
+        let mut u = hmac_sha256(password, &first)?;
+        let mut output = u;
+        for _ in 1..iterations {
+            u = hmac_sha256(password, &u)?;
+            for (out, byte) in output.iter_mut().zip(u) {
+                *out ^= byte;
+            }
+        }
+        Ok(output)
+    }
+
+    /// XOR `data` with the ChaCha20 keystream (RFC 8439), counting blocks
+    /// from 1.
+    pub(super) fn chacha20(key: &[u8; 32], nonce: &[u8; NONCE_LEN], data: &mut [u8]) -> Result<()> {
+        let word = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mut state = [0u32; 16];
+        state[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+        for i in 0..8 {
+            state[4 + i] = word(&key[i * 4..]);
+        }
+        for i in 0..3 {
+            state[13 + i] = word(&nonce[i * 4..]);
+        }
+
+        for (counter, chunk) in data.chunks_mut(64).enumerate() {
+            state[12] = counter as u32 + 1;
+            let mut x = state;
+            for _ in 0..10 {
+                for (a, b, c, d) in [
+                    (0, 4, 8, 12),
+                    (1, 5, 9, 13),
+                    (2, 6, 10, 14),
+                    (3, 7, 11, 15),
+                    (0, 5, 10, 15),
+                    (1, 6, 11, 12),
+                    (2, 7, 8, 13),
+                    (3, 4, 9, 14),
+                ] {
+                    x[a] = x[a].wrapping_add(x[b]);
+                    x[d] = (x[d] ^ x[a]).rotate_left(16);
+                    x[c] = x[c].wrapping_add(x[d]);
+                    x[b] = (x[b] ^ x[c]).rotate_left(12);
+                    x[a] = x[a].wrapping_add(x[b]);
+                    x[d] = (x[d] ^ x[a]).rotate_left(8);
+                    x[c] = x[c].wrapping_add(x[d]);
+                    x[b] = (x[b] ^ x[c]).rotate_left(7);
+                }
+            }
+            let keystream: Vec<u8> = x
+                .iter()
+                .zip(state)
+                .flat_map(|(a, b)| a.wrapping_add(b).to_le_bytes())
+                .collect();
+            for (byte, k) in chunk.iter_mut().zip(keystream) {
+                *byte ^= k;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // RFC 4231 test cases 1, 2 and 6
+    #[test]
+    fn hmac_sha256_rfc4231() {
+        assert_eq!(
+            hex(&hmac_sha256(&[0x0b; 20], b"Hi There").unwrap()),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?").unwrap()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )
+            .unwrap()),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    // RFC 7914 section 11, first 32 bytes of each
+    #[test]
+    fn pbkdf2_sha256_rfc7914() {
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"passwd", b"salt", 1).unwrap()),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"Password", b"NaCl", 80000).unwrap()),
+            "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56"
+        );
+    }
+
+    // RFC 8439 section 2.4.2
+    #[test]
+    fn chacha20_rfc8439() {
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+            one tip for the future, sunscreen would be it.";
+        let mut data = plaintext.to_vec();
+        chacha20(&key, &nonce, &mut data).unwrap();
+        assert_eq!(
+            hex(&data),
+            "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b\
+             f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8\
+             07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736\
+             5af90bbf74a35be6b40b8eedf2785e42874d"
+        );
+        chacha20(&key, &nonce, &mut data).unwrap();
+        assert_eq!(data, plaintext);
+    }
+}
@@ -1,17 +1,174 @@
+use crate::config::{self, TvConfigEntry};
 use crate::error::{LgtvError, Result};
+use crate::rate::{RateLimit, RateLimiter};
 use crate::remote::LgtvRemote;
-use futures_util::SinkExt;
-use tokio::net::TcpStream;
-use tokio::time::{sleep, Duration};
-use tokio_tungstenite::{
-    connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
-};
+use crate::{tls, trace};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{interval, sleep, Duration, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+/// How often to ping the pointer socket so the TV doesn't drop it as idle.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Pause between keypad digits; shorter gaps make some TVs drop digits.
+const NUMBER_KEY_DELAY: Duration = Duration::from_millis(300);
+
+type Outgoing = (Message, oneshot::Sender<bool>);
+
+/// Button names accepted by [`LgtvCursor::execute`].
+const BUTTON_NAMES: [&str; 23] = [
+    "up",
+    "down",
+    "left",
+    "right",
+    "click",
+    "back",
+    "enter",
+    "home",
+    "exit",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "channel_up",
+    "channel_down",
+    "volume_up",
+    "volume_down",
+    "play",
+    "pause",
+    "stop",
+    "rewind",
+    "fast_forward",
+    "asterisk",
+];
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Closest valid button names to `name`, best match first.
+fn suggest_buttons(name: &str) -> Vec<String> {
+    let name = name.to_lowercase().replace('-', "_");
+    let mut candidates: Vec<(usize, &str)> = BUTTON_NAMES
+        .iter()
+        .map(|b| (edit_distance(&name, b), *b))
+        .filter(|(d, b)| *d <= 2.max(b.len() / 3))
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(3)
+        .map(|(_, b)| b.to_string())
+        .collect()
+}
+
+/// Pauses to leave after each button press in [`LgtvCursor::execute`].
+///
+/// Some TVs drop rapid presses of certain buttons (typically channel
+/// up/down) while handling arrow keys fine, so delays can be set per button.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<String, u64>", into = "BTreeMap<String, u64>")]
+pub struct ButtonTiming {
+    default: Duration,
+    per_button: HashMap<String, Duration>,
+}
+
+impl ButtonTiming {
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            per_button: HashMap::new(),
+        }
+    }
+
+    pub fn with_delay(mut self, button: &str, delay: Duration) -> Self {
+        self.per_button.insert(button.to_string(), delay);
+        self
+    }
+
+    pub fn delay_for(&self, button: &str) -> Duration {
+        self.per_button.get(button).copied().unwrap_or(self.default)
+    }
+}
+
+/// `button_delays` as written in the config file: milliseconds per button
+/// name, with `default` for the rest, e.g. `{"default": 100, "channel_up": 500}`.
+impl TryFrom<BTreeMap<String, u64>> for ButtonTiming {
+    type Error = String;
+
+    fn try_from(delays: BTreeMap<String, u64>) -> std::result::Result<Self, String> {
+        let mut timing = Self::default();
+        for (button, ms) in delays {
+            if button == "default" {
+                timing.default = Duration::from_millis(ms);
+            } else if BUTTON_NAMES.contains(&button.as_str()) {
+                timing = timing.with_delay(&button, Duration::from_millis(ms));
+            } else {
+                let suggestions = suggest_buttons(&button);
+                return Err(if suggestions.is_empty() {
+                    format!("unknown button '{}'", button)
+                } else {
+                    format!(
+                        "unknown button '{}' (did you mean {}?)",
+                        button,
+                        suggestions.join(", ")
+                    )
+                });
+            }
+        }
+        Ok(timing)
+    }
+}
+
+impl From<ButtonTiming> for BTreeMap<String, u64> {
+    fn from(timing: ButtonTiming) -> Self {
+        let ms = |delay: Duration| u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+        let mut delays: Self = timing
+            .per_button
+            .into_iter()
+            .map(|(button, delay)| (button, ms(delay)))
+            .collect();
+        delays.insert("default".to_string(), ms(timing.default));
+        delays
+    }
+}
+
+impl Default for ButtonTiming {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100))
+    }
+}
+
+/// Outcome of a single press in [`LgtvCursor::execute`].
+#[derive(Debug)]
+pub struct ButtonResult {
+    pub button: String,
+    pub result: Result<()>,
+}
 
 pub struct LgtvCursor {
-    websocket: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    remote: LgtvRemote,
+    ws_tx: Option<mpsc::Sender<Outgoing>>,
+    timing: ButtonTiming,
+    rate: Option<RateLimiter>,
 }
 
 impl LgtvCursor {
+    #[deprecated(note = "use LgtvCursor::from_remote with LgtvRemote::builder()")]
     pub async fn new(
         name: &str,
         ip: Option<&str>,
@@ -21,126 +178,333 @@ impl LgtvCursor {
         ssl: bool,
     ) -> Result<Self> {
         // Create a remote to get the cursor socket
-        let mut remote = LgtvRemote::new(name, ip, mac, key, hostname, ssl)?;
+        #[allow(deprecated)]
+        let remote = LgtvRemote::new(name, ip, mac, key, hostname, ssl)?;
+        Self::from_remote(remote).await
+    }
+
+    /// Connect to the TV stored as `name`, with the connection options and
+    /// `button_delays` of its config entry.
+    pub async fn from_entry(name: &str, entry: &TvConfigEntry) -> Result<Self> {
+        let mut cursor = Self::from_remote(LgtvRemote::from_entry(name, entry)?).await?;
+        if let Some(timing) = &entry.button_delays {
+            cursor.set_timing(timing.clone());
+        }
+        Ok(cursor)
+    }
+
+    /// Connect to the TV stored as `name` in the config file, or the
+    /// default TV for `None`, as [`config::load_entry`] finds it.
+    pub async fn from_config<'a>(name: impl Into<Option<&'a str>>) -> Result<Self> {
+        let (name, entry) = config::load_entry(name.into())?;
+        Self::from_entry(&name, &entry).await
+    }
+
+    /// Open a pointer socket through an already configured remote. The
+    /// remote is connected (or reconnected) as part of this.
+    pub async fn from_remote(remote: LgtvRemote) -> Result<Self> {
         remote.connect().await?;
+        Self::from_connected(remote).await
+    }
+
+    /// Open a pointer socket through a remote that is already connected,
+    /// without registering with the TV again.
+    pub async fn from_connected(remote: LgtvRemote) -> Result<Self> {
+        let mut cursor = Self {
+            rate: remote.rate_limit().map(RateLimiter::new),
+            remote,
+            ws_tx: None,
+            timing: ButtonTiming::default(),
+        };
+        cursor.open_socket().await?;
+
+        Ok(cursor)
+    }
+
+    /// The remote the pointer socket was opened through, for sending
+    /// commands over the same connection.
+    pub fn remote_mut(&mut self) -> &mut LgtvRemote {
+        &mut self.remote
+    }
 
-        // Get cursor socket
-        let mut socket_path = None;
-        let mut rx = remote
-            .send_command(
-                "request",
+    /// Replace the delays used between presses in [`execute`](Self::execute).
+    pub fn set_timing(&mut self, timing: ButtonTiming) {
+        self.timing = timing;
+    }
+
+    /// Space out what is sent over the pointer socket by `limit` instead
+    /// of the remote's [`rate_limit`](LgtvRemote::rate_limit), or not at all.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate = limit.map(RateLimiter::new);
+    }
+
+    /// Ask the TV for a pointer socket, within the remote's command
+    /// timeout.
+    async fn socket_path(&mut self) -> Result<String> {
+        let payload: Value = self
+            .remote
+            .request(
                 "ssap://com.webos.service.networkinput/getPointerInputSocket",
                 None,
-                None,
             )
             .await?;
+        payload
+            .get("socketPath")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| LgtvError::CommandError("Failed to get cursor socket path".to_string()))
+    }
 
-        if let Some(response) = rx.recv().await {
-            if let Some(payload) = response.get("payload") {
-                if let Some(path) = payload.get("socketPath").and_then(|v| v.as_str()) {
-                    socket_path = Some(path.to_string());
+    async fn open_socket(&mut self) -> Result<()> {
+        let socket_path = self.socket_path().await?;
+        let (websocket, _) = tls::connect(&socket_path, &self.remote.tls_options()).await?;
+        let (mut ws_writer, mut ws_reader) = websocket.split();
+        let peer = socket_path.clone();
+
+        let (tx, mut rx) = mpsc::channel::<Outgoing>(32);
+        self.ws_tx = Some(tx);
+        // Dropped by the reader when the TV closes the socket, which stops
+        // the writer, so the next press opens a new socket straight away
+        let (reader_done, mut reader_gone) = oneshot::channel::<()>();
+
+        // Writer task, also responsible for keepalive pings
+        tokio::spawn(async move {
+            let mut keepalive = interval(KEEPALIVE_INTERVAL);
+            keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    outgoing = rx.recv() => match outgoing {
+                        Some((msg, ack)) => {
+                            trace::sent(&peer, &msg);
+                            let sent = ws_writer.send(msg).await.is_ok();
+                            let _ = ack.send(sent);
+                            if !sent {
+                                break;
+                            }
+                        }
+                        None => {
+                            let _ = ws_writer.close().await;
+                            break;
+                        }
+                    },
+                    _ = keepalive.tick() => {
+                        let ping = Message::Ping(Vec::new());
+                        trace::sent(&peer, &ping);
+                        if ws_writer.send(ping).await.is_err() {
+                            log::debug!("Pointer socket keepalive failed");
+                            break;
+                        }
+                    }
+                    _ = &mut reader_gone => {
+                        log::debug!("Pointer socket closed by the TV");
+                        break;
+                    }
                 }
             }
-        }
+        });
+
+        // Reader task, drains pongs and notices when the TV closes the socket
+        tokio::spawn(async move {
+            let _reader_done = reader_done;
+            while let Some(msg) = ws_reader.next().await {
+                if let Ok(msg) = &msg {
+                    trace::received(&socket_path, msg);
+                }
+                match msg {
+                    Ok(Message::Close(_)) => break,
+                    Err(e) => {
+                        log::debug!("Pointer socket error: {}", e);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        });
 
-        let socket_path = socket_path.ok_or_else(|| {
-            LgtvError::CommandError("Failed to get cursor socket path".to_string())
-        })?;
+        Ok(())
+    }
 
-        // Connect to cursor socket
-        let (websocket, _) = connect_async(socket_path).await?;
+    /// Re-fetch the pointer socket, re-registering with the TV if the main
+    /// connection has gone stale as well.
+    async fn reconnect(&mut self) -> Result<()> {
+        self.ws_tx = None;
+        if self.open_socket().await.is_ok() {
+            return Ok(());
+        }
+        self.remote.connect().await?;
+        self.open_socket().await
+    }
 
-        Ok(Self {
-            websocket: Some(websocket),
-        })
+    async fn try_send(&self, button_data: &str) -> bool {
+        let tx = match &self.ws_tx {
+            Some(tx) => tx,
+            None => return false,
+        };
+        let (ack_tx, ack_rx) = oneshot::channel();
+        if tx
+            .send((Message::Text(button_data.to_string()), ack_tx))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+        ack_rx.await.unwrap_or(false)
     }
 
     async fn send_button(&mut self, button_data: &str) -> Result<()> {
-        if let Some(ws) = &mut self.websocket {
-            ws.send(Message::Text(button_data.to_string())).await?;
+        if let Some(rate) = &self.rate {
+            rate.wait().await;
+        }
+        if self.try_send(button_data).await {
+            return Ok(());
+        }
+
+        log::debug!("Pointer socket send failed, reconnecting");
+        self.reconnect().await?;
+        if self.try_send(button_data).await {
             Ok(())
         } else {
             Err(LgtvError::ConnectionError(
-                "WebSocket not connected".to_string(),
+                "Failed to send to pointer socket".to_string(),
             ))
         }
     }
 
-    pub async fn execute(&mut self, buttons: Vec<&str>) -> Result<()> {
-        if buttons.is_empty() {
-            let possible_buttons = self.list_possible_buttons();
-            println!(
-                "Add button presses to perform. Possible options: {}",
-                possible_buttons.join(", ")
-            );
-            return Ok(());
+    /// Press each named button in order, pausing between presses according
+    /// to the configured [`ButtonTiming`].
+    ///
+    /// Unknown names are rejected before anything is sent. Send failures
+    /// don't stop the sequence; they are reported in the returned results.
+    pub async fn execute(&mut self, buttons: Vec<&str>) -> Result<Vec<ButtonResult>> {
+        if let Some(unknown) = buttons.iter().find(|b| !BUTTON_NAMES.contains(b)) {
+            return Err(LgtvError::UnknownButton {
+                name: unknown.to_string(),
+                suggestions: suggest_buttons(unknown),
+            });
         }
 
+        let mut results = Vec::with_capacity(buttons.len());
         for (i, button) in buttons.iter().enumerate() {
-            match *button {
-                "up" => self.up().await?,
-                "down" => self.down().await?,
-                "left" => self.left().await?,
-                "right" => self.right().await?,
-                "click" => self.click().await?,
-                "back" => self.back().await?,
-                "enter" => self.enter().await?,
-                "home" => self.home().await?,
-                "exit" => self.exit().await?,
-                "red" => self.red().await?,
-                "green" => self.green().await?,
-                "yellow" => self.yellow().await?,
-                "blue" => self.blue().await?,
-                "channel_up" => self.channel_up().await?,
-                "channel_down" => self.channel_down().await?,
-                "volume_up" => self.volume_up().await?,
-                "volume_down" => self.volume_down().await?,
-                "play" => self.play().await?,
-                "pause" => self.pause().await?,
-                "stop" => self.stop().await?,
-                "rewind" => self.rewind().await?,
-                "fast_forward" => self.fast_forward().await?,
-                "asterisk" => self.asterisk().await?,
-                _ => {
-                    println!("{} is not a possible button press, skipped", button);
-                    continue;
-                }
+            let result = match *button {
+                "up" => self.up().await,
+                "down" => self.down().await,
+                "left" => self.left().await,
+                "right" => self.right().await,
+                "click" => self.click().await,
+                "back" => self.back().await,
+                "enter" => self.enter().await,
+                "home" => self.home().await,
+                "exit" => self.exit().await,
+                "red" => self.red().await,
+                "green" => self.green().await,
+                "yellow" => self.yellow().await,
+                "blue" => self.blue().await,
+                "channel_up" => self.channel_up().await,
+                "channel_down" => self.channel_down().await,
+                "volume_up" => self.volume_up().await,
+                "volume_down" => self.volume_down().await,
+                "play" => self.play().await,
+                "pause" => self.pause().await,
+                "stop" => self.stop().await,
+                "rewind" => self.rewind().await,
+                "fast_forward" => self.fast_forward().await,
+                "asterisk" => self.asterisk().await,
+                _ => unreachable!("button names are validated above"),
+            };
+            results.push(ButtonResult {
+                button: button.to_string(),
+                result,
+            });
+
+            if i + 1 < buttons.len() {
+                sleep(self.timing.delay_for(button)).await;
             }
+        }
+
+        Ok(results)
+    }
+
+    /// Button names accepted by [`execute`](Self::execute).
+    pub fn possible_buttons() -> &'static [&'static str] {
+        &BUTTON_NAMES
+    }
 
-            if i != 0 {
-                sleep(Duration::from_millis(100)).await;
+    /// Send an arbitrary pointer-socket message made of a `type` and
+    /// `key:value` fields, for message types and button names this crate
+    /// doesn't wrap yet.
+    pub async fn send_raw(&mut self, msg_type: &str, fields: &[(&str, &str)]) -> Result<()> {
+        if msg_type.is_empty() || msg_type.contains('\n') {
+            return Err(LgtvError::CommandError(format!(
+                "Invalid pointer message type: {:?}",
+                msg_type
+            )));
+        }
+
+        let mut message = format!("type:{}\n", msg_type);
+        for (key, value) in fields {
+            if key.is_empty() || key.contains([':', '\n']) || value.contains('\n') {
+                return Err(LgtvError::CommandError(format!(
+                    "Invalid pointer message field: {}:{}",
+                    key, value
+                )));
             }
+            message.push_str(&format!("{}:{}\n", key, value));
         }
+        message.push('\n');
 
-        Ok(())
+        self.send_button(&message).await
+    }
+
+    /// Press a button by its pointer-socket name (e.g. `UP`, `HOME`, `5`).
+    pub async fn button(&mut self, name: &str) -> Result<()> {
+        self.send_raw("button", &[("name", name)]).await
+    }
+
+    /// Move the pointer relative to its current position.
+    pub async fn move_by(&mut self, dx: i32, dy: i32) -> Result<()> {
+        self.send_move(dx, dy, false).await
+    }
+
+    async fn send_move(&mut self, dx: i32, dy: i32, down: bool) -> Result<()> {
+        self.send_raw(
+            "move",
+            &[
+                ("dx", &dx.to_string()),
+                ("dy", &dy.to_string()),
+                ("down", if down { "1" } else { "0" }),
+            ],
+        )
+        .await
+    }
+
+    /// Press, move the pointer by `(dx, dy)` while held, then release.
+    ///
+    /// The motion is split into small steps so apps that track the pointer
+    /// (lists, maps, sliders) see a continuous drag rather than a jump.
+    pub async fn drag(&mut self, dx: i32, dy: i32) -> Result<()> {
+        const STEP: i32 = 10;
+        let steps = (dx.abs().max(dy.abs()) / STEP).max(1);
+
+        self.send_move(0, 0, true).await?;
+        let (mut moved_x, mut moved_y) = (0, 0);
+        for i in 1..=steps {
+            let x = dx * i / steps;
+            let y = dy * i / steps;
+            self.send_move(x - moved_x, y - moved_y, true).await?;
+            moved_x = x;
+            moved_y = y;
+            sleep(Duration::from_millis(10)).await;
+        }
+        self.send_move(0, 0, false).await
     }
 
-    fn list_possible_buttons(&self) -> Vec<String> {
-        vec![
-            "up".to_string(),
-            "down".to_string(),
-            "left".to_string(),
-            "right".to_string(),
-            "click".to_string(),
-            "back".to_string(),
-            "enter".to_string(),
-            "home".to_string(),
-            "exit".to_string(),
-            "red".to_string(),
-            "green".to_string(),
-            "yellow".to_string(),
-            "blue".to_string(),
-            "channel_up".to_string(),
-            "channel_down".to_string(),
-            "volume_up".to_string(),
-            "volume_down".to_string(),
-            "play".to_string(),
-            "pause".to_string(),
-            "stop".to_string(),
-            "rewind".to_string(),
-            "fast_forward".to_string(),
-            "asterisk".to_string(),
-        ]
+    /// Scroll the view under the pointer.
+    pub async fn scroll(&mut self, dx: i32, dy: i32) -> Result<()> {
+        self.send_raw(
+            "scroll",
+            &[("dx", &dx.to_string()), ("dy", &dy.to_string())],
+        )
+        .await
     }
 
     pub async fn up(&mut self) -> Result<()> {
@@ -234,4 +598,34 @@ impl LgtvCursor {
     pub async fn asterisk(&mut self) -> Result<()> {
         self.send_button("type:button\nname:ASTERISK\n\n").await
     }
+
+    /// Enter a number on the keypad followed by ENTER, e.g. to tune to a
+    /// channel on firmware without `openChannel`.
+    pub async fn enter_number(&mut self, number: u32) -> Result<()> {
+        for digit in number.to_string().chars() {
+            self.button(&digit.to_string()).await?;
+            sleep(NUMBER_KEY_DELAY).await;
+        }
+        self.enter().await
+    }
+
+    /// Type digits by pressing number buttons, for input fields that don't
+    /// accept remote keyboard input. Anything but digits is refused before
+    /// a button is pressed. The on-screen keyboard isn't driven, as its
+    /// layout differs between webOS versions and can't be read back.
+    pub async fn type_text(&mut self, text: &str) -> Result<()> {
+        if let Some(c) = text.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(LgtvError::CommandError(format!(
+                "Cannot type '{}': without the TV's remote keyboard only digits can be typed",
+                c
+            )));
+        }
+
+        for digit in text.chars() {
+            self.button(&digit.to_string()).await?;
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(())
+    }
 }
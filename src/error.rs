@@ -1,4 +1,7 @@
+use crate::remote::ConnectionState;
+use serde_json::Value;
 use std::net::AddrParseError;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,20 +21,158 @@ pub enum LgtvError {
     #[error("MAC address error: {0}")]
     MacAddressError(String),
 
+    #[error("MAC address is required for power on")]
+    MacAddressRequired,
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
     #[error("Authentication error: {0}")]
     AuthError(String),
 
+    /// The TV refused the stored client key; it has to be paired again.
+    #[error("Authentication error: the TV refused the client key")]
+    KeyRejected,
+
+    #[error("Pairing requires confirmation on the TV")]
+    ConfirmationRequired,
+
+    #[error("Pairing denied by the TV: {0}")]
+    PairingDenied(String),
+
+    #[error("Pairing timed out after {} seconds", .0.as_secs())]
+    PairingTimeout(Duration),
+
     #[error("Connection error: {0}")]
     ConnectionError(String),
 
+    #[error("TV certificate {actual} does not match the pinned fingerprint {expected}")]
+    CertificateMismatch { expected: String, actual: String },
+
     #[error("No TV found with name: {0}")]
     TvNotFound(String),
 
     #[error("Command error: {0}")]
     CommandError(String),
+
+    /// The TV answered with an error, or with `returnValue: false`.
+    /// `code` is its `errorCode` (or the number an error message starts
+    /// with), when it gave one.
+    #[error("The TV refused the command: {text}")]
+    TvError { code: Option<i64>, text: String },
+
+    #[error("No answer from the TV within {} seconds", .0.as_secs_f32())]
+    Timeout(Duration),
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error("The connection to the TV is {0}, not ready for commands")]
+    NotConnected(ConnectionState),
+
+    /// Too many commands are waiting for the connection, see
+    /// [`QueuePolicy`](crate::QueuePolicy).
+    #[error("{0} commands are already waiting for the TV to be connected")]
+    QueueFull(usize),
+
+    #[error("Unknown button: {name}{}", did_you_mean(.suggestions))]
+    UnknownButton {
+        name: String,
+        suggestions: Vec<String>,
+    },
+}
+
+/// What kind of failure a [`LgtvError::TvError`] is, as far as webOS tells
+/// from its error code and text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TvErrorKind {
+    /// 401: the client key wasn't granted the permission this needs, see
+    /// [`crate::payload::PermissionSet`].
+    InsufficientPermissions,
+    /// 404: this firmware has no such service or method.
+    UnknownMethod,
+    /// No installed app has the ID given.
+    AppNotFound,
+    /// The payload was missing something or had a value the TV rejected.
+    InvalidParameter,
+    /// The TV knows the request but can't do it, e.g. on this model or in
+    /// its current state.
+    NotSupported,
+    Other,
+}
+
+impl TvErrorKind {
+    /// Classify an error the TV reported with `code` and `text`.
+    pub fn from_response(code: Option<i64>, text: &str) -> Self {
+        let text = text.to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|w| text.contains(w));
+        match code {
+            Some(401) => TvErrorKind::InsufficientPermissions,
+            Some(404) if mentions(&["service", "method"]) => TvErrorKind::UnknownMethod,
+            _ if mentions(&["insufficient permission", "permission denied"]) => {
+                TvErrorKind::InsufficientPermissions
+            }
+            _ if mentions(&["no such service", "unknown method"]) => TvErrorKind::UnknownMethod,
+            _ if mentions(&["app not found", "not found app", "application not found"]) => {
+                TvErrorKind::AppNotFound
+            }
+            _ if mentions(&["invalid", "missing", "required"]) => TvErrorKind::InvalidParameter,
+            _ if mentions(&["not supported", "unsupported"]) => TvErrorKind::NotSupported,
+            _ => TvErrorKind::Other,
+        }
+    }
+}
+
+impl LgtvError {
+    /// What kind of refusal this is, if the TV refused a command.
+    pub fn tv_error_kind(&self) -> Option<TvErrorKind> {
+        match self {
+            LgtvError::TvError { code, text } => Some(TvErrorKind::from_response(*code, text)),
+            _ => None,
+        }
+    }
+
+    /// The error a message from the TV reports, if any: an `error`
+    /// response such as `401 insufficient permissions`, or a payload with
+    /// `returnValue: false` and usually an `errorCode` and `errorText`.
+    pub fn from_response(message: &Value) -> Option<Self> {
+        if message.get("type").and_then(Value::as_str) == Some("error") {
+            let text = message
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            let code = text.split_whitespace().next().and_then(|c| c.parse().ok());
+            return Some(LgtvError::TvError {
+                code,
+                text: text.to_string(),
+            });
+        }
+        let payload = message.get("payload")?;
+        if payload.get("returnValue") != Some(&Value::Bool(false)) {
+            return None;
+        }
+        let code = payload.get("errorCode").and_then(|code| match code {
+            Value::String(code) => code.trim().parse().ok(),
+            code => code.as_i64(),
+        });
+        let text = payload
+            .get("errorText")
+            .and_then(Value::as_str)
+            .unwrap_or("it reported a failure");
+        Some(LgtvError::TvError {
+            code,
+            text: text.to_string(),
+        })
+    }
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(" (did you mean {}?)", suggestions.join(", "))
+    }
 }
 
 impl From<tokio_tungstenite::tungstenite::Error> for LgtvError {
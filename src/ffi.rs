@@ -0,0 +1,346 @@
+//! A C interface to [`LgtvRemote`], for bindings in other languages and
+//! for home-theater software written in C or C++. `include/lgtv.h`
+//! declares it; build the shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+//!
+//! A handle from [`lgtv_connect`] owns its connection and the threads that
+//! run it until [`lgtv_free`]. Functions returning `int` give 0 on success
+//! and -1 on failure, with the reason from [`lgtv_last_error`]. Strings
+//! passed in are UTF-8 and only borrowed for the call; strings handed out
+//! must be released with [`lgtv_string_free`].
+//!
+//! Subscription callbacks run on a handle's own threads, which can't wait
+//! for the TV: calls made from a callback fail instead of blocking.
+
+use crate::cursor::LgtvCursor;
+use crate::error::{LgtvError, Result};
+use crate::remote::LgtvRemote;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::future::Future;
+use std::ptr;
+use tokio::runtime::Runtime;
+
+/// A connected TV, opaque to C.
+pub struct LgtvHandle {
+    // Dropped before the runtime its tasks run on
+    remote: LgtvRemote,
+    cursor: Option<LgtvCursor>,
+    runtime: Runtime,
+}
+
+/// Called with each message a subscription receives, as JSON, on one of
+/// the handle's threads. `message` is only valid during the call.
+pub type LgtvCallback = extern "C" fn(message: *const c_char, user_data: *mut c_void);
+
+/// The caller's pointer, handed back to its callback from another thread.
+struct UserData(*mut c_void);
+
+// The caller vouches for its pointer when subscribing
+unsafe impl Send for UserData {}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(e: &LgtvError) {
+    let message = CString::new(e.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// 0 for success, or -1 with the error kept for [`lgtv_last_error`].
+fn status<T>(result: Result<T>) -> c_int {
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            set_error(&e);
+            -1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be null or a NUL-terminated string that outlives `'a`.
+unsafe fn str_arg<'a>(ptr: *const c_char, what: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(LgtvError::CommandError(format!("{} is required", what)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| LgtvError::CommandError(format!("{} is not UTF-8", what)))
+}
+
+/// # Safety
+/// `handle` must be null or a live handle from [`lgtv_connect`].
+unsafe fn handle_arg<'a>(handle: *mut LgtvHandle) -> Result<&'a mut LgtvHandle> {
+    handle
+        .as_mut()
+        .ok_or_else(|| LgtvError::CommandError("handle is null".to_string()))
+}
+
+/// Wait for `future` on `runtime`. Fails on one of the library's own
+/// threads, i.e. from a subscription callback, where blocking would
+/// panic.
+fn block_on<T>(runtime: &Runtime, future: impl Future<Output = Result<T>>) -> Result<T> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(callback_error());
+    }
+    runtime.block_on(future)
+}
+
+fn callback_error() -> LgtvError {
+    LgtvError::CommandError(
+        "lgtv functions can't be called from a subscription callback".to_string(),
+    )
+}
+
+/// Run `command` on the handle's remote and report how it went.
+///
+/// # Safety
+/// As for [`handle_arg`].
+unsafe fn run<F, T>(handle: *mut LgtvHandle, command: impl FnOnce(LgtvRemote) -> F) -> c_int
+where
+    F: Future<Output = Result<T>>,
+{
+    status(handle_arg(handle).and_then(|handle| {
+        let remote = handle.remote.clone();
+        block_on(&handle.runtime, command(remote))
+    }))
+}
+
+/// The reason the last call on this thread failed, or null. Valid until
+/// the next call that fails on the same thread.
+#[no_mangle]
+pub extern "C" fn lgtv_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Connect and register with the TV at `host` (IP or hostname) using a
+/// client key from pairing. Returns null on failure.
+///
+/// # Safety
+/// `host` and `client_key` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_connect(
+    host: *const c_char,
+    client_key: *const c_char,
+    ssl: bool,
+) -> *mut LgtvHandle {
+    let connect = || -> Result<LgtvHandle> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(callback_error());
+        }
+        let host = str_arg(host, "host")?;
+        let client_key = str_arg(client_key, "client key")?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let mut builder = LgtvRemote::builder().client_key(client_key).ssl(ssl);
+        builder = if host.parse::<std::net::IpAddr>().is_ok() {
+            builder.ip(host)
+        } else {
+            builder.hostname(host)
+        };
+        let remote = builder.build()?;
+        runtime.block_on(remote.connect())?;
+        Ok(LgtvHandle {
+            remote,
+            cursor: None,
+            runtime,
+        })
+    };
+    match connect() {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(e) => {
+            set_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Close the connection and release the handle. Null is ignored. From a
+/// subscription callback nothing is freed and the handle stays usable, with
+/// the reason kept for [`lgtv_last_error`].
+///
+/// # Safety
+/// `handle` must be null or a handle from [`lgtv_connect`] that hasn't been
+/// freed, and no other call may be using it.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_free(handle: *mut LgtvHandle) {
+    if handle.is_null() {
+        return;
+    }
+    if tokio::runtime::Handle::try_current().is_ok() {
+        set_error(&callback_error());
+        return;
+    }
+    let handle = Box::from_raw(handle);
+    handle.runtime.block_on(handle.remote.close());
+}
+
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_power_off(handle: *mut LgtvHandle) -> c_int {
+    run(handle, |remote| async move { remote.off().await })
+}
+
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_set_volume(handle: *mut LgtvHandle, volume: c_int) -> c_int {
+    let Ok(volume) = u32::try_from(volume) else {
+        return status::<()>(Err(LgtvError::CommandError(
+            "volume must not be negative".to_string(),
+        )));
+    };
+    run(
+        handle,
+        |remote| async move { remote.set_volume(volume).await },
+    )
+}
+
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_volume_up(handle: *mut LgtvHandle) -> c_int {
+    run(handle, |remote| async move { remote.volume_up().await })
+}
+
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_volume_down(handle: *mut LgtvHandle) -> c_int {
+    run(handle, |remote| async move { remote.volume_down().await })
+}
+
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_mute(handle: *mut LgtvHandle, muted: bool) -> c_int {
+    run(handle, |remote| async move { remote.mute(muted).await })
+}
+
+/// Launch an app by ID, e.g. `netflix`.
+///
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`], and `app_id` a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_launch_app(handle: *mut LgtvHandle, app_id: *const c_char) -> c_int {
+    let app_id = match str_arg(app_id, "app ID") {
+        Ok(app_id) => app_id.to_string(),
+        Err(e) => return status::<()>(Err(e)),
+    };
+    run(
+        handle,
+        |remote| async move { remote.start_app(&app_id).await },
+    )
+}
+
+/// Press a remote button by name, e.g. `home` or `volumeup`, opening the
+/// pointer socket on first use.
+///
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`], and `button` a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_send_button(handle: *mut LgtvHandle, button: *const c_char) -> c_int {
+    status((|| {
+        let handle = handle_arg(handle)?;
+        let button = str_arg(button, "button")?;
+        block_on(&handle.runtime, async {
+            if handle.cursor.is_none() {
+                let cursor = LgtvCursor::from_connected(handle.remote.clone()).await?;
+                handle.cursor = Some(cursor);
+            }
+            match &mut handle.cursor {
+                Some(cursor) => cursor.button(button).await,
+                None => Ok(()),
+            }
+        })
+    })())
+}
+
+/// Send any SSAP request, e.g. `ssap://audio/getVolume`, with an optional
+/// JSON payload (or null). Returns the answer's payload as JSON, to be
+/// freed with [`lgtv_string_free`], or null on failure.
+///
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`], `uri` a
+/// NUL-terminated string and `payload` null or one.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_request(
+    handle: *mut LgtvHandle,
+    uri: *const c_char,
+    payload: *const c_char,
+) -> *mut c_char {
+    let request = || -> Result<CString> {
+        let handle = handle_arg(handle)?;
+        let uri = str_arg(uri, "URI")?;
+        let payload = if payload.is_null() {
+            None
+        } else {
+            Some(serde_json::from_str::<Value>(str_arg(payload, "payload")?)?)
+        };
+        let response: Value = block_on(&handle.runtime, handle.remote.request(uri, payload))?;
+        CString::new(response.to_string())
+            .map_err(|e| LgtvError::CommandError(format!("Answer contains NUL: {}", e)))
+    };
+    match request() {
+        Ok(response) => response.into_raw(),
+        Err(e) => {
+            set_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Subscribe to `uri`, e.g. `ssap://audio/getVolume`, calling `callback`
+/// with each message as JSON until the connection ends or the handle is
+/// freed.
+///
+/// # Safety
+/// `handle` must be a live handle from [`lgtv_connect`] and `uri` a
+/// NUL-terminated string. `callback` may be called from another thread,
+/// with `user_data`, which must stay valid until the handle is freed.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_subscribe(
+    handle: *mut LgtvHandle,
+    uri: *const c_char,
+    callback: LgtvCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let user_data = UserData(user_data);
+    status((|| {
+        let handle = handle_arg(handle)?;
+        let uri = str_arg(uri, "URI")?;
+        let mut rx = block_on(
+            &handle.runtime,
+            handle.remote.send_command("subscribe", uri, None, None),
+        )?;
+        handle.runtime.spawn(async move {
+            let user_data = user_data;
+            while let Some(message) = rx.recv().await {
+                if let Ok(message) = CString::new(message.to_string()) {
+                    callback(message.as_ptr(), user_data.0);
+                }
+            }
+        });
+        Ok(())
+    })())
+}
+
+/// Release a string returned by this library. Null is ignored.
+///
+/// # Safety
+/// `string` must be null or a string from this library that hasn't been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn lgtv_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
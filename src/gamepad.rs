@@ -0,0 +1,222 @@
+//! Drive the TV pointer and buttons from a local game controller.
+//!
+//! Reads the Linux joystick interface (`/dev/input/jsN`), which the kernel
+//! provides for any supported gamepad without extra userspace libraries.
+//! This stands in for a cross-platform bridge on gilrs, which isn't
+//! available to this build, so controllers only work on Linux for now.
+
+use crate::cursor::LgtvCursor;
+use crate::error::{LgtvError, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration};
+
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+const JS_EVENT_INIT: u8 = 0x80;
+
+// Axis and button numbers as reported by the xpad/hid drivers for
+// Xbox-style controllers; most other pads follow the same layout.
+const AXIS_LEFT_X: u8 = 0;
+const AXIS_LEFT_Y: u8 = 1;
+const AXIS_RIGHT_Y: u8 = 4;
+const AXIS_HAT_X: u8 = 6;
+const AXIS_HAT_Y: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsEvent {
+    Button { number: u8, pressed: bool },
+    Axis { number: u8, value: i16 },
+}
+
+/// Decode one `struct js_event`: a u32 timestamp, an i16 value, the event
+/// type and the axis or button number. Start-up events that report the
+/// initial state are decoded like live ones.
+fn decode(buf: &[u8; 8]) -> Option<JsEvent> {
+    let value = i16::from_ne_bytes([buf[4], buf[5]]);
+    let number = buf[7];
+    match buf[6] & !JS_EVENT_INIT {
+        JS_EVENT_BUTTON => Some(JsEvent::Button {
+            number,
+            pressed: value != 0,
+        }),
+        JS_EVENT_AXIS => Some(JsEvent::Axis { number, value }),
+        _ => None,
+    }
+}
+
+fn button_name(number: u8) -> Option<&'static str> {
+    match number {
+        0 => Some("ENTER"),
+        1 => Some("BACK"),
+        3 => Some("HOME"),
+        4 => Some("VOLUMEDOWN"),
+        5 => Some("VOLUMEUP"),
+        6 => Some("EXIT"),
+        7 | 8 => Some("HOME"),
+        _ => None,
+    }
+}
+
+/// Forwards a game controller to an [`LgtvCursor`]: the left stick moves
+/// the pointer, the right stick scrolls, the d-pad sends arrow buttons and
+/// the face and shoulder buttons send ENTER, BACK, click, HOME and volume.
+pub struct GamepadBridge {
+    device: PathBuf,
+    deadzone: i16,
+    speed: f32,
+}
+
+impl GamepadBridge {
+    /// A bridge for the joystick device at `device`, e.g. `/dev/input/js0`.
+    /// Nothing is opened until [`run`](Self::run).
+    pub fn new(device: &Path) -> Self {
+        Self {
+            device: device.to_path_buf(),
+            deadzone: 4000,
+            speed: 20.0,
+        }
+    }
+
+    /// Stick values below this magnitude are ignored.
+    pub fn deadzone(mut self, deadzone: i16) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// Pointer pixels moved per tick at full stick deflection.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Forward controller input to the cursor until the device goes away.
+    pub async fn run(&self, cursor: &mut LgtvCursor) -> Result<()> {
+        let mut file = File::open(&self.device).map_err(|e| {
+            LgtvError::ConnectionError(format!(
+                "Failed to open gamepad {}: {}",
+                self.device.display(),
+                e
+            ))
+        })?;
+
+        let (tx, mut rx) = mpsc::channel::<JsEvent>(64);
+        tokio::task::spawn_blocking(move || {
+            let mut buf = [0u8; 8];
+            while file.read_exact(&mut buf).is_ok() {
+                let Some(event) = decode(&buf) else {
+                    continue;
+                };
+                if tx.blocking_send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut stick = (0i16, 0i16);
+        let mut scroll = 0i16;
+        let mut tick = interval(Duration::from_millis(16));
+
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(JsEvent::Button { number: 2, pressed: true }) => cursor.click().await?,
+                    Some(JsEvent::Button { number, pressed: true }) => {
+                        if let Some(name) = button_name(number) {
+                            cursor.button(name).await?;
+                        }
+                    }
+                    Some(JsEvent::Button { .. }) => {}
+                    Some(JsEvent::Axis { number, value }) => match number {
+                        AXIS_LEFT_X => stick.0 = value,
+                        AXIS_LEFT_Y => stick.1 = value,
+                        AXIS_RIGHT_Y => scroll = value,
+                        AXIS_HAT_X if value < 0 => cursor.left().await?,
+                        AXIS_HAT_X if value > 0 => cursor.right().await?,
+                        AXIS_HAT_Y if value < 0 => cursor.up().await?,
+                        AXIS_HAT_Y if value > 0 => cursor.down().await?,
+                        _ => {}
+                    },
+                    None => {
+                        log::info!("Gamepad {} disconnected", self.device.display());
+                        return Ok(());
+                    }
+                },
+                _ = tick.tick() => {
+                    let dx = self.scale(stick.0);
+                    let dy = self.scale(stick.1);
+                    if dx != 0 || dy != 0 {
+                        cursor.move_by(dx, dy).await?;
+                    }
+                    let dy = self.scale(scroll);
+                    if dy != 0 {
+                        cursor.scroll(0, dy).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    fn scale(&self, value: i16) -> i32 {
+        if value.unsigned_abs() < self.deadzone.unsigned_abs() {
+            return 0;
+        }
+        (value as f32 / i16::MAX as f32 * self.speed) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(value: i16, kind: u8, number: u8) -> [u8; 8] {
+        let mut buf = [0u8; 8];
+        buf[..4].copy_from_slice(&1234u32.to_ne_bytes());
+        buf[4..6].copy_from_slice(&value.to_ne_bytes());
+        buf[6] = kind;
+        buf[7] = number;
+        buf
+    }
+
+    #[test]
+    fn decodes_button_and_axis_events() {
+        assert_eq!(
+            decode(&event(1, JS_EVENT_BUTTON, 3)),
+            Some(JsEvent::Button {
+                number: 3,
+                pressed: true
+            })
+        );
+        assert_eq!(
+            decode(&event(0, JS_EVENT_BUTTON | JS_EVENT_INIT, 0)),
+            Some(JsEvent::Button {
+                number: 0,
+                pressed: false
+            })
+        );
+        assert_eq!(
+            decode(&event(-32767, JS_EVENT_AXIS, AXIS_LEFT_Y)),
+            Some(JsEvent::Axis {
+                number: AXIS_LEFT_Y,
+                value: -32767
+            })
+        );
+        assert_eq!(decode(&event(1, 0x04, 0)), None);
+    }
+
+    #[test]
+    fn sticks_inside_the_deadzone_do_not_move() {
+        let bridge = GamepadBridge::new(Path::new("/dev/null"))
+            .deadzone(4000)
+            .speed(20.0);
+        assert_eq!(bridge.scale(0), 0);
+        assert_eq!(bridge.scale(3999), 0);
+        assert_eq!(bridge.scale(-3999), 0);
+        assert_eq!(bridge.scale(i16::MAX), 20);
+        assert_eq!(bridge.scale(-i16::MAX), -20);
+        assert_eq!(bridge.scale(i16::MIN), -20);
+        assert_eq!(bridge.scale(i16::MAX / 2), 9);
+    }
+}
@@ -0,0 +1,153 @@
+//! Controlling several TVs at once, e.g. turning everything off with one
+//! call.
+
+use crate::config::{self, Config};
+use crate::error::{LgtvError, Result};
+use crate::remote::{ConnectionState, LgtvRemote};
+use futures_util::future::join_all;
+use serde_json::Value;
+use std::future::Future;
+
+/// Remotes that commands are sent to together, each TV's at the same time
+/// as the others'. Every method gives one [`GroupResult`] per TV, in the
+/// order they were added, so one TV failing doesn't stop the rest.
+#[derive(Clone, Default)]
+pub struct LgtvGroup {
+    remotes: Vec<LgtvRemote>,
+}
+
+/// How a command went on one TV of an [`LgtvGroup`].
+#[derive(Debug)]
+pub struct GroupResult<T> {
+    /// The remote's [`name`](LgtvRemote::name)
+    pub name: String,
+    pub result: Result<T>,
+}
+
+impl LgtvGroup {
+    pub fn new(remotes: impl IntoIterator<Item = LgtvRemote>) -> Self {
+        Self {
+            remotes: remotes.into_iter().collect(),
+        }
+    }
+
+    /// The TVs stored under `names` in the config file, as
+    /// [`LgtvRemote::from_config`] builds them.
+    pub fn from_config(names: &[&str]) -> Result<Self> {
+        Self::from_tvs(&config::load_config()?, names)
+    }
+
+    /// Every TV in the config file.
+    pub fn from_config_all() -> Result<Self> {
+        let config = config::load_config()?;
+        let names: Vec<&str> = config.tvs.keys().map(String::as_str).collect();
+        Self::from_tvs(&config, &names)
+    }
+
+    /// The TVs stored under `names` in `config`, for a config read from
+    /// somewhere else than the usual locations.
+    pub fn from_tvs(config: &Config, names: &[&str]) -> Result<Self> {
+        let remotes = names
+            .iter()
+            .map(|name| {
+                let entry = config
+                    .get(name)
+                    .ok_or_else(|| LgtvError::TvNotFound(name.to_string()))?;
+                LgtvRemote::from_entry(name, entry)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(remotes))
+    }
+
+    pub fn push(&mut self, remote: LgtvRemote) {
+        self.remotes.push(remote);
+    }
+
+    pub fn remotes(&self) -> &[LgtvRemote] {
+        &self.remotes
+    }
+
+    pub fn len(&self) -> usize {
+        self.remotes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remotes.is_empty()
+    }
+
+    /// Run `command` on every TV at once, connecting those that aren't yet,
+    /// e.g. `group.run(|remote| remote.set_input("HDMI_1"))`.
+    pub async fn run<'a, F, Fut, T>(&'a self, command: F) -> Vec<GroupResult<T>>
+    where
+        F: Fn(&'a LgtvRemote) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let command = &command;
+        self.fan_out(|remote| async move {
+            if matches!(
+                remote.state(),
+                ConnectionState::Disconnected | ConnectionState::Closed
+            ) {
+                remote.connect().await?;
+            }
+            command(remote).await
+        })
+        .await
+    }
+
+    /// Run `command` on every TV at once, leaving it to connect those that
+    /// need it, e.g. for a command that may only wake the TV.
+    pub async fn fan_out<'a, F, Fut, T>(&'a self, command: F) -> Vec<GroupResult<T>>
+    where
+        F: Fn(&'a LgtvRemote) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let results = join_all(self.remotes.iter().map(&command)).await;
+        self.remotes
+            .iter()
+            .zip(results)
+            .map(|(remote, result)| GroupResult {
+                name: remote.name().to_string(),
+                result,
+            })
+            .collect()
+    }
+
+    pub async fn connect(&self) -> Vec<GroupResult<()>> {
+        self.fan_out(|remote| remote.connect()).await
+    }
+
+    /// Close every connection, see [`LgtvRemote::close`].
+    pub async fn close(&self) {
+        join_all(self.remotes.iter().map(|remote| remote.close())).await;
+    }
+
+    /// Wake every TV with Wake-on-LAN, without connecting.
+    pub async fn on(&self) -> Vec<GroupResult<()>> {
+        self.fan_out(|remote| remote.on()).await
+    }
+
+    pub async fn off(&self) -> Vec<GroupResult<()>> {
+        self.run(|remote| remote.off()).await
+    }
+
+    pub async fn screen_off(&self) -> Vec<GroupResult<()>> {
+        self.run(|remote| remote.screen_off()).await
+    }
+
+    pub async fn screen_on(&self) -> Vec<GroupResult<()>> {
+        self.run(|remote| remote.screen_on()).await
+    }
+
+    pub async fn mute(&self, muted: bool) -> Vec<GroupResult<bool>> {
+        self.run(|remote| remote.mute(muted)).await
+    }
+
+    pub async fn set_volume(&self, level: u32) -> Vec<GroupResult<u32>> {
+        self.run(|remote| remote.set_volume(level)).await
+    }
+
+    pub async fn start_app(&self, app_id: &str) -> Vec<GroupResult<Value>> {
+        self.run(|remote| remote.start_app(app_id)).await
+    }
+}
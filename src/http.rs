@@ -0,0 +1,164 @@
+//! Minimal HTTP/1.1 GET, enough for notification icons and UPnP device
+//! descriptions, so no HTTP client crate is needed. HTTPS uses the same
+//! TLS backend as the websocket.
+
+use crate::error::{LgtvError, Result};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest response read, headers included. Icons and device descriptions
+/// are far smaller.
+const MAX_RESPONSE: u64 = 1024 * 1024;
+/// How long a server gets to answer in full.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fetch `url` and return the response body. Fails if the response is
+/// larger than 1 MiB or takes longer than 10 seconds.
+pub async fn get(url: &str) -> Result<Vec<u8>> {
+    tokio::time::timeout(TIMEOUT, fetch(url))
+        .await
+        .map_err(|_| LgtvError::CommandError(format!("No answer from {} in time", url)))?
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let https = url.starts_with("https://");
+    let url_body = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+        .ok_or_else(|| LgtvError::CommandError("Invalid URL scheme".to_string()))?;
+
+    let (host_port, path) = match url_body.find('/') {
+        Some(i) => (&url_body[..i], &url_body[i..]),
+        None => (url_body, "/"),
+    };
+
+    // "[fe80::1]:1234" for IPv6 literals, "host:1234" otherwise
+    let (host, port) = match host_port.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, port)) => (host, port.strip_prefix(':')),
+            None => (rest, None),
+        },
+        None => match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    let port: u16 = port
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(if https { 443 } else { 80 });
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, host_port
+    );
+
+    let tcp_stream = tokio::net::TcpStream::connect((host, port))
+        .await
+        .map_err(|e| LgtvError::CommandError(format!("Failed to connect: {}", e)))?;
+
+    let response = if https {
+        exchange_secure(host, tcp_stream, &request).await?
+    } else {
+        exchange(tcp_stream, &request).await?
+    };
+
+    extract_body(response)
+}
+
+#[cfg(feature = "native-tls")]
+async fn exchange_secure(
+    host: &str,
+    tcp_stream: tokio::net::TcpStream,
+    request: &str,
+) -> Result<Vec<u8>> {
+    let connector = native_tls::TlsConnector::new()
+        .map_err(|e| LgtvError::CommandError(format!("TLS error: {}", e)))?;
+    let connector = tokio_native_tls::TlsConnector::from(connector);
+    let stream = connector
+        .connect(host, tcp_stream)
+        .await
+        .map_err(|e| LgtvError::CommandError(format!("TLS connect error: {}", e)))?;
+    exchange(stream, request).await
+}
+
+#[cfg(not(feature = "native-tls"))]
+async fn exchange_secure(
+    _host: &str,
+    _tcp_stream: tokio::net::TcpStream,
+    _request: &str,
+) -> Result<Vec<u8>> {
+    Err(crate::tls::unsupported())
+}
+
+async fn exchange<S>(mut stream: S, request: &str) -> Result<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| LgtvError::CommandError(format!("Failed to send request: {}", e)))?;
+    let mut response = Vec::new();
+    (&mut stream)
+        .take(MAX_RESPONSE + 1)
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| LgtvError::CommandError(format!("Failed to read response: {}", e)))?;
+    if response.len() as u64 > MAX_RESPONSE {
+        return Err(LgtvError::CommandError(format!(
+            "HTTP response larger than {} bytes",
+            MAX_RESPONSE
+        )));
+    }
+    Ok(response)
+}
+
+fn extract_body(response: Vec<u8>) -> Result<Vec<u8>> {
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| LgtvError::CommandError("Invalid HTTP response".to_string()))?;
+
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let status = head.split_whitespace().nth(1).unwrap_or("");
+    if !status.starts_with('2') {
+        return Err(LgtvError::CommandError(format!(
+            "HTTP request failed with status {}",
+            status
+        )));
+    }
+
+    let body = &response[header_end + 4..];
+    let chunked = head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if chunked {
+        dechunk(body)
+    } else {
+        Ok(body.to_vec())
+    }
+}
+
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || LgtvError::CommandError("Invalid chunked HTTP response".to_string());
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(invalid)?;
+        let size_line = String::from_utf8_lossy(&body[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| invalid())?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        if body.len() < size {
+            return Err(invalid());
+        }
+        decoded.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or(&[]);
+    }
+}
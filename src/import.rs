@@ -0,0 +1,127 @@
+//! Reading pairings made by other tools: the original Python `lgtv`,
+//! Home Assistant's webOS integration and pywebostv/aiowebostv key dumps.
+
+use crate::config::{config_name, Config, TvConfigEntry};
+use crate::error::{LgtvError, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the Python tool keeps its config, most likely first.
+pub fn python_config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join(".lgtv/config.json"));
+    }
+    paths.push(PathBuf::from("/opt/venvs/lgtv/config/config.json"));
+    paths.push(PathBuf::from("/etc/lgtv/config.json"));
+    paths
+}
+
+/// Read the TVs stored in `path`, whichever of the supported formats it
+/// is in. `host` is needed for key dumps that don't record the TV's
+/// address, and `name` names the TV when the file doesn't.
+pub fn read(path: &Path, host: Option<&str>, name: Option<&str>) -> Result<Config> {
+    let contents = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&contents)
+        .map_err(|e| LgtvError::ConfigError(format!("{}: {}", path.display(), e)))?;
+    convert(&value, host, name)
+        .map_err(|e| LgtvError::ConfigError(format!("{}: {}", path.display(), e)))
+}
+
+fn convert(
+    value: &Value,
+    host: Option<&str>,
+    name: Option<&str>,
+) -> std::result::Result<Config, String> {
+    let object = value.as_object().ok_or("expected a JSON object")?;
+
+    // Home Assistant's .storage/core.config_entries
+    if let Some(entries) = value.pointer("/data/entries").and_then(Value::as_array) {
+        return Ok(home_assistant(entries));
+    }
+
+    // pywebostv/aiowebostv: {"client_key": "..."}
+    if let Some(key) = object.get("client_key").and_then(Value::as_str) {
+        let host = host
+            .or_else(|| object.get("host").and_then(Value::as_str))
+            .ok_or("the key dump doesn't say which TV it is for; pass --host")?;
+        let mut config = Config::default();
+        config.tvs.insert(
+            name.map(str::to_string)
+                .unwrap_or_else(|| config_name(host)),
+            TvConfigEntry {
+                key: Some(key.to_string()),
+                ip: Some(host.to_string()),
+                ..Default::default()
+            },
+        );
+        return Ok(config);
+    }
+
+    // Home Assistant's old webostv.conf: {"192.168.1.100": "client-key"}
+    if !object.is_empty() && object.values().all(Value::is_string) {
+        let mut config = Config::default();
+        for (host, key) in object {
+            config.tvs.insert(
+                config_name(host),
+                TvConfigEntry {
+                    key: key.as_str().map(str::to_string),
+                    ip: Some(host.clone()),
+                    ..Default::default()
+                },
+            );
+        }
+        return Ok(config);
+    }
+
+    // The Python lgtv tool, which this crate's JSON format grew out of
+    python_lgtv(object)
+}
+
+fn python_lgtv(object: &Map<String, Value>) -> std::result::Result<Config, String> {
+    let mut config = Config::default();
+    for (name, value) in object {
+        if name == "_default" {
+            config.default = value.as_str().map(str::to_string);
+            continue;
+        }
+        if !value.is_object() {
+            continue;
+        }
+        let entry = TvConfigEntry::from_value(value).map_err(|e| format!("'{}': {}", name, e))?;
+        config.tvs.insert(name.clone(), entry);
+    }
+    if config.tvs.is_empty() {
+        return Err("no TVs found in this file".to_string());
+    }
+    Ok(config)
+}
+
+fn home_assistant(entries: &[Value]) -> Config {
+    let mut config = Config::default();
+    for entry in entries {
+        if entry.get("domain").and_then(Value::as_str) != Some("webostv") {
+            continue;
+        }
+        let data = &entry["data"];
+        let text = |value: &Value| value.as_str().map(str::to_string);
+        let Some(ip) = text(&data["host"]) else {
+            continue;
+        };
+        let label = text(&entry["title"])
+            .or_else(|| text(&data["name"]))
+            .unwrap_or_else(|| ip.clone());
+        config.tvs.insert(
+            config_name(&label),
+            TvConfigEntry {
+                key: text(&data["client_secret"]).or_else(|| text(&data["key"])),
+                ip: Some(ip),
+                uuid: text(&entry["unique_id"]),
+                friendly_name: Some(label),
+                ..Default::default()
+            },
+        );
+    }
+    config
+}
@@ -0,0 +1,372 @@
+//! Client keys kept in the system keyring: the Secret Service on Linux
+//! (through `secret-tool`), the login keychain on macOS (written through
+//! Security.framework, read through `security`) and the Credential Manager
+//! on Windows.
+//!
+//! Entries are stored under the service name `lgtv`, with the TV's config
+//! name as the account.
+
+use crate::error::{LgtvError, Result};
+
+const SERVICE: &str = "lgtv";
+
+/// Prefix of `key_ref` values that point into the keyring.
+pub const REF_PREFIX: &str = "keyring:";
+
+fn keyring_error(action: &str, account: &str, reason: impl std::fmt::Display) -> LgtvError {
+    LgtvError::ConfigError(format!(
+        "Could not {} the key for '{}' in the keyring: {}",
+        action, account, reason
+    ))
+}
+
+/// Save `secret` as the key of `account`, replacing any earlier one.
+pub fn store(account: &str, secret: &str) -> Result<()> {
+    imp::store(account, secret).map_err(|e| keyring_error("store", account, e))
+}
+
+/// The key saved for `account`.
+pub fn load(account: &str) -> Result<String> {
+    find(account)?.ok_or_else(|| keyring_error("read", account, "no key stored"))
+}
+
+/// The key saved for `account`, or `None` if the keyring has none. A
+/// keyring that can't be asked (locked, or its tool missing) is an error.
+pub fn find(account: &str) -> Result<Option<String>> {
+    imp::load(account).map_err(|e| keyring_error("read", account, e))
+}
+
+/// Forget the key saved for `account`. Succeeds if there was none.
+pub fn delete(account: &str) -> Result<()> {
+    imp::delete(account).map_err(|e| keyring_error("delete", account, e))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod imp {
+    use super::SERVICE;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    fn secret_tool() -> Command {
+        Command::new("secret-tool")
+    }
+
+    fn describe(output: &std::process::Output) -> String {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            format!("secret-tool exited with {}", output.status)
+        } else {
+            stderr
+        }
+    }
+
+    pub fn store(account: &str, secret: &str) -> Result<(), String> {
+        // The secret goes through stdin so it never shows up in `ps`
+        let mut child = secret_tool()
+            .args(["store", "--label"])
+            .arg(format!("lgtv client key for {}", account))
+            .args(["service", SERVICE, "account", account])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run secret-tool: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(secret.as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(describe(&output))
+        }
+    }
+
+    pub fn load(account: &str) -> Result<Option<String>, String> {
+        let output = secret_tool()
+            .args(["lookup", "service", SERVICE, "account", account])
+            .output()
+            .map_err(|e| format!("failed to run secret-tool: {}", e))?;
+        let secret = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !secret.is_empty() {
+            Ok(Some(secret))
+        } else if output.stderr.is_empty() {
+            // "lookup" fails without a word when nothing matched
+            Ok(None)
+        } else {
+            Err(describe(&output))
+        }
+    }
+
+    pub fn delete(account: &str) -> Result<(), String> {
+        let output = secret_tool()
+            .args(["clear", "service", SERVICE, "account", account])
+            .output()
+            .map_err(|e| format!("failed to run secret-tool: {}", e))?;
+        // "clear" also fails when nothing matched, without saying anything
+        if output.status.success() || output.stderr.is_empty() {
+            Ok(())
+        } else {
+            Err(describe(&output))
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use super::SERVICE;
+    use std::ffi::c_void;
+    use std::process::Command;
+    use std::ptr;
+
+    /// `security` exits with this when no matching item exists.
+    const ITEM_NOT_FOUND: i32 = 44;
+    /// errSecDuplicateItem
+    const DUPLICATE_ITEM: i32 = -25299;
+
+    #[link(name = "Security", kind = "framework")]
+    extern "C" {
+        fn SecKeychainAddGenericPassword(
+            keychain: *const c_void,
+            service_length: u32,
+            service: *const u8,
+            account_length: u32,
+            account: *const u8,
+            password_length: u32,
+            password: *const c_void,
+            item: *mut *const c_void,
+        ) -> i32;
+        fn SecKeychainFindGenericPassword(
+            keychain: *const c_void,
+            service_length: u32,
+            service: *const u8,
+            account_length: u32,
+            account: *const u8,
+            password_length: *mut u32,
+            password: *mut *mut c_void,
+            item: *mut *const c_void,
+        ) -> i32;
+        fn SecKeychainItemModifyAttributesAndData(
+            item: *const c_void,
+            attributes: *const c_void,
+            length: u32,
+            data: *const c_void,
+        ) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(object: *const c_void);
+    }
+
+    fn security(args: &[&str]) -> Result<std::process::Output, String> {
+        Command::new("/usr/bin/security")
+            .args(args)
+            .output()
+            .map_err(|e| format!("failed to run security: {}", e))
+    }
+
+    fn describe(output: &std::process::Output) -> String {
+        String::from_utf8_lossy(&output.stderr).trim().to_string()
+    }
+
+    /// Written through the framework rather than `security`, which only
+    /// takes the secret on its command line, where other users can see it.
+    pub fn store(account: &str, secret: &str) -> Result<(), String> {
+        let failed = |status: i32| Err(format!("keychain error {}", status));
+        // SAFETY: every pointer is valid for the length passed with it, and
+        // the item found is released before returning
+        unsafe {
+            let status = SecKeychainAddGenericPassword(
+                ptr::null(),
+                SERVICE.len() as u32,
+                SERVICE.as_ptr(),
+                account.len() as u32,
+                account.as_ptr(),
+                secret.len() as u32,
+                secret.as_ptr() as *const c_void,
+                ptr::null_mut(),
+            );
+            if status == 0 {
+                return Ok(());
+            }
+            if status != DUPLICATE_ITEM {
+                return failed(status);
+            }
+
+            let mut item: *const c_void = ptr::null();
+            let status = SecKeychainFindGenericPassword(
+                ptr::null(),
+                SERVICE.len() as u32,
+                SERVICE.as_ptr(),
+                account.len() as u32,
+                account.as_ptr(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut item,
+            );
+            if status != 0 {
+                return failed(status);
+            }
+            let status = SecKeychainItemModifyAttributesAndData(
+                item,
+                ptr::null(),
+                secret.len() as u32,
+                secret.as_ptr() as *const c_void,
+            );
+            CFRelease(item);
+            if status == 0 {
+                Ok(())
+            } else {
+                failed(status)
+            }
+        }
+    }
+
+    pub fn load(account: &str) -> Result<Option<String>, String> {
+        let output = security(&["find-generic-password", "-s", SERVICE, "-a", account, "-w"])?;
+        if output.status.success() {
+            Ok(Some(
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            ))
+        } else if output.status.code() == Some(ITEM_NOT_FOUND) {
+            Ok(None)
+        } else {
+            Err(describe(&output))
+        }
+    }
+
+    pub fn delete(account: &str) -> Result<(), String> {
+        let output = security(&["delete-generic-password", "-s", SERVICE, "-a", account])?;
+        if output.status.success() || output.status.code() == Some(ITEM_NOT_FOUND) {
+            Ok(())
+        } else {
+            Err(describe(&output))
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::SERVICE;
+    use std::ffi::c_void;
+    use std::io;
+    use std::ptr;
+
+    const CRED_TYPE_GENERIC: u32 = 1;
+    const CRED_PERSIST_LOCAL_MACHINE: u32 = 2;
+    const ERROR_NOT_FOUND: i32 = 1168;
+
+    #[repr(C)]
+    struct Credential {
+        flags: u32,
+        kind: u32,
+        target_name: *mut u16,
+        comment: *mut u16,
+        last_written: [u32; 2],
+        blob_size: u32,
+        blob: *mut u8,
+        persist: u32,
+        attribute_count: u32,
+        attributes: *mut c_void,
+        target_alias: *mut u16,
+        user_name: *mut u16,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn CredWriteW(credential: *const Credential, flags: u32) -> i32;
+        fn CredReadW(target: *const u16, kind: u32, flags: u32, out: *mut *mut Credential) -> i32;
+        fn CredDeleteW(target: *const u16, kind: u32, flags: u32) -> i32;
+        fn CredFree(buffer: *mut c_void);
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn target(account: &str) -> Vec<u16> {
+        wide(&format!("{}:{}", SERVICE, account))
+    }
+
+    pub fn store(account: &str, secret: &str) -> Result<(), String> {
+        let mut target = target(account);
+        let mut user = wide(account);
+        let mut blob = secret.as_bytes().to_vec();
+        let credential = Credential {
+            flags: 0,
+            kind: CRED_TYPE_GENERIC,
+            target_name: target.as_mut_ptr(),
+            comment: ptr::null_mut(),
+            last_written: [0; 2],
+            blob_size: blob.len() as u32,
+            blob: blob.as_mut_ptr(),
+            persist: CRED_PERSIST_LOCAL_MACHINE,
+            attribute_count: 0,
+            attributes: ptr::null_mut(),
+            target_alias: ptr::null_mut(),
+            user_name: user.as_mut_ptr(),
+        };
+        // SAFETY: every pointer in `credential` outlives the call
+        if unsafe { CredWriteW(&credential, 0) } != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error().to_string())
+        }
+    }
+
+    pub fn load(account: &str) -> Result<Option<String>, String> {
+        let target = target(account);
+        let mut credential: *mut Credential = ptr::null_mut();
+        // SAFETY: on success `credential` points to a buffer owned by the
+        // system until it is passed to CredFree
+        unsafe {
+            if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+                let error = io::Error::last_os_error();
+                return if error.raw_os_error() == Some(ERROR_NOT_FOUND) {
+                    Ok(None)
+                } else {
+                    Err(error.to_string())
+                };
+            }
+            let blob =
+                std::slice::from_raw_parts((*credential).blob, (*credential).blob_size as usize);
+            let secret = String::from_utf8_lossy(blob).into_owned();
+            CredFree(credential as *mut c_void);
+            Ok(Some(secret))
+        }
+    }
+
+    pub fn delete(account: &str) -> Result<(), String> {
+        let target = target(account);
+        // SAFETY: `target` is a NUL-terminated UTF-16 string
+        if unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) } != 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(ERROR_NOT_FOUND) {
+            Ok(())
+        } else {
+            Err(error.to_string())
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    const UNSUPPORTED: &str = "no keyring is available on this platform";
+
+    pub fn store(_account: &str, _secret: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn load(_account: &str) -> Result<Option<String>, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn delete(_account: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
@@ -1,14 +1,46 @@
 pub mod auth;
+pub mod cancel;
+pub mod client;
 pub mod config;
+pub mod crypt;
 pub mod cursor;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(all(feature = "gamepad", target_os = "linux"))]
+pub mod gamepad;
+pub mod group;
+pub(crate) mod http;
+pub mod import;
+pub mod keyring;
+pub(crate) mod mdns;
+#[cfg(target_os = "linux")]
+pub mod mouse;
+pub mod neighbor;
 pub mod payload;
+pub mod rate;
 pub mod remote;
 pub mod scan;
+pub mod settings;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tls;
+pub(crate) mod toml;
+pub mod trace;
 
 // Re-export the main types
-pub use auth::LgtvAuth;
+pub use auth::{LgtvAuth, PairingEvent};
+pub use cancel::CancellationToken;
+pub use client::LgtvClient;
 pub use cursor::LgtvCursor;
-pub use error::{LgtvError, Result};
-pub use remote::LgtvRemote;
-pub use scan::{scan_for_tvs, TvDevice};
+pub use error::{LgtvError, Result, TvErrorKind};
+pub use group::{GroupResult, LgtvGroup};
+pub use rate::RateLimit;
+pub use remote::{
+    ConnectionEvent, ConnectionState, LgtvRemote, LgtvRemoteBuilder, QueuePolicy, ReconnectPolicy,
+};
+pub use scan::{
+    interface_address, scan_for_tvs, scan_stream, scan_stream_with_options, scan_with_options,
+    sweep_subnet, ScanOptions, TvDevice,
+};
+pub use tls::TlsOptions;
@@ -0,0 +1,448 @@
+//! Line editing for `lgtv shell`: history, cursor movement and tab
+//! completion when talking to a terminal, plain line reading otherwise
+//! (piped input, or platforms without termios).
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Lines of history kept on disk.
+const HISTORY_LIMIT: usize = 1000;
+
+/// Candidates for the word being typed, given the line up to the cursor.
+pub type Completer = fn(&str) -> Vec<String>;
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    completer: Completer,
+}
+
+impl LineEditor {
+    pub fn new(completer: Completer) -> Self {
+        Self {
+            history: Vec::new(),
+            history_path: None,
+            completer,
+        }
+    }
+
+    /// Load earlier history from `path`, and save to it in
+    /// [`save_history`](Self::save_history). A missing file is fine.
+    pub fn load_history(&mut self, path: &Path) {
+        if let Ok(contents) = fs::read_to_string(path) {
+            self.history = contents.lines().map(str::to_string).collect();
+        }
+        self.history_path = Some(path.to_path_buf());
+    }
+
+    pub fn save_history(&self) -> io::Result<()> {
+        let Some(path) = &self.history_path else {
+            return Ok(());
+        };
+        let start = self.history.len().saturating_sub(HISTORY_LIMIT);
+        let mut contents = self.history[start..].join("\n");
+        contents.push('\n');
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// Remember `line`, unless it repeats the previous one.
+    pub fn add_history(&mut self, line: &str) {
+        if !line.is_empty() && self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+        }
+    }
+
+    /// Read one line, or `None` at the end of input (Ctrl-D).
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        #[cfg(unix)]
+        if io::stdin().is_terminal() && io::stdout().is_terminal() {
+            let _raw = raw::RawMode::enable()?;
+            return self.edit(prompt);
+        }
+
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    fn edit(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let mut stdin = io::stdin().lock();
+        let mut state = State {
+            prompt,
+            line: Vec::new(),
+            pos: 0,
+        };
+        // Position in the history while browsing it, and what was typed
+        // before browsing started
+        let mut index = self.history.len();
+        let mut draft = Vec::new();
+        state.redraw()?;
+
+        loop {
+            match read_key(&mut stdin)? {
+                Key::Char(c) => {
+                    state.line.insert(state.pos, c);
+                    state.pos += 1;
+                }
+                Key::Enter => {
+                    print!("\r\n");
+                    io::stdout().flush()?;
+                    return Ok(Some(state.line.iter().collect()));
+                }
+                Key::Eof if state.line.is_empty() => {
+                    print!("\r\n");
+                    io::stdout().flush()?;
+                    return Ok(None);
+                }
+                Key::Eof | Key::Delete => {
+                    if state.pos < state.line.len() {
+                        state.line.remove(state.pos);
+                    }
+                }
+                Key::Interrupt => {
+                    print!("^C\r\n");
+                    state.line.clear();
+                    state.pos = 0;
+                    index = self.history.len();
+                }
+                Key::Backspace => {
+                    if state.pos > 0 {
+                        state.pos -= 1;
+                        state.line.remove(state.pos);
+                    }
+                }
+                Key::Left => state.pos = state.pos.saturating_sub(1),
+                Key::Right => state.pos = (state.pos + 1).min(state.line.len()),
+                Key::Home => state.pos = 0,
+                Key::End => state.pos = state.line.len(),
+                Key::KillLine => {
+                    state.line.drain(..state.pos);
+                    state.pos = 0;
+                }
+                Key::Up => {
+                    if index > 0 {
+                        if index == self.history.len() {
+                            draft = state.line.clone();
+                        }
+                        index -= 1;
+                        state.set(self.history[index].chars().collect());
+                    }
+                }
+                Key::Down => {
+                    if index < self.history.len() {
+                        index += 1;
+                        state.set(match self.history.get(index) {
+                            Some(line) => line.chars().collect(),
+                            None => draft.clone(),
+                        });
+                    }
+                }
+                Key::Tab => self.complete(&mut state)?,
+                Key::Other => continue,
+            }
+            state.redraw()?;
+        }
+    }
+
+    /// Complete the word before the cursor: fully when there's only one
+    /// candidate, as far as the candidates agree otherwise, listing them
+    /// when that doesn't get any further.
+    fn complete(&self, state: &mut State) -> io::Result<()> {
+        let before: String = state.line[..state.pos].iter().collect();
+        let start = before
+            .rfind(char::is_whitespace)
+            .map(|i| before[..=i].chars().count())
+            .unwrap_or(0);
+        let typed: String = state.line[start..state.pos].iter().collect();
+        let candidates = (self.completer)(&before);
+
+        let mut completion = match candidates.as_slice() {
+            [] => return Ok(()),
+            [only] => format!("{} ", only),
+            [first, rest @ ..] => rest.iter().fold(first.clone(), |prefix, c| {
+                prefix
+                    .chars()
+                    .zip(c.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a)
+                    .collect()
+            }),
+        };
+        if completion.chars().count() <= typed.chars().count() {
+            if candidates.len() > 1 {
+                print!("\r\n{}\r\n", candidates.join("  "));
+            }
+            return Ok(());
+        }
+
+        // Only what comes after the typed part is inserted
+        completion = completion.chars().skip(typed.chars().count()).collect();
+        for c in completion.chars() {
+            state.line.insert(state.pos, c);
+            state.pos += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Choices shown at once by [`pick`].
+const PICK_ROWS: usize = 10;
+
+/// Let the user choose one of `items` on the terminal, typing to narrow
+/// them down (the letters typed must appear in order) and moving with the
+/// arrow keys. Drawn on stderr so stdout can still be piped. `None` when
+/// the user gives up with Ctrl-C or Ctrl-D.
+#[cfg(unix)]
+pub fn pick(prompt: &str, items: &[String]) -> io::Result<Option<usize>> {
+    let _raw = raw::RawMode::enable()?;
+    let mut stdin = io::stdin().lock();
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches: Vec<usize> = (0..items.len())
+            .filter(|&i| fuzzy_match(&query, &items[i]))
+            .collect();
+        selected = selected.min(matches.len().saturating_sub(1));
+        draw_pick(prompt, &query, items, &matches, selected)?;
+
+        match read_key(&mut stdin)? {
+            Key::Char(c) => {
+                query.push(c);
+                selected = 0;
+            }
+            Key::Backspace => {
+                query.pop();
+                selected = 0;
+            }
+            Key::KillLine => query.clear(),
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down | Key::Tab => selected += 1,
+            Key::Enter => {
+                if let Some(&choice) = matches.get(selected) {
+                    finish_pick(&format!("{}{}", prompt, items[choice]))?;
+                    return Ok(Some(choice));
+                }
+            }
+            Key::Interrupt | Key::Eof => {
+                finish_pick(&format!("{}^C", prompt))?;
+                return Ok(None);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether the letters of `query` appear in `item` in order, ignoring case.
+fn fuzzy_match(query: &str, item: &str) -> bool {
+    let mut letters = item.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| letters.any(|c| c == q))
+}
+
+fn draw_pick(
+    prompt: &str,
+    query: &str,
+    items: &[String],
+    matches: &[usize],
+    selected: usize,
+) -> io::Result<()> {
+    // Scroll so the selected choice is always shown
+    let first = selected.saturating_sub(PICK_ROWS - 1);
+    let mut out = format!("\r\x1b[J{}{}", prompt, query);
+    let mut rows = 0;
+    for (n, &i) in matches.iter().enumerate().skip(first).take(PICK_ROWS) {
+        if n == selected {
+            out.push_str(&format!("\r\n\x1b[7m> {}\x1b[0m", items[i]));
+        } else {
+            out.push_str(&format!("\r\n  {}", items[i]));
+        }
+        rows += 1;
+    }
+    if matches.is_empty() {
+        out.push_str("\r\n  (no matches)");
+        rows += 1;
+    }
+    let column = prompt.chars().count() + query.chars().count();
+    out.push_str(&format!("\x1b[{}A\r", rows));
+    if column > 0 {
+        out.push_str(&format!("\x1b[{}C", column));
+    }
+    let mut stderr = io::stderr();
+    stderr.write_all(out.as_bytes())?;
+    stderr.flush()
+}
+
+/// Replace the picker with `line`.
+fn finish_pick(line: &str) -> io::Result<()> {
+    let mut stderr = io::stderr();
+    write!(stderr, "\r\x1b[J{}\r\n", line)?;
+    stderr.flush()
+}
+
+struct State<'a> {
+    prompt: &'a str,
+    line: Vec<char>,
+    pos: usize,
+}
+
+impl State<'_> {
+    fn set(&mut self, line: Vec<char>) {
+        self.pos = line.len();
+        self.line = line;
+    }
+
+    fn redraw(&self) -> io::Result<()> {
+        let line: String = self.line.iter().collect();
+        let mut out = format!("\r{}{}\x1b[K", self.prompt, line);
+        let back = self.line.len() - self.pos;
+        if back > 0 {
+            out.push_str(&format!("\x1b[{}D", back));
+        }
+        let mut stdout = io::stdout();
+        stdout.write_all(out.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillLine,
+    Interrupt,
+    Eof,
+    Other,
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0u8];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_key(input: &mut impl Read) -> io::Result<Key> {
+    let key = match read_byte(input)? {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x03 => Key::Interrupt,
+        0x04 => Key::Eof,
+        0x05 => Key::End,
+        0x15 => Key::KillLine,
+        0x1b => read_escape(input)?,
+        byte if byte < 0x20 => Key::Other,
+        byte => {
+            // The rest of a UTF-8 sequence follows its first byte
+            let len = match byte {
+                0xf0.. => 4,
+                0xe0.. => 3,
+                0xc0.. => 2,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                bytes.push(read_byte(input)?);
+            }
+            match String::from_utf8_lossy(&bytes).chars().next() {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+    };
+    Ok(key)
+}
+
+/// Decode the arrow, Home, End and Delete keys, sent as `ESC [ ...` or
+/// `ESC O ...`.
+fn read_escape(input: &mut impl Read) -> io::Result<Key> {
+    if !matches!(read_byte(input)?, b'[' | b'O') {
+        return Ok(Key::Other);
+    }
+    let key = match read_byte(input)? {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        digit @ b'0'..=b'9' => {
+            let mut code = vec![digit];
+            loop {
+                match read_byte(input)? {
+                    b'~' => break,
+                    byte if byte.is_ascii_digit() || byte == b';' => code.push(byte),
+                    _ => return Ok(Key::Other),
+                }
+            }
+            match code.as_slice() {
+                b"1" | b"7" => Key::Home,
+                b"3" => Key::Delete,
+                b"4" | b"8" => Key::End,
+                _ => Key::Other,
+            }
+        }
+        _ => Key::Other,
+    };
+    Ok(key)
+}
+
+#[cfg(unix)]
+mod raw {
+    use std::io;
+
+    /// Puts the terminal in raw mode while alive, so keys arrive one at a
+    /// time without being echoed.
+    pub struct RawMode {
+        original: libc::termios,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<Self> {
+            // SAFETY: termios is plain data, filled in by tcgetattr
+            let mut original: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            // Ctrl-C clears the line instead of ending the shell
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            // SAFETY: `raw` is a valid termios derived from the current one
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: restores the settings read in `enable`
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+            }
+        }
+    }
+}
@@ -1,14 +1,75 @@
-use clap::{Parser, Subcommand};
+mod line_editor;
+mod progress;
+mod table;
+mod web;
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use futures_util::StreamExt;
 use lgtv::{
-    auth::LgtvAuth,
-    config::{find_config, read_config, write_config},
+    auth::{LgtvAuth, PairingEvent},
+    config::{
+        config_name, find_config, find_profile, known_tv, lock_config, read_config,
+        read_config_for_tv, read_config_or_default, read_existing_config, remove_tv,
+        scan_cache_path, update_config, user_config_path, write_config, Config, TvConfigEntry,
+    },
+    crypt::PassphraseSource,
     cursor::LgtvCursor,
-    error::Result,
-    remote::LgtvRemote,
-    scan::scan_for_tvs,
+    error::{LgtvError, Result, TvErrorKind},
+    group::{GroupResult, LgtvGroup},
+    import::{self, python_config_paths},
+    payload::PermissionSet,
+    remote::{LgtvRemote, NotificationIcon, TvStatus, VerifyStatus},
+    scan::{
+        interface_address, load_cache, save_cache, scan_stream_with_options, scan_with_options,
+        sweep_subnet, ScanOptions, CACHE_TTL,
+    },
+    settings::{parse_picture_settings, Setting, PICTURE_SETTINGS},
 };
+use line_editor::LineEditor;
 use serde_json::{json, Value};
+use std::io::{IsTerminal, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use table::Table;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+// ──── Exit codes ────
+// 2 is left to clap, which uses it for bad arguments
+
+const EXIT_FAILURE: i32 = 1;
+/// The config is missing or invalid, or doesn't have the TV asked for.
+const EXIT_CONFIG: i32 = 3;
+/// The TV couldn't be found or reached.
+const EXIT_CONNECTION: i32 = 4;
+/// Pairing failed, or the TV no longer accepts the client key.
+const EXIT_AUTH: i32 = 5;
+/// The TV answered, but refused the command.
+const EXIT_COMMAND: i32 = 6;
+
+/// Set by `--quiet`.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for anything that isn't an error, which `--quiet` silences.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            let line = format!($($arg)*);
+            progress::suspend(|| println!("{}", line));
+        }
+    };
+}
+
+/// Like [`say!`], for progress messages on stderr.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            let line = format!($($arg)*);
+            progress::suspend(|| eprintln!("{}", line));
+        }
+    };
+}
 
 #[derive(Parser)]
 #[clap(
@@ -18,10 +79,14 @@ use std::process::exit;
     author = "Karl Lattimer <karl@qdh.org.uk> and Rust port contributors",
 )]
 struct Cli {
-    /// TV Name to use from config
+    /// TV Name to use from config, or several separated by commas
     #[clap(short, long)]
     name: Option<String>,
 
+    /// Run the command on every TV in the config at once
+    #[clap(long, conflicts_with_all = ["name", "host"])]
+    all: bool,
+
     /// Use SSL for connection
     #[clap(long)]
     ssl: bool,
@@ -30,6 +95,38 @@ struct Cli {
     #[clap(short, long)]
     debug: bool,
 
+    /// Print nothing but errors; the exit code tells what went wrong
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Log every websocket frame sent and received, to stderr or (with
+    /// --trace-ws=FILE) to a file
+    #[clap(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "-"
+    )]
+    trace_ws: Option<PathBuf>,
+
+    /// IP address or hostname of the TV to control, without reading the
+    /// config file
+    #[clap(long, conflicts_with = "name")]
+    host: Option<String>,
+
+    /// Client key to register with, instead of the stored one
+    #[clap(long)]
+    key: Option<String>,
+
+    /// Config file to use instead of searching the usual locations
+    #[clap(long, env = "LGTV_CONFIG", conflicts_with = "profile")]
+    config: Option<PathBuf>,
+
+    /// Use the TVs of a named profile, e.g. "home" or "office"
+    #[clap(long, env = "LGTV_PROFILE")]
+    profile: Option<String>,
+
     /// Command to execute
     #[clap(subcommand)]
     command: Commands,
@@ -38,7 +135,32 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Scan for LG TVs on the network
-    Scan,
+    Scan {
+        /// Seconds to wait for answers
+        #[clap(long, default_value = "3")]
+        timeout: u64,
+
+        /// How many times to send each search request
+        #[clap(long, default_value = "3")]
+        attempts: usize,
+
+        /// Network interface (name or IP address) to search from
+        #[clap(long)]
+        interface: Option<String>,
+
+        /// Reuse the results of a scan from the last hour, if any
+        #[clap(long, conflicts_with = "watch")]
+        cached: bool,
+
+        /// Keep listening and print TVs as they appear and disappear
+        #[clap(long, conflicts_with = "deep")]
+        watch: bool,
+
+        /// If multicast discovery finds nothing, probe every host in the
+        /// local /24 for the TV's control port
+        #[clap(long)]
+        deep: bool,
+    },
 
     /// Authenticate with a TV
     Auth {
@@ -46,59 +168,171 @@ enum Commands {
         host: String,
         /// Name to give to the TV
         name: String,
+        /// Permissions to request, e.g. "all,-CONTROL_POWER" or
+        /// "CONTROL_AUDIO,CONTROL_INPUT_TV" (default: all)
+        #[clap(long)]
+        permissions: Option<String>,
+        /// Pair by entering a PIN shown on the TV instead of accepting a prompt
+        #[clap(long)]
+        pin: bool,
+        /// Seconds to wait for the pairing to be confirmed on the TV
+        #[clap(long, default_value_t = 60)]
+        timeout: u64,
+        /// Never wait for confirmation on the TV; fail with a JSON error if
+        /// the key isn't already trusted
+        #[clap(long, conflicts_with = "pin")]
+        non_interactive: bool,
+        /// Client key to register with (defaults to the stored key, if any)
+        #[clap(long)]
+        key: Option<String>,
+        /// Keep the client key in the system keyring instead of the config
+        /// file
+        #[clap(long)]
+        keyring: bool,
     },
 
+    /// Scan for TVs, pair with one and save it, step by step
+    Setup,
+
     /// Set a TV as the default
     SetDefault {
         /// TV name
         name: String,
     },
 
+    /// Remove a TV from the config
+    Remove {
+        /// TV name
+        name: String,
+    },
+
+    /// List the configured TVs, checking which are on and which still
+    /// accept their stored key
+    Devices {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
+
+    /// Inspect or migrate the config file
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommands,
+    },
+
+    // ── Command groups ────────────────────────
+    /// Power the TV or its screen on and off
+    Power {
+        #[clap(subcommand)]
+        command: PowerCommands,
+    },
+
+    /// Volume, muting and sound output
+    Audio {
+        #[clap(subcommand)]
+        command: AudioCommands,
+    },
+
+    /// Watch live TV channels
+    Channel {
+        #[clap(subcommand)]
+        command: ChannelCommands,
+    },
+
+    /// Switch between HDMI and other external inputs
+    Input {
+        #[clap(subcommand)]
+        command: InputCommands,
+    },
+
+    /// Launch and close apps
+    App {
+        #[clap(subcommand)]
+        command: AppCommands,
+    },
+
     // ── Power ──────────────────────────────────
     /// Power on the TV (via Wake-on-LAN)
-    On,
+    #[clap(hide = true)]
+    On {
+        /// Wait until the TV answers, sending the packet again every few
+        /// seconds
+        #[clap(long)]
+        wait: bool,
+    },
 
     /// Power off the TV
-    Off,
+    #[clap(hide = true)]
+    Off {
+        /// Turn off after this long, e.g. "30m" or "1h30m", using the TV's
+        /// sleep timer when it offers that delay
+        #[clap(long, value_name = "DELAY", value_parser = parse_delay)]
+        after: Option<std::time::Duration>,
+        /// With --after, wait here instead of using the TV's sleep timer
+        #[clap(long, requires = "after")]
+        local: bool,
+    },
 
     /// Turn the screen off (standby)
-    ScreenOff,
+    #[clap(hide = true)]
+    ScreenOff {
+        /// Turn the screen off after this long, e.g. "10m" (waits here)
+        #[clap(long, value_name = "DELAY", value_parser = parse_delay)]
+        after: Option<std::time::Duration>,
+    },
 
     /// Turn the screen on
+    #[clap(hide = true)]
     ScreenOn,
 
     /// Get the current power state
+    #[clap(hide = true)]
     GetPowerState,
 
+    /// Show power, volume, input, app and channel in one go
+    Status {
+        /// Print JSON instead of text
+        #[clap(long)]
+        json: bool,
+    },
+
     // ── Audio ──────────────────────────────────
     /// Mute/unmute the TV
+    #[clap(hide = true)]
     Mute {
         /// Mute state (true/false)
+        #[clap(action = clap::ArgAction::Set, default_value_t = true)]
         muted: bool,
     },
 
     /// Set volume level
+    #[clap(hide = true)]
     SetVolume {
         /// Volume level (0-100)
         level: u32,
     },
 
     /// Volume up
+    #[clap(hide = true)]
     VolumeUp,
 
     /// Volume down
+    #[clap(hide = true)]
     VolumeDown,
 
     /// Get audio status
+    #[clap(hide = true)]
     AudioStatus,
 
     /// Get current volume
+    #[clap(hide = true)]
     AudioVolume,
 
     /// Get current sound output device
+    #[clap(hide = true)]
     GetSoundOutput,
 
     /// Set sound output device (tv_speaker, external_arc, headphone, etc.)
+    #[clap(hide = true)]
     SetSoundOutput {
         /// Output device name
         output: String,
@@ -106,21 +340,51 @@ enum Commands {
 
     // ── TV Channels ───────────────────────────
     /// Get the current TV channel
+    #[clap(hide = true)]
     GetTvChannel,
 
     /// Set the TV channel
+    #[clap(hide = true)]
     SetTvChannel {
-        /// Channel ID
+        /// Channel number (e.g. "13"), name (e.g. "BBC One") or ID
         channel_id: String,
     },
 
     /// List available channels
-    ListChannels,
+    #[clap(hide = true)]
+    ListChannels {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
+
+    /// Tune to a channel by typing its number on the keypad
+    #[clap(hide = true)]
+    ChannelNumber {
+        /// Channel number
+        number: u32,
+    },
+
+    /// Show what's on the current channel, or the given ones, over the
+    /// next few hours
+    Guide {
+        /// Channel number, name or ID; may be repeated (default: the
+        /// current channel)
+        #[clap(long = "channel", value_name = "CHANNEL")]
+        channels: Vec<String>,
+        /// How many hours ahead to show
+        #[clap(long, default_value_t = 4)]
+        hours: u64,
+        /// Print the programmes as JSON
+        #[clap(long)]
+        json: bool,
+    },
 
     /// Channel up
+    #[clap(hide = true)]
     InputChannelUp,
 
     /// Channel down
+    #[clap(hide = true)]
     InputChannelDown,
 
     // ── Media Controls ────────────────────────
@@ -141,15 +405,22 @@ enum Commands {
 
     // ── Input Switching ───────────────────────
     /// List external inputs (HDMI, etc.)
-    ListInputs,
+    #[clap(hide = true)]
+    ListInputs {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
 
     /// Switch to an input
+    #[clap(hide = true)]
     SetInput {
-        /// Input ID
+        /// Input ID, or a name for it: "hdmi1", "HDMI 2" or the label set
+        /// on the TV
         input_id: String,
     },
 
     /// Set device info for an input
+    #[clap(hide = true)]
     SetDeviceInfo {
         /// Device ID
         id: String,
@@ -161,30 +432,39 @@ enum Commands {
 
     // ── Applications ──────────────────────────
     /// List installed apps
-    ListApps,
+    #[clap(hide = true)]
+    ListApps {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
 
     /// List launch points
+    #[clap(hide = true)]
     ListLaunchPoints,
 
     /// Launch an app
+    #[clap(hide = true)]
     StartApp {
-        /// App ID
+        /// App ID, or part of the app's title, e.g. "prime"
         app_id: String,
     },
 
     /// Close an app
+    #[clap(hide = true)]
     CloseApp {
         /// App ID
         app_id: String,
     },
 
     /// Launch an app with a custom JSON payload
+    #[clap(hide = true)]
     OpenAppWithPayload {
         /// JSON payload string
         payload: String,
     },
 
     /// Get info about the foreground app
+    #[clap(hide = true)]
     GetForegroundAppInfo,
 
     // ── Browser & YouTube ─────────────────────
@@ -223,6 +503,12 @@ enum Commands {
     Notification {
         /// Message to display
         message: String,
+        /// Image file or http(s) URL to show next to the message
+        #[clap(long, conflicts_with = "app_icon")]
+        icon: Option<String>,
+        /// Show the icon of one of the TV's apps, e.g. "netflix"
+        #[clap(long)]
+        app_icon: Option<String>,
     },
 
     /// Send a notification with an icon
@@ -257,10 +543,18 @@ enum Commands {
     Input3dOff,
 
     // ── Picture Settings ──────────────────────
+    /// Read and change picture settings
+    Picture {
+        #[clap(subcommand)]
+        command: PictureCommands,
+    },
+
     /// Get picture settings
+    #[clap(hide = true)]
     GetPictureSettings,
 
     /// Set picture mode
+    #[clap(hide = true)]
     SetPictureMode {
         /// Picture mode name
         mode: String,
@@ -280,423 +574,2687 @@ enum Commands {
     /// Send the enter key
     SendEnterKey,
 
+    /// Send any SSAP request and print the TV's response
+    Raw {
+        /// Endpoint, e.g. "ssap://audio/getVolume" (the "ssap://" may be
+        /// left out)
+        uri: String,
+        /// JSON payload to send with the request
+        #[clap(long)]
+        payload: Option<String>,
+        /// "subscribe" keeps printing updates until interrupted
+        #[clap(long = "type", default_value = "request", value_parser = ["request", "subscribe"])]
+        kind: String,
+    },
+
+    /// Type text into the focused input field. Without the TV's remote
+    /// keyboard (no field is focused) only digits can be typed, as number
+    /// buttons
+    Type {
+        /// Text to type
+        text: String,
+        /// Replace the current contents of the field
+        #[clap(long)]
+        replace: bool,
+        /// Press enter after typing
+        #[clap(long)]
+        enter: bool,
+    },
+
     /// Send button presses to the TV
     SendButton {
-        /// Button names (e.g., up, down, left, right, etc.)
+        /// Button names (e.g., up, down, left, right, etc.), or "-" to read
+        /// them line by line from stdin
         #[clap(required = true)]
         buttons: Vec<String>,
     },
 
+    /// Drag the pointer (press, move, release), e.g. to scroll long lists
+    Drag {
+        /// Horizontal distance in pixels
+        #[clap(allow_hyphen_values = true)]
+        dx: i32,
+        /// Vertical distance in pixels
+        #[clap(allow_hyphen_values = true)]
+        dy: i32,
+    },
+
+    /// Use a local mouse as the TV pointer
+    #[cfg(target_os = "linux")]
+    Mouse {
+        #[clap(subcommand)]
+        action: MouseAction,
+    },
+
+    /// Control the TV pointer and buttons with a local gamepad
+    #[cfg(all(feature = "gamepad", target_os = "linux"))]
+    Gamepad {
+        /// Joystick device to read
+        #[clap(long, default_value = "/dev/input/js0")]
+        device: std::path::PathBuf,
+    },
+
+    /// Check that the stored client key is still accepted by the TV
+    Verify,
+
     /// Print stored config for the TV
     Serialise,
+
+    /// Run several commands over one connection, in order, stopping at the
+    /// first one that fails
+    Batch {
+        /// Commands as they would be written after "lgtv", each quoted,
+        /// e.g. "screen-off" "set-volume 12"
+        #[clap(required = true)]
+        commands: Vec<String>,
+    },
+
+    /// Type commands interactively over one connection, with history and
+    /// tab completion
+    Shell,
+
+    /// Run JSON commands, one per line, answering each with a line of JSON
+    Exec {
+        /// File to read the commands from, or "-" for stdin
+        input: PathBuf,
+    },
+
+    /// Serve a remote control page to browsers on the LAN, such as the one
+    /// on a phone, for callers with the token printed at startup
+    ServeWeb {
+        /// Address and port to listen on
+        #[clap(long, default_value = "0.0.0.0:8080")]
+        listen: std::net::SocketAddr,
+
+        /// Token the page and API callers must present, instead of a random
+        /// one printed at startup
+        #[clap(long, env = "LGTV_WEB_TOKEN")]
+        token: Option<String>,
+    },
 }
 
-/// Print a JSON value as pretty-printed output.
-fn print_response(value: &Value) {
-    if let Ok(s) = serde_json::to_string_pretty(value) {
-        println!("{}", s);
-    }
+/// One command of a batch or a shell line, parsed as if it had been given
+/// on its own.
+#[derive(Parser)]
+#[clap(no_binary_name = true)]
+struct CommandLine {
+    #[clap(subcommand)]
+    command: Commands,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print where the config file is (or would be created)
+    Path,
+    /// Check the config file and every TV in it, suggesting fixes
+    Doctor,
+    /// List the stored TVs and which one is the default
+    Show {
+        /// Print client keys instead of hiding them
+        #[clap(long)]
+        show_keys: bool,
+    },
+    /// Encrypt the stored client keys, with the passphrase in
+    /// LGTV_PASSPHRASE or one kept in the system keyring
+    Encrypt {
+        /// Keep a generated passphrase in the system keyring instead of
+        /// reading LGTV_PASSPHRASE
+        #[clap(long)]
+        keyring: bool,
+    },
+    /// Store the client keys in plain text again
+    Decrypt,
+    /// Print the whole config as JSON, with client keys included, for
+    /// copying to another machine
+    Export,
+    /// Add the TVs of a file written by `config export`
+    Import {
+        /// File to import
+        path: PathBuf,
+        /// Replace TVs that already exist under the same name instead of
+        /// merging their fields
+        #[clap(long)]
+        overwrite: bool,
+    },
+    /// Copy TVs paired with the Python lgtv tool, Home Assistant or
+    /// pywebostv into this config
+    ImportPython {
+        /// File to import (default: the Python tool's config)
+        path: Option<PathBuf>,
+        /// TV address, for key dumps that don't include it
+        #[clap(long)]
+        host: Option<String>,
+        /// Replace TVs that already exist under the same name
+        #[clap(long)]
+        force: bool,
+    },
+}
 
-    // Configure logging
-    if cli.debug {
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
-    } else {
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-    }
+// ──── Command groups ────
+
+/// How list commands show what they found: a table at a terminal, JSON
+/// otherwise.
+#[derive(Args, Clone)]
+struct ListArgs {
+    /// Only show rows containing this text
+    #[clap(long, value_name = "TEXT")]
+    filter: Option<String>,
+    /// Sort the rows by this column, e.g. name
+    #[clap(long, value_name = "COLUMN")]
+    sort: Option<String>,
+    /// Print JSON instead of a table
+    #[clap(long)]
+    json: bool,
+}
+// Each command in a group does the same as one of the older top-level
+// commands, which stay (hidden from --help) so existing scripts keep working
 
-    match &cli.command {
-        Commands::Scan => {
-            let results = scan_for_tvs().await?;
+#[derive(Subcommand, Clone)]
+enum PowerCommands {
+    /// Power on the TV (via Wake-on-LAN)
+    On {
+        /// Wait until the TV answers, sending the packet again every few
+        /// seconds
+        #[clap(long)]
+        wait: bool,
+    },
+    /// Power off the TV
+    Off {
+        /// Turn off after this long, e.g. "30m" or "1h30m", using the TV's
+        /// sleep timer when it offers that delay
+        #[clap(long, value_name = "DELAY", value_parser = parse_delay)]
+        after: Option<std::time::Duration>,
+        /// With --after, wait here instead of using the TV's sleep timer
+        #[clap(long, requires = "after")]
+        local: bool,
+    },
+    /// Turn the screen off, leaving the TV on
+    ScreenOff {
+        /// Turn the screen off after this long, e.g. "10m" (waits here)
+        #[clap(long, value_name = "DELAY", value_parser = parse_delay)]
+        after: Option<std::time::Duration>,
+    },
+    /// Turn the screen on
+    ScreenOn,
+    /// Get the current power state
+    State,
+}
 
-            if !results.is_empty() {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&json!({
-                        "result": "ok",
-                        "count": results.len(),
-                        "list": results
-                    }))?
-                );
-                exit(0);
-            } else {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&json!({
-                        "result": "failed",
-                        "count": 0
-                    }))?
-                );
-                exit(1);
-            }
-        }
+#[derive(Subcommand, Clone)]
+enum AudioCommands {
+    /// Get the current volume
+    Get,
+    /// Set the volume level
+    Set {
+        /// Volume level (0-100)
+        level: u32,
+    },
+    /// Volume up
+    Up,
+    /// Volume down
+    Down,
+    /// Mute the TV
+    Mute,
+    /// Unmute the TV
+    Unmute,
+    /// Get volume, mute state and sound output at once
+    Status,
+    /// Get the current sound output device
+    Output,
+    /// Set the sound output device (tv_speaker, external_arc, headphone, etc.)
+    SetOutput {
+        /// Output device name
+        output: String,
+    },
+}
 
-        Commands::Auth { host, name } => {
-            let config_path = find_config()?;
-            let mut config = read_config(&config_path).unwrap_or_else(|_| json!({}));
+#[derive(Subcommand, Clone)]
+enum ChannelCommands {
+    /// Get the current channel
+    Get,
+    /// Switch to a channel
+    Set {
+        /// Channel number (e.g. "13"), name (e.g. "BBC One") or ID
+        channel: String,
+    },
+    /// List available channels
+    List {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
+    /// Tune to a channel by typing its number on the keypad
+    Number {
+        /// Channel number
+        number: u32,
+    },
+    /// Channel up
+    Up,
+    /// Channel down
+    Down,
+    /// Show what's on over the next few hours
+    Guide {
+        /// Channel number, name or ID; may be repeated (default: the
+        /// current channel)
+        #[clap(long = "channel", value_name = "CHANNEL")]
+        channels: Vec<String>,
+        /// How many hours ahead to show
+        #[clap(long, default_value_t = 4)]
+        hours: u64,
+        /// Print the programmes as JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
 
-            let mut auth = LgtvAuth::new(name, host, cli.ssl)?;
-            auth.connect().await?;
+#[derive(Subcommand, Clone)]
+enum InputCommands {
+    /// List external inputs (HDMI, etc.)
+    List {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
+    /// Switch to an input
+    Set {
+        /// Input ID, or a name for it: "hdmi1", "HDMI 2" or the label set
+        /// on the TV
+        input: String,
+    },
+    /// Set the icon and label of an input
+    SetDeviceInfo {
+        /// Input ID
+        id: String,
+        /// Icon name
+        icon: String,
+        /// Label
+        label: String,
+    },
+}
 
-            config[name] = auth.serialise();
-            write_config(&config_path, &config)?;
+#[derive(Subcommand, Clone)]
+enum AppCommands {
+    /// List installed apps
+    List {
+        #[clap(flatten)]
+        view: ListArgs,
+    },
+    /// List launch points
+    LaunchPoints,
+    /// Launch an app
+    Launch {
+        /// App ID, or part of the app's title, e.g. "prime"
+        app: String,
+    },
+    /// Launch an app with a custom JSON payload
+    LaunchWithPayload {
+        /// JSON payload string
+        payload: String,
+    },
+    /// Close an app
+    Close {
+        /// App ID
+        app: String,
+    },
+    /// Get info about the foreground app
+    Foreground,
+}
 
-            println!("Wrote config file: {}", config_path.display());
-            exit(0);
-        }
+#[derive(Subcommand, Clone)]
+enum PictureCommands {
+    /// Get picture settings (default: backlight, contrast, brightness,
+    /// color and picture mode)
+    Get {
+        /// Settings to read, e.g. "colorTemperature"
+        keys: Vec<String>,
+    },
+    /// Change several settings at once, e.g. "backlight=40 contrast=85
+    /// colorTemperature=W30"; nothing is changed if any of them is invalid
+    Set {
+        /// Settings as key=value
+        #[clap(required = true, value_name = "KEY=VALUE")]
+        settings: Vec<String>,
+    },
+    /// Set the picture mode
+    Mode {
+        /// Picture mode name, e.g. "cinema"
+        mode: String,
+    },
+    /// List the settings `set` knows and the values they take
+    Keys,
+}
 
-        Commands::SetDefault { name } => {
-            let config_path = find_config()?;
-            let mut config = match read_config(&config_path) {
-                Ok(c) => c,
-                Err(_) => {
-                    println!("No config file found");
-                    exit(1);
+impl Commands {
+    /// The top-level command a command in a group stands for, or `None`
+    /// for commands outside the groups.
+    fn ungrouped(&self) -> Option<Commands> {
+        let command = match self {
+            Commands::Power { command } => match command.clone() {
+                PowerCommands::On { wait } => Commands::On { wait },
+                PowerCommands::Off { after, local } => Commands::Off { after, local },
+                PowerCommands::ScreenOff { after } => Commands::ScreenOff { after },
+                PowerCommands::ScreenOn => Commands::ScreenOn,
+                PowerCommands::State => Commands::GetPowerState,
+            },
+            Commands::Audio { command } => match command.clone() {
+                AudioCommands::Get => Commands::AudioVolume,
+                AudioCommands::Set { level } => Commands::SetVolume { level },
+                AudioCommands::Up => Commands::VolumeUp,
+                AudioCommands::Down => Commands::VolumeDown,
+                AudioCommands::Mute => Commands::Mute { muted: true },
+                AudioCommands::Unmute => Commands::Mute { muted: false },
+                AudioCommands::Status => Commands::AudioStatus,
+                AudioCommands::Output => Commands::GetSoundOutput,
+                AudioCommands::SetOutput { output } => Commands::SetSoundOutput { output },
+            },
+            Commands::Channel { command } => match command.clone() {
+                ChannelCommands::Get => Commands::GetTvChannel,
+                ChannelCommands::Set { channel } => Commands::SetTvChannel {
+                    channel_id: channel,
+                },
+                ChannelCommands::List { view } => Commands::ListChannels { view },
+                ChannelCommands::Number { number } => Commands::ChannelNumber { number },
+                ChannelCommands::Up => Commands::InputChannelUp,
+                ChannelCommands::Down => Commands::InputChannelDown,
+                ChannelCommands::Guide {
+                    channels,
+                    hours,
+                    json,
+                } => Commands::Guide {
+                    channels,
+                    hours,
+                    json,
+                },
+            },
+            Commands::Input { command } => match command.clone() {
+                InputCommands::List { view } => Commands::ListInputs { view },
+                InputCommands::Set { input } => Commands::SetInput { input_id: input },
+                InputCommands::SetDeviceInfo { id, icon, label } => {
+                    Commands::SetDeviceInfo { id, icon, label }
                 }
-            };
+            },
+            Commands::App { command } => match command.clone() {
+                AppCommands::List { view } => Commands::ListApps { view },
+                AppCommands::LaunchPoints => Commands::ListLaunchPoints,
+                AppCommands::Launch { app } => Commands::StartApp { app_id: app },
+                AppCommands::LaunchWithPayload { payload } => {
+                    Commands::OpenAppWithPayload { payload }
+                }
+                AppCommands::Close { app } => Commands::CloseApp { app_id: app },
+                AppCommands::Foreground => Commands::GetForegroundAppInfo,
+            },
+            _ => return None,
+        };
+        Some(command)
+    }
+}
 
-            if config.get(name).is_none() {
-                println!("TV not found in config");
-                exit(1);
-            }
+#[cfg(target_os = "linux")]
+#[derive(Subcommand)]
+enum MouseAction {
+    /// Stream local mouse motion, clicks and wheel to the TV pointer
+    Passthrough {
+        /// Mouse device to read
+        #[clap(long, default_value = "/dev/input/mice")]
+        device: std::path::PathBuf,
+        /// Pointer speed multiplier
+        #[clap(long, default_value_t = 1.0)]
+        speed: f32,
+    },
+}
 
-            config["_default"] = json!(name);
-            write_config(&config_path, &config)?;
+/// Print `question` to stderr and read one trimmed line of the answer.
+async fn ask<R>(input: &mut R, question: &str) -> Result<String>
+where
+    R: AsyncBufRead + Unpin,
+{
+    eprint!("{}", question);
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line).await?;
+    Ok(line.trim().to_string())
+}
 
-            println!("Wrote default to config file: {}", config_path.display());
-            exit(0);
+/// The config file selected by `--config` or `--profile`, or the one
+/// found in the usual locations.
+fn config_path(cli: &Cli) -> Result<PathBuf> {
+    match (&cli.config, &cli.profile) {
+        (Some(path), _) => Ok(path.clone()),
+        (None, Some(profile)) => find_profile(profile),
+        (None, None) => find_config(),
+    }
+}
+
+/// Print what is wrong with the config at `path` and how to fix it,
+/// returning how many problems there are. Warnings (things that only keep
+/// some commands from working) aren't counted.
+async fn doctor(path: &Path) -> usize {
+    let mut problems = 0;
+    let mut problem = |message: String, fix: &str| {
+        problems += 1;
+        println!("problem: {}\n    fix: {}", message, fix);
+    };
+    let warning = |message: String, fix: &str| println!("warning: {}\n    fix: {}", message, fix);
+
+    say!("config: {}", path.display());
+    if !path.exists() {
+        problem(
+            "the config file does not exist".to_string(),
+            "pair a TV with 'lgtv setup' or 'lgtv auth <host> <name>'",
+        );
+        return problems;
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let probe = dir.join(format!(".lgtv-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+        }
+        Err(e) => problem(
+            format!("{} is not writable ({})", dir.display(), e),
+            "fix the directory's permissions, or point --config at a writable file",
+        ),
+    }
+    #[cfg(unix)]
+    if let Ok(meta) = std::fs::metadata(path) {
+        use std::os::unix::fs::PermissionsExt;
+        if meta.permissions().mode() & 0o077 != 0 {
+            warning(
+                "the config file can be read by other users".to_string(),
+                &format!("chmod 600 {}", path.display()),
+            );
         }
+    }
 
-        // Commands that require a TV configuration
-        _ => {
-            let tv_name = match &cli.name {
-                Some(name) => name.clone(),
-                None => {
-                    let config_path = find_config()?;
-                    let config = match read_config(&config_path) {
-                        Ok(c) => c,
-                        Err(_) => {
-                            println!("No config file found");
-                            exit(1);
-                        }
-                    };
+    let config = match read_config(path) {
+        Ok(config) => config,
+        Err(e) => {
+            problem(
+                e.to_string(),
+                "correct the file by hand, or restore the previous version from its .bak file",
+            );
+            return problems;
+        }
+    };
+    if config.tvs.is_empty() {
+        warning(
+            "no TVs are configured".to_string(),
+            "pair one with 'lgtv setup'",
+        );
+    }
+    match &config.default {
+        Some(name) if !config.tvs.contains_key(name) => problem(
+            format!("_default is '{}', which is not a configured TV", name),
+            "choose another with 'lgtv set-default <name>'",
+        ),
+        None if config.tvs.len() > 1 => warning(
+            "no default TV is set, so every command needs -n".to_string(),
+            "pick one with 'lgtv set-default <name>'",
+        ),
+        _ => {}
+    }
 
-                    match config.get("_default").and_then(|v| v.as_str()) {
-                        Some(default_name) => default_name.to_string(),
-                        None => {
-                            println!("A TV name is required. Set one with -n/--name or the set-default command.");
-                            exit(1);
-                        }
-                    }
-                }
-            };
+    for (name, entry) in &config.tvs {
+        say!("tv '{}':", name);
+        match entry.client_key() {
+            Ok(Some(_)) => {}
+            Ok(None) => problem(
+                format!("'{}' has no client key", name),
+                &format!("pair again with 'lgtv auth <host> {}'", name),
+            ),
+            Err(e) => problem(
+                format!("the client key of '{}' can't be read: {}", name, e),
+                "check LGTV_PASSPHRASE or the keyring, or pair again",
+            ),
+        }
+        if entry.mac.is_none() {
+            warning(
+                format!("'{}' has no MAC address, so 'lgtv on' can't wake it", name),
+                "add a \"mac\" field (shown by 'lgtv scan')",
+            );
+        }
 
-            let config_path = find_config()?;
-            let config = match read_config(&config_path) {
-                Ok(c) => c,
-                Err(_) => {
-                    println!("No config file found");
-                    exit(1);
-                }
-            };
+        let Some((host, port)) = control_address(entry) else {
+            problem(
+                format!("'{}' has neither an ip nor a hostname", name),
+                "add an \"ip\" field (shown by 'lgtv scan')",
+            );
+            continue;
+        };
+        match probe_port(host, port).await {
+            Ok(()) => say!("    reachable at {}:{}", host, port),
+            Err(Some(e)) => warning(
+                format!("{}:{} refused the connection ({})", host, port, e),
+                "make sure the TV is on; newer firmware only listens with ssl = true",
+            ),
+            Err(None) => warning(
+                format!("{}:{} did not answer", host, port),
+                "make sure the TV is on, or run 'lgtv scan' in case its address changed",
+            ),
+        }
+    }
+    problems
+}
 
-            let tv_config = match config.get(&tv_name) {
-                Some(c) => c,
-                None => {
-                    println!(
-                        "No entry with the name '{}' was found in the configuration at {}.",
-                        tv_name,
-                        config_path.display()
-                    );
-                    exit(1);
-                }
-            };
+/// Host and port of the TV's control socket.
+fn control_address(entry: &TvConfigEntry) -> Option<(&str, u16)> {
+    let host = entry.ip.as_deref().or(entry.hostname.as_deref())?;
+    let port = entry.port.unwrap_or(if entry.ssl { 3001 } else { 3000 });
+    Some((host.trim_start_matches('[').trim_end_matches(']'), port))
+}
 
-            let ip = tv_config.get("ip").and_then(|v| v.as_str());
-            let mac = tv_config.get("mac").and_then(|v| v.as_str());
-            let key = tv_config.get("key").and_then(|v| v.as_str());
-            let hostname = tv_config.get("hostname").and_then(|v| v.as_str());
+/// Whether something accepts connections on `host:port`; the error is
+/// `None` when nothing answered at all.
+async fn probe_port(host: &str, port: u16) -> std::result::Result<(), Option<std::io::Error>> {
+    let connect = tokio::net::TcpStream::connect((host, port));
+    match tokio::time::timeout(std::time::Duration::from_secs(2), connect).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(Some(e)),
+        Err(_) => Err(None),
+    }
+}
 
-            match &cli.command {
-                Commands::SendButton { buttons } => {
-                    let mut cursor =
-                        LgtvCursor::new(&tv_name, ip, mac, key, hostname, cli.ssl).await?;
-                    cursor
-                        .execute(buttons.iter().map(|s| s.as_str()).collect())
-                        .await?;
-                }
+/// What `lgtv devices` found out about one TV.
+struct DeviceReport {
+    name: String,
+    default: bool,
+    address: Option<String>,
+    mac: Option<String>,
+    model: Option<String>,
+    port_open: bool,
+    /// The result of registering with the stored key, if the port was open
+    /// and there is a key
+    pairing: Option<Result<VerifyStatus>>,
+}
 
-                Commands::Serialise => {
-                    print_response(tv_config);
-                }
+impl DeviceReport {
+    fn pairing_text(&self) -> String {
+        match &self.pairing {
+            None if !self.port_open => "-".to_string(),
+            None => "no key".to_string(),
+            Some(Ok(VerifyStatus::Valid)) => "ok".to_string(),
+            Some(Ok(VerifyStatus::KeyRejected)) => "key rejected".to_string(),
+            Some(Ok(VerifyStatus::Unreachable(reason))) => format!("failed: {}", reason),
+            Some(Err(e)) => format!("failed: {}", e),
+        }
+    }
 
-                // All commands that use the remote
-                _ => {
-                    let mut remote = LgtvRemote::new(&tv_name, ip, mac, key, hostname, cli.ssl)?;
+    fn to_json(&self) -> Value {
+        let pairing = match &self.pairing {
+            None if !self.port_open => json!(null),
+            None => json!({"status": "no_key"}),
+            Some(Ok(status)) => json!(status),
+            Some(Err(e)) => json!({"status": "error", "reason": e.to_string()}),
+        };
+        json!({
+            "name": self.name,
+            "default": self.default,
+            "address": self.address,
+            "mac": self.mac,
+            "model": self.model,
+            "port_open": self.port_open,
+            "pairing": pairing,
+        })
+    }
+}
 
-                    match &cli.command {
-                        // ── Power ─────────────────────────────
-                        Commands::On => match remote.on().await {
-                            Ok(_) => println!("Power on command sent successfully"),
-                            Err(e) => {
-                                if e.to_string().contains("MAC address is required") {
-                                    println!("Error: MAC address is required for power on. Please run 'lgtv scan' and then 'lgtv auth' to get the MAC address.");
-                                } else {
-                                    println!("Error: {}", e);
-                                }
-                                exit(1);
-                            }
-                        },
-                        Commands::Off => {
-                            remote.connect().await?;
-                            remote.off().await?;
-                        }
-                        Commands::ScreenOff => {
-                            remote.connect().await?;
-                            remote.screen_off().await?;
-                        }
-                        Commands::ScreenOn => {
-                            remote.connect().await?;
-                            remote.screen_on().await?;
-                        }
-                        Commands::GetPowerState => {
-                            remote.connect().await?;
-                            let resp = remote.get_power_state().await?;
-                            print_response(&resp);
-                        }
+/// Probe every configured TV at once: is its control port open, and does
+/// it still accept the stored key?
+async fn devices(config: &Config) -> Vec<DeviceReport> {
+    let probes = config.tvs.iter().map(|(name, entry)| async move {
+        let address = control_address(entry);
+        let port_open = match address {
+            Some((host, port)) => probe_port(host, port).await.is_ok(),
+            None => false,
+        };
+        let pairing = match entry.client_key() {
+            Ok(Some(_)) if port_open => Some(match LgtvRemote::from_entry(name, entry) {
+                Ok(remote) => remote.verify().await,
+                Err(e) => Err(e),
+            }),
+            Err(e) if port_open => Some(Err(e)),
+            _ => None,
+        };
+        DeviceReport {
+            name: name.clone(),
+            default: config.default.as_ref() == Some(name),
+            address: address.map(|(host, port)| format!("{}:{}", host, port)),
+            mac: entry.mac.clone(),
+            model: entry.model.clone(),
+            port_open,
+            pairing,
+        }
+    });
+    futures_util::future::join_all(probes).await
+}
 
-                        // ── Audio ─────────────────────────────
-                        Commands::Mute { muted } => {
-                            remote.connect().await?;
-                            remote.mute(*muted).await?;
-                        }
-                        Commands::SetVolume { level } => {
-                            remote.connect().await?;
-                            remote.set_volume(*level).await?;
-                        }
-                        Commands::VolumeUp => {
-                            remote.connect().await?;
-                            remote.volume_up().await?;
-                        }
-                        Commands::VolumeDown => {
-                            remote.connect().await?;
-                            remote.volume_down().await?;
-                        }
-                        Commands::AudioStatus => {
-                            remote.connect().await?;
-                            let resp = remote.audio_status().await?;
-                            print_response(&resp);
-                        }
-                        Commands::AudioVolume => {
-                            remote.connect().await?;
-                            let resp = remote.audio_volume().await?;
-                            print_response(&resp);
-                        }
-                        Commands::GetSoundOutput => {
-                            remote.connect().await?;
-                            let resp = remote.get_sound_output().await?;
-                            print_response(&resp);
-                        }
-                        Commands::SetSoundOutput { output } => {
-                            remote.connect().await?;
-                            remote.set_sound_output(output).await?;
-                        }
+/// The `devices` table, narrowed down and sorted as asked.
+fn devices_table(reports: &[DeviceReport], view: &ListArgs) -> Result<Table> {
+    let mut table = Table::new(&["NAME", "ADDRESS", "MAC", "MODEL", "PORT", "PAIRING"]);
+    for report in reports {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        table.push(vec![
+            if report.default {
+                format!("{} *", report.name)
+            } else {
+                report.name.clone()
+            },
+            text(&report.address),
+            text(&report.mac),
+            text(&report.model),
+            if report.port_open { "open" } else { "closed" }.to_string(),
+            report.pairing_text(),
+        ]);
+    }
+    view.apply(&mut table)?;
+    Ok(table)
+}
 
-                        // ── TV Channels ───────────────────────
-                        Commands::GetTvChannel => {
-                            remote.connect().await?;
-                            let resp = remote.get_tv_channel().await?;
-                            print_response(&resp);
-                        }
-                        Commands::SetTvChannel { channel_id } => {
-                            remote.connect().await?;
-                            remote.set_tv_channel(channel_id).await?;
-                        }
-                        Commands::ListChannels => {
-                            remote.connect().await?;
-                            let resp = remote.list_channels().await?;
-                            print_response(&resp);
-                        }
-                        Commands::InputChannelUp => {
-                            remote.connect().await?;
-                            remote.input_channel_up().await?;
-                        }
-                        Commands::InputChannelDown => {
-                            remote.connect().await?;
-                            remote.input_channel_down().await?;
-                        }
+impl ListArgs {
+    fn apply(&self, table: &mut Table) -> Result<()> {
+        if let Some(filter) = &self.filter {
+            table.filter(filter);
+        }
+        if let Some(column) = &self.sort {
+            table.sort(column)?;
+        }
+        Ok(())
+    }
+}
 
-                        // ── Media Controls ────────────────────
-                        Commands::InputMediaPlay => {
-                            remote.connect().await?;
-                            remote.input_media_play().await?;
-                        }
-                        Commands::InputMediaPause => {
-                            remote.connect().await?;
-                            remote.input_media_pause().await?;
-                        }
-                        Commands::InputMediaStop => {
-                            remote.connect().await?;
-                            remote.input_media_stop().await?;
-                        }
-                        Commands::InputMediaRewind => {
-                            remote.connect().await?;
-                            remote.input_media_rewind().await?;
-                        }
-                        Commands::InputMediaFastForward => {
-                            remote.connect().await?;
-                            remote.input_media_fast_forward().await?;
-                        }
+/// `field` of `item` as text, or "" if it isn't a string.
+fn text_field(item: &Value, field: &str) -> String {
+    item.get(field)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
 
-                        // ── Input Switching ───────────────────
-                        Commands::ListInputs => {
-                            remote.connect().await?;
-                            let resp = remote.list_inputs().await?;
-                            print_response(&resp);
-                        }
-                        Commands::SetInput { input_id } => {
-                            remote.connect().await?;
-                            remote.set_input(input_id).await?;
-                        }
-                        Commands::SetDeviceInfo { id, icon, label } => {
-                            remote.connect().await?;
-                            remote.set_device_info(id, icon, label).await?;
-                        }
+/// Print the list under `key` in `response` as a table when talking to a
+/// terminal, or the response itself otherwise. `--filter` and `--sort`
+/// apply to both; the JSON keeps only the items left in the table.
+fn print_list(
+    session: &mut Session<'_>,
+    mut response: Value,
+    key: &str,
+    header: &[&'static str],
+    view: &ListArgs,
+    cells: impl Fn(&Value) -> Vec<String>,
+) -> Result<()> {
+    let items = match response.get_mut(key) {
+        Some(Value::Array(items)) => std::mem::take(items),
+        _ => Vec::new(),
+    };
+    let mut table = Table::new(header);
+    for item in &items {
+        table.push(cells(item));
+    }
+    view.apply(&mut table)?;
+
+    if view.json || session.capturing() || !std::io::stdout().is_terminal() {
+        let kept: Vec<Value> = table
+            .indices()
+            .into_iter()
+            .map(|i| items[i].clone())
+            .collect();
+        if let Some(list) = response.get_mut(key) {
+            *list = json!(kept);
+        }
+        session.print(response);
+    } else if !QUIET.load(Ordering::Relaxed) {
+        table.print();
+    }
+    Ok(())
+}
 
-                        // ── Applications ──────────────────────
-                        Commands::ListApps => {
-                            remote.connect().await?;
-                            let resp = remote.list_apps().await?;
-                            print_response(&resp);
-                        }
-                        Commands::ListLaunchPoints => {
-                            remote.connect().await?;
-                            let resp = remote.list_launch_points().await?;
-                            print_response(&resp);
-                        }
-                        Commands::StartApp { app_id } => {
-                            remote.connect().await?;
-                            remote.start_app(app_id).await?;
-                        }
-                        Commands::CloseApp { app_id } => {
-                            remote.connect().await?;
-                            remote.close_app(app_id).await?;
-                        }
-                        Commands::OpenAppWithPayload { payload } => {
-                            let parsed: Value = serde_json::from_str(payload).map_err(|e| {
-                                lgtv::error::LgtvError::CommandError(format!(
-                                    "Invalid JSON payload: {}",
-                                    e
-                                ))
-                            })?;
-                            remote.connect().await?;
-                            remote.open_app_with_payload(parsed).await?;
-                        }
-                        Commands::GetForegroundAppInfo => {
-                            remote.connect().await?;
-                            let resp = remote.get_foreground_app_info().await?;
-                            print_response(&resp);
-                        }
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
 
-                        // ── Browser & YouTube ─────────────────
-                        Commands::OpenBrowserAt { url } => {
-                            remote.connect().await?;
-                            remote.open_browser_at(url).await?;
-                        }
-                        Commands::OpenYoutubeId { video_id } => {
-                            remote.connect().await?;
-                            remote.open_youtube_id(video_id).await?;
-                        }
-                        Commands::OpenYoutubeUrl { url } => {
-                            remote.connect().await?;
-                            remote.open_youtube_url(url).await?;
-                        }
-                        Commands::OpenYoutubeLegacyId { video_id } => {
-                            remote.connect().await?;
-                            remote.open_youtube_legacy_id(video_id).await?;
-                        }
-                        Commands::OpenYoutubeLegacyUrl { url } => {
-                            remote.connect().await?;
-                            remote.open_youtube_legacy_url(url).await?;
-                        }
+/// The connection that commands for one TV go over. The remote and the
+/// pointer socket are opened when a command first needs them and reused
+/// after that, so a batch registers with the TV only once.
+struct Session<'a> {
+    tv_name: &'a str,
+    entry: &'a TvConfigEntry,
+    /// Remote to use instead of building one from `entry`, not yet connected
+    prepared: Option<LgtvRemote>,
+    remote: Option<LgtvRemote>,
+    cursor: Option<LgtvCursor>,
+    /// Responses collected instead of printed, for `exec`
+    captured: Option<Vec<Value>>,
+}
+
+impl<'a> Session<'a> {
+    fn new(tv_name: &'a str, entry: &'a TvConfigEntry) -> Self {
+        Self {
+            tv_name,
+            entry,
+            prepared: None,
+            remote: None,
+            cursor: None,
+            captured: None,
+        }
+    }
+
+    /// A session over `remote`, such as one of an [`LgtvGroup`]'s.
+    fn with_remote(tv_name: &'a str, entry: &'a TvConfigEntry, remote: LgtvRemote) -> Self {
+        Self {
+            prepared: Some(remote),
+            ..Self::new(tv_name, entry)
+        }
+    }
+
+    fn capturing(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    /// Print a response from the TV, or keep it if output is captured.
+    fn print(&mut self, value: Value) {
+        match &mut self.captured {
+            Some(captured) => captured.push(value),
+            None => print_response(&value),
+        }
+    }
+
+    /// A fresh remote, for commands that manage their own connection.
+    fn unconnected_remote(&self) -> Result<LgtvRemote> {
+        match &self.prepared {
+            Some(remote) => Ok(remote.clone()),
+            None => LgtvRemote::from_entry(self.tv_name, self.entry),
+        }
+    }
+
+    async fn remote(&mut self) -> Result<&mut LgtvRemote> {
+        if let Some(cursor) = &mut self.cursor {
+            return Ok(cursor.remote_mut());
+        }
+        let remote = match self.remote.take() {
+            Some(remote) => remote,
+            None => {
+                let remote = match &self.prepared {
+                    Some(remote) => remote.clone(),
+                    None => LgtvRemote::from_entry(self.tv_name, self.entry)?,
+                };
+                remote.connect().await?;
+                remote
+            }
+        };
+        Ok(self.remote.insert(remote))
+    }
+
+    async fn cursor(&mut self) -> Result<&mut LgtvCursor> {
+        let cursor = match self.cursor.take() {
+            Some(cursor) => cursor,
+            None => {
+                let mut cursor = match self.remote.take() {
+                    Some(remote) => LgtvCursor::from_connected(remote).await?,
+                    None => LgtvCursor::from_remote(self.unconnected_remote()?).await?,
+                };
+                if let Some(timing) = &self.entry.button_delays {
+                    cursor.set_timing(timing.clone());
+                }
+                cursor
+            }
+        };
+        Ok(self.cursor.insert(cursor))
+    }
+}
+
+/// Split a command line into words like a shell would, honouring single
+/// and double quotes and backslash escapes.
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(LgtvError::CommandError(format!(
+            "Unterminated quote in '{}'",
+            line
+        )));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parse one command of a batch or an `exec` stream. Commands that manage
+/// the config rather than talk to the TV are refused.
+fn parse_command(words: &[String], context: &str) -> Result<Commands> {
+    let Some(name) = words.first() else {
+        return Err(LgtvError::CommandError(format!(
+            "Empty command in {}",
+            context
+        )));
+    };
+    let parsed = CommandLine::try_parse_from(words).map_err(|e| {
+        let message = e.to_string();
+        let first = message.lines().next().unwrap_or_default();
+        LgtvError::CommandError(format!(
+            "'{}': {}",
+            words.join(" "),
+            first.trim_start_matches("error: ")
+        ))
+    })?;
+    session_command(parsed.command, name, context)
+}
+
+/// Refuse commands that manage the config rather than talk to the TV,
+/// which make no sense in the middle of a `context`.
+fn session_command(command: Commands, name: &str, context: &str) -> Result<Commands> {
+    match command {
+        Commands::Scan { .. }
+        | Commands::Auth { .. }
+        | Commands::Setup
+        | Commands::SetDefault { .. }
+        | Commands::Remove { .. }
+        | Commands::Devices { .. }
+        | Commands::Config { .. }
+        | Commands::Batch { .. }
+        | Commands::Shell
+        | Commands::Exec { .. }
+        | Commands::ServeWeb { .. } => Err(LgtvError::CommandError(format!(
+            "'{}' can't be used in {}",
+            name, context
+        ))),
+        command => Ok(command),
+    }
+}
+
+/// Completions for `lgtv shell`: command names, then the flags of the
+/// command, and button names for `send-button`.
+fn complete(before: &str) -> Vec<String> {
+    let words: Vec<&str> = before.split_whitespace().collect();
+    let typed = if before.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        words.last().copied().unwrap_or_default()
+    };
+    let complete_words = if typed.is_empty() {
+        &words[..]
+    } else {
+        &words[..words.len() - 1]
+    };
+
+    // Follow the words typed so far down to the (group) command they name
+    let mut command = CommandLine::command();
+    let mut depth = 0;
+    for word in complete_words {
+        let Some(sub) = command.find_subcommand(word) else {
+            break;
+        };
+        command = sub.clone();
+        depth += 1;
+    }
+    if depth == 0 && !complete_words.is_empty() {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<String> = if depth == complete_words.len() && command.has_subcommands()
+    {
+        let mut names: Vec<String> = command
+            .get_subcommands()
+            .map(|c| c.get_name().to_string())
+            .collect();
+        if depth == 0 {
+            names.push("exit".to_string());
+        }
+        names
+    } else {
+        let mut candidates: Vec<String> = command
+            .get_arguments()
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{}", long))
+            .collect();
+        if command.get_name() == "send-button" {
+            candidates.extend(LgtvCursor::possible_buttons().iter().map(|b| b.to_string()));
+        }
+        candidates
+    };
+    candidates.retain(|c| c.starts_with(typed));
+    candidates.sort();
+    candidates
+}
+
+/// Read commands line by line and run them over `session` until the user
+/// types `exit` or presses Ctrl-D. Failed commands are reported without
+/// ending the shell.
+async fn run_shell(session: &mut Session<'_>, history_path: &Path) -> Result<()> {
+    // Connect up front, so a TV that can't be reached is reported at once
+    session.remote().await?;
+
+    let mut editor = LineEditor::new(complete);
+    editor.load_history(history_path);
+    let prompt = format!("{}> ", session.tv_name);
+    while let Some(line) = tokio::task::block_in_place(|| editor.read_line(&prompt))? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history(line);
+        if line == "exit" || line == "quit" {
+            break;
+        }
+
+        let words = match split_words(line) {
+            Ok(words) => words,
+            Err(e) => {
+                report_error(&e);
+                continue;
+            }
+        };
+        let command = match CommandLine::try_parse_from(&words) {
+            Ok(parsed) => parsed.command,
+            Err(e) => {
+                // Help and usage errors, as clap formats them
+                let _ = e.print();
+                continue;
+            }
+        };
+        let result = match session_command(command, &words[0], "the shell") {
+            Ok(command) => run_tv_command(session, &command).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            report_error(&e);
+        }
+    }
+
+    if let Err(e) = editor.save_history() {
+        log::warn!("Could not save the shell history: {}", e);
+    }
+    Ok(())
+}
+
+/// `uri` with the `ssap://` scheme added if it was left out.
+fn ssap_uri(uri: &str) -> String {
+    if uri.contains("://") {
+        uri.to_string()
+    } else {
+        format!("ssap://{}", uri)
+    }
+}
+
+/// The payload of a message from the TV, or the error it reported.
+fn tv_payload(message: Value) -> Result<Value> {
+    if let Some(error) = LgtvError::from_response(&message) {
+        return Err(error);
+    }
+    Ok(message.get("payload").cloned().unwrap_or(Value::Null))
+}
+
+/// The line answering an `exec` request.
+fn exec_reply(id: &Value, result: Result<Value>) -> Value {
+    match result {
+        Ok(response) => json!({"id": id, "ok": true, "response": response}),
+        Err(e) => json!({"id": id, "ok": false, "error": e.to_string()}),
+    }
+}
+
+/// Read JSON requests line by line from `input` and run them over
+/// `session`, answering each with one line of JSON on stdout.
+async fn run_exec<R>(session: &mut Session<'_>, input: R) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+{
+    // Connect up front, so a TV that can't be reached is reported at once
+    session.remote().await?;
+
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let id = request.get("id").cloned().unwrap_or(Value::Null);
+                let result = exec_request(session, &request, &id).await;
+                exec_reply(&id, result)
+            }
+            Err(e) => exec_reply(
+                &Value::Null,
+                Err(LgtvError::CommandError(format!("Invalid JSON: {}", e))),
+            ),
+        };
+        println!("{}", reply);
+    }
+    Ok(())
+}
+
+/// Run one `exec` request: `{"uri": ..., "payload": ..., "type": ...}` is
+/// sent to the TV as is, and `{"command": "set-volume 12"}` (or a list of
+/// words) runs like it would on the command line. Updates to a
+/// subscription keep arriving as further replies with the same `id`.
+/// The words of an `exec` request's `"command"`, given as one line or as
+/// an array of words.
+fn command_words(request: &Value) -> Result<Vec<String>> {
+    match request.get("command") {
+        Some(Value::String(line)) => split_words(line),
+        Some(Value::Array(words)) => Ok(words
+            .iter()
+            .map(|word| match word {
+                Value::String(word) => word.clone(),
+                other => other.to_string(),
+            })
+            .collect()),
+        _ => Err(LgtvError::CommandError(
+            "Expected a \"uri\" or a \"command\"".to_string(),
+        )),
+    }
+}
+
+async fn exec_request(session: &mut Session<'_>, request: &Value, id: &Value) -> Result<Value> {
+    if let Some(uri) = request.get("uri").and_then(Value::as_str) {
+        let kind = request
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("request");
+        if kind != "request" && kind != "subscribe" {
+            return Err(LgtvError::CommandError(format!(
+                "Unknown type '{}' (expected request or subscribe)",
+                kind
+            )));
+        }
+        let remote = session.remote().await?;
+        let mut rx = remote
+            .send_command(kind, &ssap_uri(uri), request.get("payload").cloned(), None)
+            .await?;
+        let first = rx
+            .recv()
+            .await
+            .ok_or_else(|| LgtvError::CommandError("No response received".to_string()))?;
+        if kind == "subscribe" {
+            let id = id.clone();
+            tokio::spawn(async move {
+                while let Some(update) = rx.recv().await {
+                    println!("{}", exec_reply(&id, tv_payload(update)));
+                }
+            });
+        }
+        return tv_payload(first);
+    }
+
+    let words = command_words(request)?;
+    let command = parse_command(&words, "exec")?;
+    // These print as they go or never finish
+    let streaming = match &command {
+        Commands::SendButton { buttons } => buttons.len() == 1 && buttons[0] == "-",
+        Commands::Raw { .. } | Commands::Verify => true,
+        #[cfg(target_os = "linux")]
+        Commands::Mouse { .. } => true,
+        #[cfg(all(feature = "gamepad", target_os = "linux"))]
+        Commands::Gamepad { .. } => true,
+        _ => false,
+    };
+    if streaming {
+        return Err(LgtvError::CommandError(format!(
+            "'{}' can't be used in exec",
+            words[0]
+        )));
+    }
+
+    session.captured = Some(Vec::new());
+    let result = run_tv_command(session, &command).await;
+    let mut captured = session.captured.take().unwrap_or_default();
+    result?;
+    Ok(match captured.len() {
+        0 => Value::Null,
+        1 => captured.remove(0),
+        _ => Value::Array(captured),
+    })
+}
+
+/// The exit code for a command that failed with `e`.
+fn exit_code(e: &LgtvError) -> i32 {
+    match e {
+        LgtvError::ConfigError(_) | LgtvError::TvNotFound(_) => EXIT_CONFIG,
+        LgtvError::WebSocketError(_)
+        | LgtvError::ConnectionError(_)
+        | LgtvError::Timeout(_)
+        | LgtvError::NotConnected(_)
+        | LgtvError::QueueFull(_)
+        | LgtvError::CertificateMismatch { .. } => EXIT_CONNECTION,
+        LgtvError::AuthError(_)
+        | LgtvError::KeyRejected
+        | LgtvError::ConfirmationRequired
+        | LgtvError::PairingDenied(_)
+        | LgtvError::PairingTimeout(_) => EXIT_AUTH,
+        LgtvError::TvError { .. } => EXIT_COMMAND,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Print an error to stderr, with a hint on what to do about it where
+/// there is one.
+fn report_error(e: &LgtvError) {
+    eprintln!("Error: {}", e);
+    if let LgtvError::KeyRejected = e {
+        eprintln!("The TV no longer accepts the stored client key. Run 'lgtv auth <host> <name>' to pair again.");
+    }
+    match e.tv_error_kind() {
+        Some(TvErrorKind::InsufficientPermissions) => eprintln!(
+            "The client key wasn't granted the permission this needs. Run 'lgtv auth <host> <name>' to pair again with it."
+        ),
+        Some(TvErrorKind::UnknownMethod) => {
+            eprintln!("This TV's firmware doesn't offer that command.")
+        }
+        _ => {}
+    }
+    if let LgtvError::MacAddressRequired = e {
+        eprintln!("Run 'lgtv scan' and then 'lgtv auth' to get the MAC address.");
+    }
+    if let LgtvError::UnknownButton { .. } = e {
+        eprintln!(
+            "Possible options: {}",
+            LgtvCursor::possible_buttons().join(", ")
+        );
+    }
+}
+
+fn print_status(status: &TvStatus) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    let unknown = || "unknown".to_string();
+    println!("Power:   {}", status.power.clone().unwrap_or_else(unknown));
+    let volume = status.volume.map(|v| v.to_string()).unwrap_or_else(unknown);
+    if status.muted == Some(true) {
+        println!("Volume:  {} (muted)", volume);
+    } else {
+        println!("Volume:  {}", volume);
+    }
+    println!("Input:   {}", status.input.clone().unwrap_or_else(unknown));
+    println!("App:     {}", status.app.clone().unwrap_or_else(unknown));
+    if let Some(number) = &status.channel_number {
+        match &status.channel_name {
+            Some(name) => println!("Channel: {} {}", number, name),
+            None => println!("Channel: {}", number),
+        }
+    }
+}
+
+fn print_response(value: &Value) {
+    if let Ok(s) = serde_json::to_string_pretty(value) {
+        say!("{}", s);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(e) = run(&cli).await {
+        report_error(&e);
+        exit(exit_code(&e));
+    }
+}
+
+/// How long `on --wait` waits for the TV, and how often it wakes it again.
+const ON_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+const ON_RESEND_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Send the Wake-on-LAN packet, and with `wait`, keep at it until the TV's
+/// control port answers.
+async fn power_on(session: &Session<'_>, wait: bool) -> Result<()> {
+    let remote = session.unconnected_remote()?;
+    remote.on().await?;
+    if !wait {
+        return Ok(());
+    }
+    let Some((host, port)) = control_address(session.entry) else {
+        return Err(LgtvError::ConfigError(format!(
+            "'{}' has neither an ip nor a hostname to wait for",
+            session.tv_name
+        )));
+    };
+
+    let _spinner = progress::Spinner::start("Waiting for the TV to come on");
+    let started = std::time::Instant::now();
+    let mut sent = started;
+    loop {
+        if probe_port(host, port).await.is_ok() {
+            return Ok(());
+        }
+        if started.elapsed() >= ON_WAIT_TIMEOUT {
+            return Err(LgtvError::ConnectionError(format!(
+                "The TV did not come on within {} seconds",
+                ON_WAIT_TIMEOUT.as_secs()
+            )));
+        }
+        if sent.elapsed() >= ON_RESEND_INTERVAL {
+            remote.on().await?;
+            sent = std::time::Instant::now();
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+// ──── Programme guide ────
+
+/// What's on `channels` (the current channel if empty) between now and
+/// `hours` from now: one object per channel, with its `programList`
+/// narrowed down to that window.
+async fn guide(remote: &mut LgtvRemote, channels: &[String], hours: u64) -> Result<Vec<Value>> {
+    let mut ids = Vec::new();
+    for channel in channels {
+        ids.push(Some(remote.find_channel(channel).await?));
+    }
+    if ids.is_empty() {
+        ids.push(None);
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let until = now + hours as i64 * 3600;
+    let mut schedule = Vec::new();
+    for id in ids {
+        let info = remote.channel_program_info(id.as_deref()).await?;
+        let programs: Vec<Value> = info
+            .get("programList")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|program| {
+                // Programmes without times are kept, rather than guessed at
+                let start = program.get("startTime").and_then(tv_time);
+                let end = program.get("endTime").and_then(tv_time);
+                start.is_none_or(|start| start < until) && end.is_none_or(|end| end > now)
+            })
+            .cloned()
+            .collect();
+        schedule.push(json!({
+            "channel": info.get("channel").cloned().unwrap_or(json!({"channelId": id})),
+            "programList": programs,
+        }));
+    }
+    Ok(schedule)
+}
+
+/// Seconds since the epoch for one of the TV's `"2024,01,31,20,00,00"`
+/// timestamps, taking it as UTC.
+fn tv_time(value: &Value) -> Option<i64> {
+    let fields: Vec<i64> = value
+        .as_str()?
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|field| !field.is_empty())
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    let [year, month, day, hour, minute, second] = fields[..] else {
+        return None;
+    };
+
+    // Days since the epoch for a civil date (Howard Hinnant's algorithm)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// `HH:MM` of one of the TV's local timestamps.
+fn tv_clock(value: Option<&Value>) -> String {
+    let fields: Vec<&str> = value
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|field| !field.is_empty())
+        .collect();
+    match fields[..] {
+        [_, _, _, hour, minute, ..] => format!("{:0>2}:{:0>2}", hour, minute),
+        _ => "??:??".to_string(),
+    }
+}
+
+fn print_guide(schedule: &[Value]) {
+    for (i, entry) in schedule.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let channel = &entry["channel"];
+        let text = |key: &str| channel.get(key).and_then(Value::as_str).unwrap_or_default();
+        println!("{} {}", text("channelNumber"), text("channelName"));
+        let programs = entry["programList"].as_array().map(Vec::as_slice);
+        match programs.unwrap_or_default() {
+            [] => println!("  (no programme information)"),
+            programs => {
+                for program in programs {
+                    println!(
+                        "  {}-{}  {}",
+                        tv_clock(program.get("localStartTime")),
+                        tv_clock(program.get("localEndTime")),
+                        program
+                            .get("programName")
+                            .and_then(Value::as_str)
+                            .unwrap_or("(untitled)")
+                    );
+                }
+            }
+        }
+    }
+}
+
+// ──── Delayed power off ────
+
+/// Delays the TV's sleep timer offers, in minutes.
+const SLEEP_TIMER_MINUTES: [u64; 8] = [10, 20, 30, 60, 90, 120, 180, 240];
+
+/// A delay such as "90s", "30m", "1h30m" or "2h"; a bare number is in
+/// minutes.
+fn parse_delay(text: &str) -> std::result::Result<std::time::Duration, String> {
+    let invalid = || format!("'{}' is not a delay like 30m, 1h30m or 90s", text);
+    if let Ok(minutes) = text.parse::<u64>() {
+        return Ok(std::time::Duration::from_secs(minutes * 60));
+    }
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        seconds += value * unit;
+        number.clear();
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+/// `delay` as "1h30m", "45s" and so on.
+fn format_delay(delay: std::time::Duration) -> String {
+    let secs = delay.as_secs();
+    let mut text = String::new();
+    for (value, unit) in [(secs / 3600, "h"), (secs / 60 % 60, "m"), (secs % 60, "s")] {
+        if value > 0 {
+            text.push_str(&format!("{}{}", value, unit));
+        }
+    }
+    text
+}
+
+/// Program the TV's own sleep timer, so the TV turns off even if this
+/// machine goes to sleep first. Returns false when the timer doesn't offer
+/// `delay` or the TV won't set it, leaving the waiting to the caller.
+async fn set_sleep_timer(session: &mut Session<'_>, delay: std::time::Duration) -> Result<bool> {
+    let minutes = delay.as_secs() / 60;
+    if !delay.as_secs().is_multiple_of(60) || !SLEEP_TIMER_MINUTES.contains(&minutes) {
+        note!(
+            "The TV's sleep timer only offers {} minutes; waiting here instead",
+            SLEEP_TIMER_MINUTES.map(|m| m.to_string()).join(", ")
+        );
+        return Ok(false);
+    }
+    let remote = session.remote().await?;
+    match remote.set_sleep_timer(minutes as u32).await {
+        Ok(_) => Ok(true),
+        Err(e @ LgtvError::TvError { .. }) => {
+            log::debug!("Sleep timer refused: {}", e);
+            note!("The TV wouldn't set its sleep timer; waiting here instead");
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn wait_locally(delay: std::time::Duration, what: &str) {
+    note!("{} in {}", what, format_delay(delay));
+    let _spinner = progress::Spinner::start("Waiting");
+    tokio::time::sleep(delay).await;
+}
+
+/// Tell the user what to do once the TV shows its pairing prompt.
+fn show_pairing_progress(event: PairingEvent) {
+    if event == PairingEvent::PromptDisplayed {
+        say!("Please accept the pairing request on your LG TV");
+        progress::set_message("Waiting for the pairing to be accepted on the TV");
+    }
+}
 
-                        // ── Notifications ─────────────────────
-                        Commands::Notification { message } => {
-                            remote.connect().await?;
-                            remote.notification(message).await?;
+/// Ask which TV to use when there are several and nothing says which,
+/// if someone is at the terminal to answer.
+fn pick_tv(config: &Config) -> Result<Option<String>> {
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    if config.tvs.len() < 2 || !interactive {
+        return Ok(None);
+    }
+    #[cfg(unix)]
+    {
+        let names: Vec<&String> = config.tvs.keys().collect();
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        let labels: Vec<String> = config
+            .tvs
+            .iter()
+            .map(|(name, entry)| {
+                let address = control_address(entry).map_or("-", |(host, _)| host);
+                let model = entry.model.as_deref().unwrap_or("");
+                format!("{:width$}  {}  {}", name, address, model)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        let Some(choice) = line_editor::pick("Which TV? ", &labels)? else {
+            return Ok(None);
+        };
+        let name = names[choice].clone();
+        note!("Make it the default with: lgtv set-default {}", name);
+        Ok(Some(name))
+    }
+    #[cfg(not(unix))]
+    Ok(None)
+}
+
+/// The TV to talk to and its settings, from the config file and the
+/// environment.
+fn configured_tv(cli: &Cli, config_path: &Path) -> Result<(String, TvConfigEntry)> {
+    let config = read_config_for_tv(config_path)?;
+    let tv_name = match config
+        .select(cli.name.as_deref())
+        .map_or_else(|| pick_tv(&config), |name| Ok(Some(name)))?
+    {
+        Some(name) => name,
+        None => {
+            return Err(LgtvError::ConfigError(
+                "A TV name is required. Set one with -n/--name or the set-default command."
+                    .to_string(),
+            ))
+        }
+    };
+    let mut entry = config.entry(&tv_name)?;
+    entry.apply_env()?;
+    Ok((tv_name, entry))
+}
+
+async fn run(cli: &Cli) -> Result<()> {
+    // Configure logging
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    let level = match (cli.debug, cli.quiet) {
+        (true, _) => "debug",
+        (false, true) => "error",
+        (false, false) => "info",
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level)).init();
+    progress::enable(
+        !cli.quiet && std::io::stdout().is_terminal() && std::io::stderr().is_terminal(),
+    );
+    match cli.trace_ws.as_deref() {
+        Some(path) if path == Path::new("-") => lgtv::trace::to_writer(std::io::stderr()),
+        Some(path) => lgtv::trace::to_file(path)?,
+        None => {}
+    }
+
+    match &cli.command {
+        Commands::Scan {
+            timeout,
+            attempts,
+            interface,
+            cached,
+            watch,
+            deep,
+        } => {
+            let mut options = ScanOptions::new()
+                .timeout(std::time::Duration::from_secs(*timeout))
+                .attempts(*attempts);
+            if let Some(interface) = interface {
+                let address = match interface.parse::<IpAddr>() {
+                    Ok(ip) => ip,
+                    Err(_) => IpAddr::V4(interface_address(interface)?),
+                };
+                options = options.bind_address(address);
+            }
+            if *watch {
+                let mut events = Box::pin(lgtv::scan::watch(&options).await?);
+                while let Some(event) = events.next().await {
+                    say!("{}", serde_json::to_string(&event)?);
+                }
+                exit(0);
+            }
+
+            let cache_path = scan_cache_path()?;
+            let mut results = Vec::new();
+            if *cached {
+                if let Some(devices) = load_cache(&cache_path, CACHE_TTL) {
+                    results = devices;
+                }
+            }
+
+            let scanned = results.is_empty();
+            let spinner = progress::Spinner::start("Scanning for LG TVs");
+            if scanned {
+                let mut found = Box::pin(scan_stream_with_options(&options).await?);
+                while let Some(device) = found.next().await {
+                    note!(
+                        "Found {} at {}",
+                        device.tv_name.as_deref().unwrap_or("LG TV"),
+                        device.address
+                    );
+                    results.push(device);
+                }
+            }
+
+            if results.is_empty() && *deep {
+                note!("No TVs answered, sweeping the local subnet...");
+                progress::set_message("Sweeping the local subnet");
+                for device in sweep_subnet().await? {
+                    note!("Found TV at {}", device.address);
+                    results.push(device);
+                }
+            }
+            drop(spinner);
+
+            if !results.is_empty() {
+                if scanned {
+                    if let Err(e) = save_cache(&cache_path, &results) {
+                        log::debug!("Failed to cache scan results: {}", e);
+                    }
+                }
+
+                // Point out which TVs are already configured, and which of
+                // those have moved to a different address
+                let config_path = config_path(cli)?;
+                let config = read_config_or_default(&config_path)?;
+                let mut moved = Vec::new();
+                let mut list = Vec::new();
+                for device in &results {
+                    let mut item = serde_json::to_value(device)?;
+                    if let Some((name, entry)) = known_tv(&config, device) {
+                        item["known_as"] = json!(name);
+                        if entry.ip.as_deref() != Some(device.address.as_str()) {
+                            item["configured_ip"] = json!(entry.ip);
+                            moved.push((name.to_string(), device.clone()));
                         }
-                        Commands::NotificationWithIcon { message, icon_url } => {
-                            remote.connect().await?;
-                            remote.notification_with_icon(message, icon_url).await?;
+                    }
+                    list.push(item);
+                }
+
+                say!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "result": "ok",
+                        "count": results.len(),
+                        "list": list
+                    }))?
+                );
+
+                if !moved.is_empty() && std::io::stdin().is_terminal() {
+                    let mut input = BufReader::new(tokio::io::stdin());
+                    let mut updates = Vec::new();
+                    for (name, device) in moved {
+                        let question = format!(
+                            "'{}' is now at {}. Update the config? [y/N]: ",
+                            name, device.address
+                        );
+                        eprint!("{}", question);
+                        std::io::stderr().flush()?;
+                        let mut answer = String::new();
+                        input.read_line(&mut answer).await?;
+                        if matches!(answer.trim(), "y" | "Y" | "yes") {
+                            updates.push((name, device));
                         }
-                        Commands::CreateAlert { message, buttons } => {
-                            let btn_value: Value = serde_json::from_str(buttons).map_err(|e| {
-                                lgtv::error::LgtvError::CommandError(format!(
-                                    "Invalid JSON for buttons: {}",
-                                    e
-                                ))
-                            })?;
-                            remote.connect().await?;
-                            let resp = remote.create_alert(message, btn_value).await?;
-                            print_response(&resp);
+                    }
+                    if !updates.is_empty() {
+                        update_config(&config_path, |config| {
+                            for (name, device) in updates {
+                                if let Some(entry) = config.get_mut(&name) {
+                                    entry.ip = Some(device.address.clone());
+                                    if device.uuid.is_some() {
+                                        entry.uuid = device.uuid;
+                                    }
+                                }
+                            }
+                            Ok(())
+                        })?;
+                        note!("Wrote config file: {}", config_path.display());
+                    }
+                }
+                exit(0);
+            } else {
+                say!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "result": "failed",
+                        "count": 0
+                    }))?
+                );
+                exit(EXIT_CONNECTION);
+            }
+        }
+
+        Commands::Auth {
+            host,
+            name,
+            permissions,
+            pin,
+            timeout,
+            non_interactive,
+            key,
+            keyring,
+        } => {
+            let config_path = config_path(cli)?;
+            let config = read_config_or_default(&config_path)?;
+
+            let mut auth = LgtvAuth::new(name, host, cli.ssl)?;
+            if let Some(spec) = permissions {
+                auth.set_permissions(PermissionSet::parse(spec)?);
+            }
+            auth.set_timeout(std::time::Duration::from_secs(*timeout));
+            // A key that can't be read is no reason not to pair again
+            let stored_key = config.get(name).and_then(|e| e.client_key().ok().flatten());
+            if let Some(key) = key.clone().or(stored_key) {
+                auth.set_client_key(&key);
+            }
+
+            // The PIN prompt reads from the terminal, where a spinner would
+            // get in the way
+            let mut spinner = None;
+            if !*non_interactive && !*pin {
+                auth.on_progress(show_pairing_progress);
+                spinner = Some(progress::Spinner::start(&format!("Connecting to {}", host)));
+            }
+
+            let paired = if *non_interactive {
+                auth.connect_non_interactive().await
+            } else if *pin {
+                auth.connect_with_pin(|| async {
+                    print!("Enter the PIN shown on the TV: ");
+                    std::io::stdout().flush()?;
+                    let mut line = String::new();
+                    BufReader::new(tokio::io::stdin())
+                        .read_line(&mut line)
+                        .await?;
+                    Ok(line)
+                })
+                .await
+            } else {
+                auth.connect().await
+            };
+            drop(spinner);
+            match paired {
+                Ok(()) => {}
+                Err(e) if *non_interactive => {
+                    let code = match e {
+                        LgtvError::ConfirmationRequired => "confirmation_required",
+                        LgtvError::PairingDenied(_) => "denied",
+                        LgtvError::PairingTimeout(_) => "timeout",
+                        _ => "error",
+                    };
+                    println!(
+                        "{}",
+                        json!({"result": "failed", "error": code, "message": e.to_string()})
+                    );
+                    exit(exit_code(&e));
+                }
+                Err(e @ (LgtvError::PairingDenied(_) | LgtvError::PairingTimeout(_))) => {
+                    eprintln!("Error: {}", e);
+                    exit(EXIT_AUTH);
+                }
+                Err(e) => return Err(e),
+            }
+
+            // Merged into the config as it is now, not as it was before
+            // pairing, in case another run changed it meanwhile
+            update_config(&config_path, |config| {
+                let entry = config.tvs.entry(name.clone()).or_default();
+                auth.merge_into(entry);
+                if *keyring || entry.key_ref.is_some() {
+                    entry.move_key_to_keyring(name)?;
+                }
+                Ok(())
+            })?;
+
+            if !*non_interactive {
+                say!("Wrote config file: {}", config_path.display());
+            }
+            exit(0);
+        }
+
+        Commands::Setup => {
+            let mut input = BufReader::new(tokio::io::stdin());
+
+            say!("Scanning for LG TVs...");
+            let devices = scan_with_options(&ScanOptions::new()).await?;
+            if devices.is_empty() {
+                return Err(LgtvError::ConnectionError(
+                    "No TVs found. Make sure the TV is on and on the same network, or try 'lgtv scan --deep'."
+                        .to_string(),
+                ));
+            }
+
+            for (i, device) in devices.iter().enumerate() {
+                let label = device
+                    .friendly_name
+                    .as_deref()
+                    .or(device.tv_name.as_deref())
+                    .unwrap_or("LG TV");
+                match &device.model_name {
+                    Some(model) => {
+                        eprintln!("  {}) {} ({}) at {}", i + 1, label, model, device.address)
+                    }
+                    None => eprintln!("  {}) {} at {}", i + 1, label, device.address),
+                }
+            }
+
+            let device = loop {
+                let answer = if devices.len() == 1 {
+                    "1".to_string()
+                } else {
+                    ask(&mut input, &format!("Choose a TV [1-{}]: ", devices.len())).await?
+                };
+                match answer.parse::<usize>() {
+                    Ok(n) if (1..=devices.len()).contains(&n) => break &devices[n - 1],
+                    _ => eprintln!("Please enter a number between 1 and {}", devices.len()),
+                }
+            };
+
+            let suggested = config_name(
+                device
+                    .friendly_name
+                    .as_deref()
+                    .or(device.tv_name.as_deref())
+                    .unwrap_or("tv"),
+            );
+            let name = ask(&mut input, &format!("Name for this TV [{}]: ", suggested)).await?;
+            let name = if name.is_empty() { suggested } else { name };
+
+            let mut auth = LgtvAuth::new(&name, &device.address, cli.ssl)?;
+            if let Some(mac) = &device.mac {
+                auth.set_mac_address(mac);
+            }
+            auth.on_progress(show_pairing_progress);
+            let spinner = progress::Spinner::start(&format!("Connecting to {}", device.address));
+            let paired = auth.connect().await;
+            drop(spinner);
+            match paired {
+                Ok(()) => {}
+                Err(e @ (LgtvError::PairingDenied(_) | LgtvError::PairingTimeout(_))) => {
+                    eprintln!("Error: {}", e);
+                    exit(EXIT_AUTH);
+                }
+                Err(e) => return Err(e),
+            }
+
+            let answer = ask(&mut input, "Make this the default TV? [Y/n]: ").await?;
+            let make_default =
+                !answer.eq_ignore_ascii_case("n") && !answer.eq_ignore_ascii_case("no");
+
+            let config_path = config_path(cli)?;
+            update_config(&config_path, |config| {
+                let entry = config.tvs.entry(name.clone()).or_default();
+                auth.merge_into(entry);
+                if entry.key_ref.is_some() {
+                    entry.move_key_to_keyring(&name)?;
+                }
+                if device.uuid.is_some() {
+                    entry.uuid = device.uuid.clone();
+                }
+                if make_default {
+                    config.default = Some(name.clone());
+                }
+                Ok(())
+            })?;
+
+            say!("Saved '{}' to config file: {}", name, config_path.display());
+            exit(0);
+        }
+
+        Commands::SetDefault { name } => {
+            let config_path = config_path(cli)?;
+            let _lock = lock_config(&config_path)?;
+            let mut config = read_existing_config(&config_path)?;
+
+            if config.get(name).is_none() {
+                return Err(LgtvError::TvNotFound(name.clone()));
+            }
+
+            config.default = Some(name.clone());
+            write_config(&config_path, &config)?;
+
+            say!("Wrote default to config file: {}", config_path.display());
+            exit(0);
+        }
+
+        Commands::Remove { name } => {
+            let config_path = config_path(cli)?;
+            let _lock = lock_config(&config_path)?;
+            let mut config = read_existing_config(&config_path)?;
+
+            if let Some(entry) = config.get(name) {
+                entry.delete_keyring_key()?;
+            }
+            if !remove_tv(&mut config, name) {
+                return Err(LgtvError::TvNotFound(name.clone()));
+            }
+            write_config(&config_path, &config)?;
+
+            say!(
+                "Removed '{}' from config file: {}",
+                name,
+                config_path.display()
+            );
+            exit(0);
+        }
+
+        Commands::Devices { view } => {
+            let config = read_config_or_default(&config_path(cli)?)?;
+            if config.tvs.is_empty() {
+                say!("No TVs configured yet; pair one with 'lgtv setup'");
+                exit(0);
+            }
+            let reports = devices(&config).await;
+            let table = devices_table(&reports, view)?;
+            if view.json {
+                let reports: Vec<Value> = table
+                    .indices()
+                    .into_iter()
+                    .map(|i| reports[i].to_json())
+                    .collect();
+                print_response(&json!(reports));
+            } else if !QUIET.load(Ordering::Relaxed) {
+                table.print();
+            }
+            exit(0);
+        }
+
+        Commands::Config { command } => match command {
+            ConfigCommands::Path => {
+                let config_path = config_path(cli)?;
+                if config_path.exists() {
+                    say!("{}", config_path.display());
+                } else {
+                    say!("{} (does not exist yet)", config_path.display());
+                }
+                exit(0);
+            }
+            ConfigCommands::Doctor => {
+                let problems = doctor(&config_path(cli)?).await;
+                if problems == 0 {
+                    say!("No problems found");
+                    exit(0);
+                }
+                return Err(LgtvError::ConfigError(format!(
+                    "{} problem(s) found",
+                    problems
+                )));
+            }
+            ConfigCommands::Show { show_keys } => {
+                let config_path = config_path(cli)?;
+                let config = read_config_or_default(&config_path)?;
+                let mut tvs = serde_json::Map::new();
+                for (name, entry) in &config.tvs {
+                    let mut item = entry.to_value();
+                    if entry.key.is_some() {
+                        item["key"] = if *show_keys {
+                            json!(entry.client_key()?)
+                        } else {
+                            json!("(hidden, use --show-keys)")
+                        };
+                    }
+                    item["default"] = json!(config.default.as_ref() == Some(name));
+                    tvs.insert(name.clone(), item);
+                }
+                print_response(&json!({
+                    "path": config_path,
+                    "exists": config_path.exists(),
+                    "default": config.default,
+                    "tvs": tvs,
+                }));
+                exit(0);
+            }
+            ConfigCommands::Encrypt { keyring } => {
+                let source = if *keyring {
+                    PassphraseSource::Keyring
+                } else {
+                    PassphraseSource::Env
+                };
+                let config_path = config_path(cli)?;
+                update_config(&config_path, |config| {
+                    // Keys encrypted under another passphrase are re-encrypted
+                    for entry in config.tvs.values_mut() {
+                        if entry.key.is_some() {
+                            entry.key = entry.client_key()?;
                         }
-                        Commands::CloseAlert { alert_id } => {
-                            remote.connect().await?;
-                            remote.close_alert(alert_id).await?;
+                    }
+                    config.encrypt_keys = Some(source.name().to_string());
+                    Ok(())
+                })?;
+                say!("Encrypted client keys in {}", config_path.display());
+                exit(0);
+            }
+            ConfigCommands::Decrypt => {
+                let config_path = config_path(cli)?;
+                update_config(&config_path, |config| {
+                    for entry in config.tvs.values_mut() {
+                        if entry.key.is_some() {
+                            entry.key = entry.client_key()?;
                         }
+                    }
+                    config.encrypt_keys = None;
+                    Ok(())
+                })?;
+                say!("Decrypted client keys in {}", config_path.display());
+                exit(0);
+            }
+            ConfigCommands::Export => {
+                let config_path = config_path(cli)?;
+                let mut config = read_config(&config_path)?;
+                // Keys in the keyring or encrypted with a local passphrase
+                // don't travel with the file
+                for entry in config.tvs.values_mut() {
+                    entry.key = entry.client_key()?;
+                    entry.key_ref = None;
+                }
+                config.encrypt_keys = None;
+                say!("{}", serde_json::to_string_pretty(&config)?);
+                exit(0);
+            }
+            ConfigCommands::Import { path, overwrite } => {
+                let imported = read_config(path)?;
+                let count = imported.tvs.len();
+                let config_path = config_path(cli)?;
+                update_config(&config_path, |config| config.merge(imported, *overwrite))?;
+                say!(
+                    "Imported {} TV(s) from {} into {}",
+                    count,
+                    path.display(),
+                    config_path.display()
+                );
+                exit(0);
+            }
+            ConfigCommands::ImportPython { path, host, force } => {
+                let source = match path
+                    .clone()
+                    .or_else(|| python_config_paths().into_iter().find(|p| p.exists()))
+                {
+                    Some(source) => source,
+                    None => {
+                        return Err(LgtvError::ConfigError(
+                            "No config from the Python lgtv tool found; pass its path".to_string(),
+                        ))
+                    }
+                };
+                let imported = import::read(&source, host.as_deref(), cli.name.as_deref())?;
+                if imported.tvs.is_empty() {
+                    return Err(LgtvError::ConfigError(format!(
+                        "No TVs to import in {}",
+                        source.display()
+                    )));
+                }
 
-                        // ── 3D Display ────────────────────────
-                        Commands::Input3dOn => {
-                            remote.connect().await?;
-                            remote.input_3d_on().await?;
-                        }
-                        Commands::Input3dOff => {
-                            remote.connect().await?;
-                            remote.input_3d_off().await?;
+                // Importing the file lgtv already reads moves it to the
+                // current location, leaving the Python tool's copy alone
+                let mut config_path = config_path(cli)?;
+                if same_file(&config_path, &source) {
+                    if let Some(path) = user_config_path() {
+                        config_path = path;
+                    }
+                }
+                let _lock = lock_config(&config_path)?;
+                let mut config = read_config_or_default(&config_path)?;
+                let mut count = 0;
+                for (name, entry) in imported.tvs {
+                    if config.tvs.contains_key(&name) && !*force {
+                        say!("Skipped '{}': already configured (use --force)", name);
+                        continue;
+                    }
+                    say!("Imported '{}'", name);
+                    config.tvs.insert(name, entry);
+                    count += 1;
+                }
+                if config.default.is_none() {
+                    config.default = imported
+                        .default
+                        .filter(|name| config.tvs.contains_key(name));
+                }
+                if count > 0 {
+                    write_config(&config_path, &config)?;
+                    say!(
+                        "Imported {} TV(s) from {} into {}",
+                        count,
+                        source.display(),
+                        config_path.display()
+                    );
+                }
+                exit(0);
+            }
+        },
+
+        // Commands that require a TV configuration
+        _ => {
+            let config_path = config_path(cli)?;
+            if cli.all || cli.name.as_deref().is_some_and(|name| name.contains(',')) {
+                return run_group(cli, &config_path).await;
+            }
+            let (tv_name, mut entry) = match &cli.host {
+                // --host skips the config file altogether
+                Some(host) => {
+                    let mut entry = TvConfigEntry::default();
+                    entry.apply_env()?;
+                    entry.set_host(host);
+                    (host.clone(), entry)
+                }
+                None => configured_tv(cli, &config_path)?,
+            };
+
+            // Explicit flags win over the entry and the environment
+            if let Some(key) = &cli.key {
+                entry.key = Some(key.clone());
+                entry.key_ref = None;
+            }
+            if cli.ssl {
+                entry.ssl = true;
+                entry.port = None;
+            }
+
+            let mut session = Session::new(&tv_name, &entry);
+            match &cli.command {
+                Commands::Exec { input } => {
+                    if input.as_os_str() == "-" {
+                        run_exec(&mut session, BufReader::new(tokio::io::stdin())).await?;
+                    } else {
+                        let contents = std::fs::read(input)?;
+                        run_exec(&mut session, contents.as_slice()).await?;
+                    }
+                }
+                Commands::ServeWeb { listen, token } => {
+                    web::serve(&mut session, *listen, token.clone()).await?;
+                }
+                Commands::Shell => {
+                    let history_path = config_path.with_file_name("shell-history");
+                    run_shell(&mut session, &history_path).await?;
+                }
+                Commands::Batch { commands } => {
+                    // Parse everything first, so a typo doesn't leave the
+                    // batch half done
+                    let parsed = commands
+                        .iter()
+                        .map(|line| parse_command(&split_words(line)?, "a batch"))
+                        .collect::<Result<Vec<_>>>()?;
+                    for (line, command) in commands.iter().zip(&parsed) {
+                        log::debug!("Running '{}'", line);
+                        if let Err(e) = run_tv_command(&mut session, command).await {
+                            eprintln!("'{}' failed, skipping the rest of the batch", line);
+                            return Err(e);
                         }
+                    }
+                }
+                command => run_tv_command(&mut session, command).await?,
+            }
+        }
+    }
 
-                        // ── Picture Settings ──────────────────
-                        Commands::GetPictureSettings => {
-                            remote.connect().await?;
-                            let resp = remote.get_picture_settings().await?;
-                            print_response(&resp);
+    Ok(())
+}
+
+/// Run the command on several TVs at once (`--all`, or `--name a,b`),
+/// printing each TV's output under its name once all are done. Exits with
+/// the first failure's code.
+async fn run_group(cli: &Cli, config_path: &Path) -> Result<()> {
+    let one_at_a_time = match &cli.command {
+        Commands::Exec { .. } | Commands::ServeWeb { .. } | Commands::Shell => true,
+        Commands::Batch { .. } | Commands::Verify => true,
+        _ => cli.key.is_some(),
+    };
+    if one_at_a_time {
+        return Err(LgtvError::ConfigError(
+            "That works with one TV at a time; pick it with -n/--name".to_string(),
+        ));
+    }
+
+    let mut config = read_existing_config(config_path)?;
+    let names: Vec<String> = match &cli.name {
+        Some(names) if !cli.all => names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => config.tvs.keys().cloned().collect(),
+    };
+    if names.is_empty() {
+        return Err(LgtvError::ConfigError(
+            "No TVs to run the command on".to_string(),
+        ));
+    }
+    if cli.ssl {
+        for entry in config.tvs.values_mut() {
+            entry.ssl = true;
+            entry.port = None;
+        }
+    }
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let group = LgtvGroup::from_tvs(&config, &names)?;
+
+    // Each TV's session connects if the command needs it, so `on` can
+    // still wake TVs that are off
+    let config = &config;
+    let results = group
+        .fan_out(|remote| async move {
+            let name = remote.name();
+            let entry = config
+                .get(name)
+                .ok_or_else(|| LgtvError::TvNotFound(name.to_string()))?;
+            let mut session = Session::with_remote(name, entry, remote.clone());
+            session.captured = Some(Vec::new());
+            run_tv_command(&mut session, &cli.command).await?;
+            Ok(session.captured.unwrap_or_default())
+        })
+        .await;
+    let mut failure = None;
+    for GroupResult { name, result } in results {
+        match result {
+            Ok(output) if output.is_empty() => say!("{}: done", name),
+            Ok(output) => {
+                say!("{}:", name);
+                for value in &output {
+                    print_response(value);
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: Error: {}", name, e);
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    match failure {
+        Some(e) => exit(exit_code(&e)),
+        None => Ok(()),
+    }
+}
+
+/// Run a command that talks to the TV over `session`.
+async fn run_tv_command(session: &mut Session<'_>, command: &Commands) -> Result<()> {
+    let ungrouped = command.ungrouped();
+    let command = ungrouped.as_ref().unwrap_or(command);
+    match command {
+        Commands::SendButton { buttons } => {
+            let cursor = session.cursor().await?;
+            if buttons.len() == 1 && buttons[0] == "-" {
+                let mut lines = BufReader::new(tokio::io::stdin()).lines();
+                while let Some(line) = lines.next_line().await? {
+                    let names: Vec<&str> = line.split_whitespace().collect();
+                    if names.is_empty() {
+                        continue;
+                    }
+                    match cursor.execute(names).await {
+                        Ok(results) => {
+                            for r in &results {
+                                if let Err(e) = &r.result {
+                                    eprintln!("Error: {}: {}", r.button, e);
+                                }
+                            }
                         }
-                        Commands::SetPictureMode { mode } => {
-                            remote.connect().await?;
-                            remote.set_picture_mode(mode).await?;
+                        Err(e @ LgtvError::UnknownButton { .. }) => eprintln!("{}", e),
+                        Err(e) => return Err(e),
+                    }
+                }
+            } else {
+                let results = cursor
+                    .execute(buttons.iter().map(|s| s.as_str()).collect())
+                    .await?;
+
+                let failures: Vec<String> = results
+                    .iter()
+                    .filter_map(|r| Some(format!("{}: {}", r.button, r.result.as_ref().err()?)))
+                    .collect();
+                if !failures.is_empty() {
+                    return Err(LgtvError::CommandError(failures.join("; ")));
+                }
+            }
+        }
+
+        Commands::ChannelNumber { number } => {
+            let cursor = session.cursor().await?;
+            cursor.enter_number(*number).await?;
+        }
+
+        Commands::Drag { dx, dy } => {
+            let cursor = session.cursor().await?;
+            cursor.drag(*dx, *dy).await?;
+        }
+
+        #[cfg(target_os = "linux")]
+        Commands::Mouse {
+            action: MouseAction::Passthrough { device, speed },
+        } => {
+            let cursor = session.cursor().await?;
+            lgtv::mouse::MousePassthrough::new(device)
+                .speed(*speed)
+                .run(cursor)
+                .await?;
+        }
+
+        #[cfg(all(feature = "gamepad", target_os = "linux"))]
+        Commands::Gamepad { device } => {
+            let cursor = session.cursor().await?;
+            lgtv::gamepad::GamepadBridge::new(device)
+                .run(cursor)
+                .await?;
+        }
+
+        Commands::Serialise => {
+            let entry = session.entry.to_value();
+            session.print(entry);
+        }
+
+        // Both of these work without registering with the TV
+        Commands::On { wait } => match power_on(session, *wait).await {
+            Ok(_) => {
+                if !session.capturing() {
+                    if *wait {
+                        say!("The TV is on");
+                    } else {
+                        say!("Power on command sent successfully");
+                    }
+                }
+            }
+            Err(e) => return Err(e),
+        },
+        Commands::Off {
+            after: Some(delay),
+            local,
+        } => {
+            if !*local && set_sleep_timer(session, *delay).await? {
+                if !session.capturing() {
+                    say!(
+                        "The TV's sleep timer will turn it off in {}",
+                        format_delay(*delay)
+                    );
+                }
+            } else {
+                wait_locally(*delay, "Turning the TV off").await;
+                session.remote().await?.off().await?;
+            }
+        }
+        Commands::ScreenOff { after: Some(delay) } => {
+            wait_locally(*delay, "Turning the screen off").await;
+            session.remote().await?.screen_off().await?;
+        }
+        Commands::Picture {
+            command: PictureCommands::Keys,
+        } => {
+            for setting in PICTURE_SETTINGS {
+                say!("{:<20} {}", setting.name, setting.describe());
+            }
+        }
+        Commands::Verify => match session.unconnected_remote()?.verify().await? {
+            VerifyStatus::Valid => {
+                say!("Client key for '{}' is valid", session.tv_name)
+            }
+            VerifyStatus::KeyRejected => {
+                eprintln!(
+                    "The TV no longer accepts the stored key for '{}'. Re-run 'lgtv auth' to pair again.",
+                    session.tv_name
+                );
+                exit(EXIT_AUTH);
+            }
+            VerifyStatus::Unreachable(reason) => {
+                eprintln!("Could not reach '{}': {}", session.tv_name, reason);
+                exit(EXIT_CONNECTION);
+            }
+        },
+
+        // All commands that use the remote
+        _ => {
+            let remote = session.remote().await?;
+
+            match command {
+                // ── Power ─────────────────────────────
+                Commands::Off { .. } => {
+                    remote.off().await?;
+                }
+                Commands::ScreenOff { .. } => {
+                    remote.screen_off().await?;
+                }
+                Commands::ScreenOn => {
+                    remote.screen_on().await?;
+                }
+                Commands::GetPowerState => {
+                    let resp = remote.get_power_state().await?;
+                    session.print(resp);
+                }
+                Commands::Status { json } => {
+                    let status = remote.status().await?;
+                    if *json || session.capturing() {
+                        session.print(serde_json::to_value(&status)?);
+                    } else {
+                        print_status(&status);
+                    }
+                }
+
+                // ── Audio ─────────────────────────────
+                Commands::Mute { muted } => {
+                    remote.mute(*muted).await?;
+                }
+                Commands::SetVolume { level } => {
+                    remote.set_volume(*level).await?;
+                }
+                Commands::VolumeUp => {
+                    remote.volume_up().await?;
+                }
+                Commands::VolumeDown => {
+                    remote.volume_down().await?;
+                }
+                Commands::AudioStatus => {
+                    let resp = remote.audio_status().await?;
+                    session.print(resp);
+                }
+                Commands::AudioVolume => {
+                    let resp = remote.audio_volume().await?;
+                    session.print(resp);
+                }
+                Commands::GetSoundOutput => {
+                    let resp = remote.get_sound_output().await?;
+                    session.print(resp);
+                }
+                Commands::SetSoundOutput { output } => {
+                    remote.set_sound_output(output).await?;
+                }
+
+                // ── TV Channels ───────────────────────
+                Commands::GetTvChannel => {
+                    let resp = remote.get_tv_channel().await?;
+                    session.print(resp);
+                }
+                Commands::SetTvChannel { channel_id } => {
+                    let channel_id = remote.find_channel(channel_id).await?;
+                    remote.set_tv_channel(&channel_id).await?;
+                }
+                Commands::ListChannels { view } => {
+                    let resp = remote.list_channels().await?;
+                    let header = ["NUMBER", "NAME", "TYPE", "ID"];
+                    let fields = [
+                        "channelNumber",
+                        "channelName",
+                        "channelTypeName",
+                        "channelId",
+                    ];
+                    print_list(session, resp, "channelList", &header, view, |channel| {
+                        fields.map(|field| text_field(channel, field)).to_vec()
+                    })?;
+                }
+                Commands::Guide {
+                    channels,
+                    hours,
+                    json,
+                } => {
+                    let schedule = guide(remote, channels, *hours).await?;
+                    if *json || session.capturing() {
+                        session.print(json!(schedule));
+                    } else if !QUIET.load(Ordering::Relaxed) {
+                        print_guide(&schedule);
+                    }
+                }
+                Commands::InputChannelUp => {
+                    remote.input_channel_up().await?;
+                }
+                Commands::InputChannelDown => {
+                    remote.input_channel_down().await?;
+                }
+
+                // ── Media Controls ────────────────────
+                Commands::InputMediaPlay => {
+                    remote.input_media_play().await?;
+                }
+                Commands::InputMediaPause => {
+                    remote.input_media_pause().await?;
+                }
+                Commands::InputMediaStop => {
+                    remote.input_media_stop().await?;
+                }
+                Commands::InputMediaRewind => {
+                    remote.input_media_rewind().await?;
+                }
+                Commands::InputMediaFastForward => {
+                    remote.input_media_fast_forward().await?;
+                }
+
+                // ── Input Switching ───────────────────
+                Commands::ListInputs { view } => {
+                    let resp = remote.list_inputs().await?;
+                    let header = ["ID", "LABEL", "CONNECTED", "APP"];
+                    print_list(session, resp, "devices", &header, view, |input| {
+                        let connected = match input.get("connected").and_then(Value::as_bool) {
+                            Some(true) => "yes",
+                            Some(false) => "no",
+                            None => "",
+                        };
+                        vec![
+                            text_field(input, "id"),
+                            text_field(input, "label"),
+                            connected.to_string(),
+                            text_field(input, "appId"),
+                        ]
+                    })?;
+                }
+                Commands::SetInput { input_id } => {
+                    let input_id = remote.find_input(input_id).await?;
+                    remote.set_input(&input_id).await?;
+                }
+                Commands::SetDeviceInfo { id, icon, label } => {
+                    remote.set_device_info(id, icon, label).await?;
+                }
+
+                // ── Applications ──────────────────────
+                Commands::ListApps { view } => {
+                    let resp = remote.list_apps().await?;
+                    let header = ["ID", "TITLE", "VERSION"];
+                    let fields = ["id", "title", "version"];
+                    print_list(session, resp, "apps", &header, view, |app| {
+                        fields.map(|field| text_field(app, field)).to_vec()
+                    })?;
+                }
+                Commands::ListLaunchPoints => {
+                    let resp = remote.list_launch_points().await?;
+                    session.print(resp);
+                }
+                Commands::StartApp { app_id } => {
+                    let app_id = remote.find_app(app_id).await?;
+                    remote.start_app(&app_id).await?;
+                }
+                Commands::CloseApp { app_id } => {
+                    remote.close_app(app_id).await?;
+                }
+                Commands::OpenAppWithPayload { payload } => {
+                    let parsed: Value = serde_json::from_str(payload).map_err(|e| {
+                        lgtv::error::LgtvError::CommandError(format!("Invalid JSON payload: {}", e))
+                    })?;
+                    remote.open_app_with_payload(parsed).await?;
+                }
+                Commands::GetForegroundAppInfo => {
+                    let resp = remote.get_foreground_app_info().await?;
+                    session.print(resp);
+                }
+
+                // ── Browser & YouTube ─────────────────
+                Commands::OpenBrowserAt { url } => {
+                    remote.open_browser_at(url).await?;
+                }
+                Commands::OpenYoutubeId { video_id } => {
+                    remote.open_youtube_id(video_id).await?;
+                }
+                Commands::OpenYoutubeUrl { url } => {
+                    remote.open_youtube_url(url).await?;
+                }
+                Commands::OpenYoutubeLegacyId { video_id } => {
+                    remote.open_youtube_legacy_id(video_id).await?;
+                }
+                Commands::OpenYoutubeLegacyUrl { url } => {
+                    remote.open_youtube_legacy_url(url).await?;
+                }
+
+                // ── Notifications ─────────────────────
+                Commands::Notification {
+                    message,
+                    icon,
+                    app_icon,
+                } => {
+                    let icon = match (icon, app_icon) {
+                        (Some(url), _)
+                            if url.starts_with("http://") || url.starts_with("https://") =>
+                        {
+                            Some(NotificationIcon::Url(url.clone()))
                         }
+                        (Some(path), _) => Some(NotificationIcon::File(PathBuf::from(path))),
+                        (None, Some(app)) => Some(NotificationIcon::App(app.clone())),
+                        (None, None) => None,
+                    };
+                    remote.notification(message, icon.as_ref()).await?;
+                }
+                Commands::NotificationWithIcon { message, icon_url } => {
+                    remote.notification_with_icon(message, icon_url).await?;
+                }
+                Commands::CreateAlert { message, buttons } => {
+                    let btn_value: Value = serde_json::from_str(buttons).map_err(|e| {
+                        lgtv::error::LgtvError::CommandError(format!(
+                            "Invalid JSON for buttons: {}",
+                            e
+                        ))
+                    })?;
+                    let resp = remote.create_alert(message, btn_value).await?;
+                    session.print(resp);
+                }
+                Commands::CloseAlert { alert_id } => {
+                    remote.close_alert(alert_id).await?;
+                }
+
+                // ── 3D Display ────────────────────────
+                Commands::Input3dOn => {
+                    remote.input_3d_on().await?;
+                }
+                Commands::Input3dOff => {
+                    remote.input_3d_off().await?;
+                }
+
+                // ── Picture Settings ──────────────────
+                Commands::Picture { command } => match command {
+                    PictureCommands::Get { keys } if keys.is_empty() => {
+                        let resp = remote.get_picture_settings().await?;
+                        session.print(resp);
+                    }
+                    PictureCommands::Get { keys } => {
+                        let keys: Vec<&str> = keys
+                            .iter()
+                            .map(|key| Setting::find(key).map_or(key.as_str(), |s| s.name))
+                            .collect();
+                        let resp = remote.picture_settings(&keys).await?;
+                        session.print(resp);
+                    }
+                    PictureCommands::Set { settings } => {
+                        let settings = parse_picture_settings(settings)?;
+                        remote.set_picture_settings(settings).await?;
+                    }
+                    PictureCommands::Mode { mode } => {
+                        remote.set_picture_mode(mode).await?;
+                    }
+                    PictureCommands::Keys => unreachable!("handled without connecting"),
+                },
+                Commands::GetPictureSettings => {
+                    let resp = remote.get_picture_settings().await?;
+                    session.print(resp);
+                }
+                Commands::SetPictureMode { mode } => {
+                    remote.set_picture_mode(mode).await?;
+                }
 
-                        // ── System Info ───────────────────────
-                        Commands::SwInfo => {
-                            remote.connect().await?;
-                            let resp = remote.sw_info().await?;
-                            print_response(&resp);
+                // ── System Info ───────────────────────
+                Commands::SwInfo => {
+                    let resp = remote.sw_info().await?;
+                    session.print(resp);
+                }
+                Commands::GetSystemInfo => {
+                    let resp = remote.get_system_info().await?;
+                    session.print(resp);
+                }
+                Commands::ListServices => {
+                    let resp = remote.list_services().await?;
+                    session.print(resp);
+                }
+
+                // ── Misc ──────────────────────────────
+                Commands::SendEnterKey => {
+                    remote.send_enter_key().await?;
+                }
+                Commands::Raw { uri, payload, kind } => {
+                    let payload = match payload {
+                        Some(payload) => {
+                            Some(serde_json::from_str::<Value>(payload).map_err(|e| {
+                                LgtvError::CommandError(format!("Invalid JSON payload: {}", e))
+                            })?)
                         }
-                        Commands::GetSystemInfo => {
-                            remote.connect().await?;
-                            let resp = remote.get_system_info().await?;
-                            print_response(&resp);
+                        None => None,
+                    };
+                    let mut rx = remote
+                        .send_command(kind, &ssap_uri(uri), payload, None)
+                        .await?;
+                    if kind == "subscribe" {
+                        // One compact JSON line per update
+                        while let Some(response) = rx.recv().await {
+                            say!("{}", response);
                         }
-                        Commands::ListServices => {
-                            remote.connect().await?;
-                            let resp = remote.list_services().await?;
-                            print_response(&resp);
+                    } else {
+                        match rx.recv().await {
+                            Some(response) => session.print(response),
+                            None => {
+                                return Err(LgtvError::CommandError(
+                                    "No response received".to_string(),
+                                ))
+                            }
                         }
-
-                        // ── Misc ──────────────────────────────
-                        Commands::SendEnterKey => {
-                            remote.connect().await?;
+                    }
+                }
+                Commands::Type {
+                    text,
+                    replace,
+                    enter,
+                } => {
+                    if remote.ime_available().await? {
+                        remote.insert_text(text, *replace).await?;
+                        if *enter {
                             remote.send_enter_key().await?;
                         }
-
-                        // Already handled above
-                        _ => unreachable!(),
+                    } else {
+                        log::info!("Remote keyboard not available, falling back to number buttons");
+                        let cursor = session.cursor().await?;
+                        cursor.type_text(text).await?;
+                        if *enter {
+                            cursor.enter().await?;
+                        }
                     }
                 }
+
+                // Already handled above
+                _ => unreachable!(),
             }
         }
     }
@@ -0,0 +1,187 @@
+//! Just enough multicast DNS to find TVs on networks that block SSDP.
+//!
+//! Queries are sent from an ephemeral port, which makes responders answer
+//! with unicast (RFC 6762 section 6.7) so nothing has to bind port 5353.
+
+use crate::error::Result;
+use crate::scan::multicast_socket;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use tokio::sync::mpsc;
+use tokio::time::{timeout_at, Instant};
+
+/// Advertised by webOS TVs themselves.
+const LG_SMART_DEVICE: &str = "_lg-smart-device._tcp.local";
+
+/// Advertised by newer models; only counted with LG vendor info in TXT.
+const AIRPLAY: &str = "_airplay._tcp.local";
+
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+
+/// Class IN with the "unicast response" bit set.
+const CLASS_IN_QU: u16 = 0x8001;
+
+/// Browse for TVs until `deadline`, sending the address and instance name
+/// of each one that answers.
+pub(crate) async fn browse(
+    bind_address: IpAddr,
+    deadline: Instant,
+    found: mpsc::Sender<(IpAddr, String)>,
+) -> Result<()> {
+    let socket = multicast_socket(bind_address)?;
+    let multicast_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+    socket
+        .send_to(&query(&[LG_SMART_DEVICE, AIRPLAY]), multicast_addr)
+        .await?;
+
+    let mut buf = [0u8; 9000];
+    loop {
+        let (len, addr) = match timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            Ok(Ok(received)) => received,
+            Ok(Err(e)) => {
+                log::debug!("Error receiving mDNS response: {}", e);
+                continue;
+            }
+            Err(_) => return Ok(()),
+        };
+
+        let Some(records) = parse_records(&buf[..len]) else {
+            log::debug!("Malformed mDNS response from {}", addr);
+            continue;
+        };
+        if let Some(name) = lg_instance(&records) {
+            if found.send((addr.ip(), name)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn query(services: &[&str]) -> Vec<u8> {
+    let mut packet = vec![0, 0, 0, 0];
+    packet.extend_from_slice(&(services.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    for service in services {
+        for label in service.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN_QU.to_be_bytes());
+    }
+    packet
+}
+
+#[derive(Debug)]
+enum Record {
+    Ptr { service: String, instance: String },
+    Txt { name: String, entries: Vec<String> },
+}
+
+/// The instance name of an LG TV among the records of one response.
+fn lg_instance(records: &[Record]) -> Option<String> {
+    let mut txt: HashMap<&str, &[String]> = HashMap::new();
+    for record in records {
+        if let Record::Txt { name, entries } = record {
+            txt.insert(name, entries);
+        }
+    }
+
+    records.iter().find_map(|record| {
+        let Record::Ptr { service, instance } = record else {
+            return None;
+        };
+        let is_lg = if service.eq_ignore_ascii_case(LG_SMART_DEVICE) {
+            true
+        } else if service.eq_ignore_ascii_case(AIRPLAY) {
+            txt.get(instance.as_str()).is_some_and(|entries| {
+                entries.iter().any(|entry| {
+                    let entry = entry.to_ascii_lowercase();
+                    entry.starts_with("manufacturer=lg")
+                })
+            })
+        } else {
+            false
+        };
+        // "[LG] webOS TV OLED55C1._airplay._tcp.local" -> "[LG] webOS TV OLED55C1"
+        is_lg.then(|| {
+            instance
+                .strip_suffix(service.as_str())
+                .unwrap_or(instance)
+                .trim_end_matches('.')
+                .to_string()
+        })
+    })
+}
+
+fn parse_records(packet: &[u8]) -> Option<Vec<Record>> {
+    let count = |offset: usize| -> Option<usize> {
+        Some(u16::from_be_bytes([*packet.get(offset)?, *packet.get(offset + 1)?]) as usize)
+    };
+    let questions = count(4)?;
+    let records = count(6)? + count(8)? + count(10)?;
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(packet, offset)?.1 + 4;
+    }
+
+    let mut parsed = Vec::new();
+    for _ in 0..records {
+        let (name, next) = read_name(packet, offset)?;
+        let kind = count(next)? as u16;
+        let length = count(next + 8)?;
+        let start = next + 10;
+        let rdata = packet.get(start..start + length)?;
+
+        match kind {
+            TYPE_PTR => parsed.push(Record::Ptr {
+                service: name,
+                instance: read_name(packet, start)?.0,
+            }),
+            TYPE_TXT => {
+                let mut entries = Vec::new();
+                let mut i = 0;
+                while i < rdata.len() {
+                    let len = rdata[i] as usize;
+                    let entry = rdata.get(i + 1..i + 1 + len)?;
+                    entries.push(String::from_utf8_lossy(entry).into_owned());
+                    i += 1 + len;
+                }
+                parsed.push(Record::Txt { name, entries });
+            }
+            _ => {}
+        }
+        offset = start + length;
+    }
+    Some(parsed)
+}
+
+/// Read a possibly compressed domain name, returning it and the offset just
+/// past it.
+fn read_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds the number of compression pointers followed
+    for _ in 0..128 {
+        let len = *packet.get(offset)? as usize;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(offset + 1)));
+            }
+            l if l & 0xc0 == 0xc0 => {
+                let pointer = ((l & 0x3f) << 8) | *packet.get(offset + 1)? as usize;
+                end.get_or_insert(offset + 2);
+                offset = pointer;
+            }
+            l => {
+                let label = packet.get(offset + 1..offset + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                offset += 1 + l;
+            }
+        }
+    }
+    None
+}
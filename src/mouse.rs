@@ -0,0 +1,135 @@
+//! Forward a local mouse to the TV pointer, Magic Remote style.
+//!
+//! Reads the kernel's combined PS/2 mouse device (`/dev/input/mice`),
+//! switched into IntelliMouse mode so wheel motion is reported too.
+
+use crate::cursor::LgtvCursor;
+use crate::error::{LgtvError, Result};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration};
+
+/// Sample-rate sequence that enables the 4-byte IntelliMouse protocol.
+const IMPS2_INIT: [u8; 6] = [0xf3, 200, 0xf3, 100, 0xf3, 80];
+
+const BUTTON_LEFT: u8 = 0x01;
+const BUTTON_RIGHT: u8 = 0x02;
+const BUTTON_MIDDLE: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy)]
+struct Packet {
+    buttons: u8,
+    dx: i8,
+    dy: i8,
+    wheel: i8,
+}
+
+pub struct MousePassthrough {
+    device: PathBuf,
+    speed: f32,
+}
+
+impl MousePassthrough {
+    pub fn new(device: &Path) -> Self {
+        Self {
+            device: device.to_path_buf(),
+            speed: 1.0,
+        }
+    }
+
+    /// Multiplier applied to local mouse motion.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Stream mouse input to the cursor until the device goes away.
+    pub async fn run(&self, cursor: &mut LgtvCursor) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.device)
+            .map_err(|e| {
+                LgtvError::ConnectionError(format!(
+                    "Failed to open mouse {} (reading it usually requires root or the input group): {}",
+                    self.device.display(),
+                    e
+                ))
+            })?;
+
+        file.write_all(&IMPS2_INIT)?;
+        // The device acknowledges every byte of the init sequence
+        let mut ack = [0u8; IMPS2_INIT.len()];
+        file.read_exact(&mut ack)?;
+
+        let (tx, mut rx) = mpsc::channel::<Packet>(256);
+        tokio::task::spawn_blocking(move || {
+            let mut buf = [0u8; 4];
+            while file.read_exact(&mut buf).is_ok() {
+                let packet = Packet {
+                    buttons: buf[0] & 0x07,
+                    dx: buf[1] as i8,
+                    dy: buf[2] as i8,
+                    wheel: buf[3] as i8,
+                };
+                if tx.blocking_send(packet).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut buttons = 0u8;
+        let mut pending = (0f32, 0f32);
+        let mut tick = interval(Duration::from_millis(16));
+
+        loop {
+            tokio::select! {
+                packet = rx.recv() => {
+                    let packet = match packet {
+                        Some(p) => p,
+                        None => {
+                            log::info!("Mouse {} closed", self.device.display());
+                            return Ok(());
+                        }
+                    };
+
+                    // PS/2 reports Y increasing upwards
+                    pending.0 += packet.dx as f32 * self.speed;
+                    pending.1 -= packet.dy as f32 * self.speed;
+
+                    let pressed = packet.buttons & !buttons;
+                    buttons = packet.buttons;
+                    if pressed != 0 {
+                        self.flush(cursor, &mut pending).await?;
+                    }
+                    if pressed & BUTTON_LEFT != 0 {
+                        cursor.click().await?;
+                    }
+                    if pressed & BUTTON_RIGHT != 0 {
+                        cursor.back().await?;
+                    }
+                    if pressed & BUTTON_MIDDLE != 0 {
+                        cursor.home().await?;
+                    }
+                    if packet.wheel != 0 {
+                        cursor.scroll(0, packet.wheel as i32 * 20).await?;
+                    }
+                }
+                _ = tick.tick() => self.flush(cursor, &mut pending).await?,
+            }
+        }
+    }
+
+    async fn flush(&self, cursor: &mut LgtvCursor, pending: &mut (f32, f32)) -> Result<()> {
+        let dx = pending.0 as i32;
+        let dy = pending.1 as i32;
+        if dx != 0 || dy != 0 {
+            cursor.move_by(dx, dy).await?;
+            pending.0 -= dx as f32;
+            pending.1 -= dy as f32;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! MAC address lookup through the operating system's ARP/neighbor table.
+
+use std::net::IpAddr;
+use std::process::Command;
+
+/// Find the MAC address of a host on the local network, formatted as
+/// `AA:BB:CC:DD:EE:FF`.
+///
+/// Only works for hosts we have recently talked to, since the lookup reads
+/// the neighbor cache instead of sending anything itself.
+pub fn lookup_mac(ip: &str) -> Option<String> {
+    let ip: IpAddr = ip.parse().ok()?;
+
+    #[cfg(target_os = "linux")]
+    if let Some(mac) = proc_net_arp(&ip) {
+        return Some(mac);
+    }
+
+    arp_command(&ip)
+}
+
+#[cfg(target_os = "linux")]
+fn proc_net_arp(ip: &IpAddr) -> Option<String> {
+    // IP address  HW type  Flags  HW address  Mask  Device
+    let table = std::fs::read_to_string("/proc/net/arp").ok()?;
+    table.lines().skip(1).find_map(|line| {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let complete = cols.get(2).is_some_and(|flags| *flags != "0x0");
+        if complete && cols.first()?.parse::<IpAddr>().ok()? == *ip {
+            normalize_mac(cols.get(3)?)
+        } else {
+            None
+        }
+    })
+}
+
+fn arp_command(ip: &IpAddr) -> Option<String> {
+    let ip = ip.to_string();
+    let args: &[&str] = if cfg!(windows) {
+        &["-a", &ip]
+    } else {
+        &["-n", &ip]
+    };
+    let output = Command::new("arp").args(args).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .filter(|line| line.contains(&ip))
+        .flat_map(|line| line.split_whitespace())
+        .find_map(normalize_mac)
+}
+
+/// Parse `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff` or macOS-style `a:b:c:d:e:f`
+/// into upper-case, colon-separated form. Rejects the all-zero address that
+/// incomplete entries show.
+pub fn normalize_mac(text: &str) -> Option<String> {
+    let parts: Vec<&str> = text.split([':', '-']).collect();
+    if parts.len() != 6 {
+        return None;
+    }
+
+    let mut bytes = [0u8; 6];
+    for (byte, part) in bytes.iter_mut().zip(&parts) {
+        if part.is_empty() || part.len() > 2 {
+            return None;
+        }
+        *byte = u8::from_str_radix(part, 16).ok()?;
+    }
+    if bytes == [0u8; 6] {
+        return None;
+    }
+
+    Some(
+        bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
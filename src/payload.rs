@@ -1,7 +1,153 @@
+use crate::error::{LgtvError, Result};
 use serde_json::{json, Value};
 
-/// Hello data payload used for connecting to the TV
+/// Every permission the pairing manifest can request.
+pub const ALL_PERMISSIONS: &[&str] = &[
+    "LAUNCH",
+    "LAUNCH_WEBAPP",
+    "APP_TO_APP",
+    "CLOSE",
+    "TEST_OPEN",
+    "TEST_PROTECTED",
+    "CONTROL_AUDIO",
+    "CONTROL_DISPLAY",
+    "CONTROL_INPUT_JOYSTICK",
+    "CONTROL_INPUT_MEDIA_RECORDING",
+    "CONTROL_INPUT_MEDIA_PLAYBACK",
+    "CONTROL_INPUT_TV",
+    "CONTROL_POWER",
+    "READ_APP_STATUS",
+    "READ_CURRENT_CHANNEL",
+    "READ_INPUT_DEVICE_LIST",
+    "READ_NETWORK_STATE",
+    "READ_RUNNING_APPS",
+    "READ_TV_CHANNEL_LIST",
+    "WRITE_NOTIFICATION_TOAST",
+    "READ_POWER_STATE",
+    "READ_COUNTRY_INFO",
+    "READ_SETTINGS",
+    "CONTROL_TV_SCREEN",
+    "CONTROL_TV_STANBY",
+    "CONTROL_FAVORITE_GROUP",
+    "CONTROL_USER_INFO",
+    "CHECK_BLUETOOTH_DEVICE",
+    "CONTROL_BLUETOOTH",
+    "CONTROL_TIMER_INFO",
+    "STB_INTERNAL_CONNECTION",
+    "CONTROL_RECORDING",
+    "READ_RECORDING_STATE",
+    "WRITE_RECORDING_LIST",
+    "READ_RECORDING_LIST",
+    "READ_RECORDING_SCHEDULE",
+    "WRITE_RECORDING_SCHEDULE",
+    "READ_STORAGE_DEVICE_LIST",
+    "READ_TV_PROGRAM_INFO",
+    "CONTROL_BOX_CHANNEL",
+    "READ_TV_ACR_AUTH_TOKEN",
+    "READ_TV_CONTENT_STATE",
+    "READ_TV_CURRENT_TIME",
+    "ADD_LAUNCHER_CHANNEL",
+    "SET_CHANNEL_SKIP",
+    "RELEASE_CHANNEL_SKIP",
+    "CONTROL_CHANNEL_BLOCK",
+    "DELETE_SELECT_CHANNEL",
+    "CONTROL_CHANNEL_GROUP",
+    "SCAN_TV_CHANNELS",
+    "CONTROL_TV_POWER",
+    "CONTROL_WOL",
+];
+
+/// The permissions requested from the TV when pairing.
+///
+/// The TV grants scopes per client key, so a remote must register with the
+/// same set that was used during `auth`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionSet {
+    permissions: Vec<String>,
+}
+
+impl PermissionSet {
+    pub fn all() -> Self {
+        Self {
+            permissions: ALL_PERMISSIONS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            permissions: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, permission: &str) -> Result<Self> {
+        let permission = Self::known(permission)?;
+        if !self.contains(permission) {
+            self.permissions.push(permission.to_string());
+        }
+        Ok(self)
+    }
+
+    pub fn without(mut self, permission: &str) -> Result<Self> {
+        let permission = Self::known(permission)?;
+        self.permissions.retain(|p| p != permission);
+        Ok(self)
+    }
+
+    pub fn contains(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+
+    pub fn is_all(&self) -> bool {
+        ALL_PERMISSIONS.iter().all(|p| self.contains(p))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.permissions.iter().map(|p| p.as_str())
+    }
+
+    /// Parse a comma-separated list such as `CONTROL_AUDIO,CONTROL_INPUT_TV`.
+    /// The list may start with `all` and then exclude scopes with a leading
+    /// `-`, e.g. `all,-CONTROL_POWER,-READ_CURRENT_CHANNEL`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut set = Self::empty();
+        for (i, item) in spec.split(',').map(str::trim).enumerate() {
+            if item.is_empty() {
+                continue;
+            }
+            if i == 0 && item.eq_ignore_ascii_case("all") {
+                set = Self::all();
+            } else if let Some(excluded) = item.strip_prefix('-') {
+                set = set.without(excluded)?;
+            } else {
+                set = set.with(item)?;
+            }
+        }
+        Ok(set)
+    }
+
+    fn known(permission: &str) -> Result<&'static str> {
+        ALL_PERMISSIONS
+            .iter()
+            .find(|p| p.eq_ignore_ascii_case(permission))
+            .copied()
+            .ok_or_else(|| LgtvError::ConfigError(format!("Unknown permission: {}", permission)))
+    }
+}
+
+impl Default for PermissionSet {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Hello data payload used for connecting to the TV, requesting every
+/// permission in [`ALL_PERMISSIONS`]
 pub fn hello_data() -> Value {
+    hello_data_with(&PermissionSet::all())
+}
+
+/// Hello data payload requesting only the given permissions
+pub fn hello_data_with(permissions: &PermissionSet) -> Value {
     json!({
         "id": "register_0",
         "payload": {
@@ -9,60 +155,7 @@ pub fn hello_data() -> Value {
             "manifest": {
                 "appVersion": "1.1",
                 "manifestVersion": 1,
-                "permissions": [
-                    "LAUNCH",
-                    "LAUNCH_WEBAPP",
-                    "APP_TO_APP",
-                    "CLOSE",
-                    "TEST_OPEN",
-                    "TEST_PROTECTED",
-                    "CONTROL_AUDIO",
-                    "CONTROL_DISPLAY",
-                    "CONTROL_INPUT_JOYSTICK",
-                    "CONTROL_INPUT_MEDIA_RECORDING",
-                    "CONTROL_INPUT_MEDIA_PLAYBACK",
-                    "CONTROL_INPUT_TV",
-                    "CONTROL_POWER",
-                    "READ_APP_STATUS",
-                    "READ_CURRENT_CHANNEL",
-                    "READ_INPUT_DEVICE_LIST",
-                    "READ_NETWORK_STATE",
-                    "READ_RUNNING_APPS",
-                    "READ_TV_CHANNEL_LIST",
-                    "WRITE_NOTIFICATION_TOAST",
-                    "READ_POWER_STATE",
-                    "READ_COUNTRY_INFO",
-                    "READ_SETTINGS",
-                    "CONTROL_TV_SCREEN",
-                    "CONTROL_TV_STANBY",
-                    "CONTROL_FAVORITE_GROUP",
-                    "CONTROL_USER_INFO",
-                    "CHECK_BLUETOOTH_DEVICE",
-                    "CONTROL_BLUETOOTH",
-                    "CONTROL_TIMER_INFO",
-                    "STB_INTERNAL_CONNECTION",
-                    "CONTROL_RECORDING",
-                    "READ_RECORDING_STATE",
-                    "WRITE_RECORDING_LIST",
-                    "READ_RECORDING_LIST",
-                    "READ_RECORDING_SCHEDULE",
-                    "WRITE_RECORDING_SCHEDULE",
-                    "READ_STORAGE_DEVICE_LIST",
-                    "READ_TV_PROGRAM_INFO",
-                    "CONTROL_BOX_CHANNEL",
-                    "READ_TV_ACR_AUTH_TOKEN",
-                    "READ_TV_CONTENT_STATE",
-                    "READ_TV_CURRENT_TIME",
-                    "ADD_LAUNCHER_CHANNEL",
-                    "SET_CHANNEL_SKIP",
-                    "RELEASE_CHANNEL_SKIP",
-                    "CONTROL_CHANNEL_BLOCK",
-                    "DELETE_SELECT_CHANNEL",
-                    "CONTROL_CHANNEL_GROUP",
-                    "SCAN_TV_CHANNELS",
-                    "CONTROL_TV_POWER",
-                    "CONTROL_WOL"
-                ],
+                "permissions": permissions.iter().collect::<Vec<_>>(),
                 "signatures": [
                     {
                         "signature": "eyJhbGdvcml0aG0iOiJSU0EtU0hBMjU2Iiwia2V5SWQiOiJ0ZXN0LXNpZ25pbmctY2VydCIsInNpZ25hdHVyZVZlcnNpb24iOjF9.hrVRgjCwXVvE2OOSpDZ58hR+59aFNwYDyjQgKk3auukd7pcegmE2CzPCa0bJ0ZsRAcKkCTJrWo5iDzNhMBWRyaMOv5zWSrthlf7G128qvIlpMT0YNY+n/FaOHE73uLrS/g7swl3/qH/BGFG2Hu4RlL48eb3lLKqTt2xKHdCs6Cd4RMfJPYnzgvI4BNrFUKsjkcu+WD4OO2A27Pq1n50cMchmcaXadJhGrOqH5YmHdOCj5NSHzJYrsW0HPlpuAx/ECMeIZYDh6RMqaFM2DXzdKX9NmmyqzJ3o/0lkk/N97gfVRLW5hA29yeAwaCViZNCP8iC9aO0q9fQojoa7NQnAtw==",
@@ -0,0 +1,112 @@
+//! A spinner on stderr with the time elapsed, for commands that wait on
+//! the network, so a slow TV doesn't look like a hang. Only drawn when
+//! [`enable`]d, which `main` does for interactive terminals.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const TICK: Duration = Duration::from_millis(100);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The spinner being shown, if any. Held while anything is printed, so
+/// output and redraws don't interleave.
+static LINE: Mutex<Option<Line>> = Mutex::new(None);
+
+struct Line {
+    message: String,
+    started: Instant,
+    frame: usize,
+}
+
+impl Line {
+    fn draw(&self) {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r{} {} ({}s)\x1b[K",
+            FRAMES[self.frame % FRAMES.len()],
+            self.message,
+            self.started.elapsed().as_secs()
+        );
+        let _ = stderr.flush();
+    }
+}
+
+fn clear() {
+    let mut stderr = io::stderr().lock();
+    let _ = write!(stderr, "\r\x1b[K");
+    let _ = stderr.flush();
+}
+
+fn line() -> std::sync::MutexGuard<'static, Option<Line>> {
+    LINE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn enable(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Shows `message` with a spinner until dropped.
+pub struct Spinner {
+    task: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str) -> Self {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return Self { task: None };
+        }
+        *line() = Some(Line {
+            message: message.to_string(),
+            started: Instant::now(),
+            frame: 0,
+        });
+        let task = tokio::spawn(async {
+            let mut tick = tokio::time::interval(TICK);
+            loop {
+                tick.tick().await;
+                if let Some(line) = line().as_mut() {
+                    line.frame += 1;
+                    line.draw();
+                }
+            }
+        });
+        Self { task: Some(task) }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            if line().take().is_some() {
+                clear();
+            }
+        }
+    }
+}
+
+/// Change what the current spinner says, keeping its elapsed time.
+pub fn set_message(message: &str) {
+    if let Some(line) = line().as_mut() {
+        line.message = message.to_string();
+        line.draw();
+    }
+}
+
+/// Run `print` with the spinner out of the way, drawing it again after.
+pub fn suspend<T>(print: impl FnOnce() -> T) -> T {
+    let line = line();
+    if line.is_some() {
+        clear();
+    }
+    let result = print();
+    if let Some(line) = line.as_ref() {
+        line.draw();
+    }
+    result
+}
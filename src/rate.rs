@@ -0,0 +1,98 @@
+//! Spacing out what is sent to the TV, for firmware that drops or reorders
+//! requests arriving in quick succession.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Instant};
+
+/// How fast a connection may send: up to `burst` messages back to back,
+/// then one every `min_gap`. See
+/// [`LgtvRemoteBuilder::rate_limit`](crate::LgtvRemoteBuilder::rate_limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RateLimitConfig", into = "RateLimitConfig")]
+pub struct RateLimit {
+    min_gap: Duration,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// At most one message every `min_gap`.
+    pub fn new(min_gap: Duration) -> Self {
+        Self { min_gap, burst: 1 }
+    }
+
+    /// Allow this many messages without a gap after a quiet spell
+    /// (default 1).
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    pub fn min_gap(&self) -> Duration {
+        self.min_gap
+    }
+}
+
+/// `rate_limit` as written in the config file, e.g.
+/// `{"min_gap_ms": 100, "burst": 3}`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitConfig {
+    min_gap_ms: u64,
+    #[serde(default = "one")]
+    burst: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl From<RateLimitConfig> for RateLimit {
+    fn from(config: RateLimitConfig) -> Self {
+        Self::new(Duration::from_millis(config.min_gap_ms)).burst(config.burst)
+    }
+}
+
+impl From<RateLimit> for RateLimitConfig {
+    fn from(limit: RateLimit) -> Self {
+        Self {
+            min_gap_ms: u64::try_from(limit.min_gap.as_millis()).unwrap_or(u64::MAX),
+            burst: limit.burst,
+        }
+    }
+}
+
+/// Holds senders back to keep to a [`RateLimit`], letting them go in the
+/// order they arrived.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    /// When the next message would be sent if there had been no burst
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Wait until the next message may be sent.
+    pub(crate) async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        let due = (*next).max(now);
+        let slack = self.limit.min_gap * (self.limit.burst - 1);
+        if let Some(at) = due.checked_sub(slack).filter(|at| *at > now) {
+            sleep_until(at).await;
+        }
+        *next = due + self.limit.min_gap;
+    }
+}
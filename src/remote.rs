@@ -1,30 +1,690 @@
+use crate::cancel::CancellationToken;
+use crate::config::{self, TvConfigEntry};
 use crate::error::{LgtvError, Result};
-use crate::payload;
+use crate::http;
+use crate::payload::{self, PermissionSet};
+use crate::rate::{RateLimit, RateLimiter};
+use crate::tls::{self, TlsOptions};
+use crate::trace;
 use base64::Engine;
 use futures_util::{SinkExt, StreamExt};
-use serde_json::{json, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
+use std::future::Future;
 use std::net::ToSocketAddrs;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::{mpsc, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, MutexGuard, RwLock, RwLockReadGuard};
+use tokio::sync::{broadcast, mpsc, Notify};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::{timeout, timeout_at, Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::Message;
 use wake_on_lan::MagicPacket;
 
+/// How long each step of [`LgtvRemote::verify`] may take.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a request waits for its answer unless told otherwise.
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Connection events kept for subscribers that fall behind.
+const EVENT_CAPACITY: usize = 16;
+/// How long [`LgtvRemote::close`] waits for the TV to acknowledge.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Result of [`LgtvRemote::verify`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "reason")]
+pub enum VerifyStatus {
+    /// The TV accepted the client key and answered a request.
+    Valid,
+    /// The TV is reachable but no longer accepts the client key.
+    KeyRejected,
+    /// The TV could not be reached, e.g. because it is off.
+    Unreachable(String),
+}
+
+/// A snapshot of what the TV is doing, from [`LgtvRemote::status`]. Fields
+/// the TV didn't report are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TvStatus {
+    /// "Active", "Screen Off", "Active Standby" and so on.
+    pub power: Option<String>,
+    pub volume: Option<u64>,
+    pub muted: Option<bool>,
+    /// The input being shown, e.g. `HDMI_1`, or `LIVE_TV` for the tuner.
+    pub input: Option<String>,
+    pub app: Option<String>,
+    /// Only set while watching live TV.
+    pub channel_number: Option<String>,
+    pub channel_name: Option<String>,
+}
+
+/// Where a connection is in its life, from [`LgtvRemote::state`]. Commands
+/// can only be sent when it is `Ready`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// Not connected yet, or the connection dropped.
+    Disconnected,
+    /// Opening the websocket.
+    Connecting,
+    /// Connected, waiting for the TV to accept the client key.
+    Registering,
+    Ready,
+    /// Ended by [`LgtvRemote::close`].
+    Closed,
+}
+
+impl std::fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Registering => "registering",
+            ConnectionState::Ready => "ready",
+            ConnectionState::Closed => "closed",
+        })
+    }
+}
+
+/// A change in the connection to the TV, from [`LgtvRemote::events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// Connected and registered, at first or again after a drop.
+    Connected,
+    /// The connection ended. Requests waiting for an answer fail, and
+    /// subscriptions end.
+    Disconnected { reason: String },
+    /// Attempt `attempt` to reconnect starts after `delay`.
+    Reconnecting { attempt: u32, delay: Duration },
+    /// Reconnecting failed `attempts` times, or the TV no longer accepts the
+    /// client key; nothing more is tried.
+    GaveUp { attempts: u32, reason: String },
+}
+
+/// How [`LgtvRemote`] reconnects when the connection drops, e.g. when the
+/// TV goes to standby: after `initial_delay`, then twice as long after
+/// each failure, up to `max_delay`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long to wait before the first attempt (default 1 second).
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// The longest wait between attempts (default 60 seconds).
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Give up after this many failed attempts (default: never).
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+}
+
+/// How [`LgtvRemote`] holds on to commands issued while it isn't connected,
+/// e.g. while the TV wakes up: up to `capacity` of them (default 32), each
+/// for up to `ttl` (default 30 seconds), sent in order once the connection
+/// is ready again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuePolicy {
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl Default for QueuePolicy {
+    fn default() -> Self {
+        Self {
+            capacity: 32,
+            ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+impl QueuePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many commands may wait at once; more fail with
+    /// [`LgtvError::QueueFull`].
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// How long a command waits for the connection before failing with
+    /// [`LgtvError::NotConnected`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+/// Picture shown next to a toast from [`LgtvRemote::notification`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationIcon {
+    /// An image file on this machine.
+    File(PathBuf),
+    /// An image to download, over HTTP or HTTPS.
+    Url(String),
+    /// The launcher icon of one of the TV's apps, by app ID or title as
+    /// for [`LgtvRemote::find_app`].
+    App(String),
+}
+
+/// A connection to one TV. Commands take `&self`, and clones are handles to
+/// the same connection, so several tasks can send commands at once; each
+/// gets its own answer. Options set on a handle (port, timeout and so on)
+/// only apply to that handle.
+#[derive(Clone)]
 pub struct LgtvRemote {
     client_key: String,
     mac_address: Option<String>,
     ip: String,
     hostname: Option<String>,
     name: String,
-    command_count: u32,
+    command_count: Arc<AtomicU32>,
     ssl: bool,
-    handshake_done: Arc<Mutex<bool>>,
-    response_channels: Arc<Mutex<HashMap<String, mpsc::Sender<Value>>>>,
-    ws_tx: Option<mpsc::Sender<Message>>,
+    port: Option<u16>,
+    state: Arc<RwLock<ConnectionState>>,
+    response_channels: Arc<ResponseChannels>,
+    ws_tx: Arc<RwLock<Option<mpsc::Sender<Message>>>>,
+    permissions: PermissionSet,
+    /// Pinned, or learned from the first TLS connection
+    cert_fingerprint: Arc<RwLock<Option<String>>>,
+    /// How certificates are checked, apart from the fingerprint above
+    tls: TlsOptions,
+    broadcast_address: Option<String>,
+    command_timeout: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    cancel: Option<CancellationToken>,
+    queue: Option<Arc<CommandQueue>>,
+    rate: Option<Arc<RateLimiter>>,
+    events: broadcast::Sender<ConnectionEvent>,
+    tasks: Arc<Tasks>,
+    /// Aborts the tasks once the last handle given out is dropped. The
+    /// handle the reader task keeps for reconnecting has none, or the
+    /// connection would keep itself alive.
+    _owner: Option<Arc<Owner>>,
+}
+
+/// Commands waiting for the connection to be ready, see [`QueuePolicy`].
+struct CommandQueue {
+    policy: QueuePolicy,
+    waiting: AtomicUsize,
+    /// Taken in turn, so commands go out in the order they were issued
+    turn: tokio::sync::Mutex<()>,
+    /// Woken when the connection becomes ready or is closed
+    changed: Notify,
+}
+
+/// A command's place in the [`CommandQueue`], until it has been sent.
+struct QueueTurn<'a> {
+    _turn: tokio::sync::MutexGuard<'a, ()>,
+    _waiting: Waiting<'a>,
+}
+
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl CommandQueue {
+    fn new(policy: QueuePolicy) -> Arc<Self> {
+        Arc::new(Self {
+            policy,
+            waiting: AtomicUsize::new(0),
+            turn: tokio::sync::Mutex::new(()),
+            changed: Notify::new(),
+        })
+    }
+
+    /// Wait until the connection is ready and it is this command's turn.
+    async fn wait_ready(&self, state: &RwLock<ConnectionState>) -> Result<QueueTurn<'_>> {
+        let count = self.waiting.fetch_add(1, Ordering::SeqCst);
+        let waiting = Waiting(&self.waiting);
+        if count >= self.policy.capacity {
+            return Err(LgtvError::QueueFull(self.policy.capacity));
+        }
+        let deadline = Instant::now() + self.policy.ttl;
+        let expired = || LgtvError::NotConnected(*read(state));
+        let turn = timeout_at(deadline, self.turn.lock())
+            .await
+            .map_err(|_| expired())?;
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            // Registered before checking, so a change in between isn't missed
+            changed.as_mut().enable();
+            match *read(state) {
+                ConnectionState::Ready => {
+                    return Ok(QueueTurn {
+                        _turn: turn,
+                        _waiting: waiting,
+                    })
+                }
+                ConnectionState::Closed => {
+                    return Err(LgtvError::NotConnected(ConnectionState::Closed))
+                }
+                _ => {}
+            }
+            timeout_at(deadline, changed).await.map_err(|_| expired())?;
+        }
+    }
+
+    fn wake(&self) {
+        self.changed.notify_waiters();
+    }
+}
+
+/// The reader and writer tasks of the current connection, and of earlier
+/// ones that haven't finished yet.
+#[derive(Default)]
+struct Tasks(std::sync::Mutex<TaskList>);
+
+#[derive(Default)]
+struct TaskList {
+    handles: Vec<JoinHandle<()>>,
+    /// Set by [`LgtvRemote::close`], so the reader doesn't reconnect
+    closing: bool,
+    /// Set once every handle is gone; tasks started after are stopped
+    dropped: bool,
+}
+
+impl Tasks {
+    fn list(&self) -> MutexGuard<'_, TaskList> {
+        lock(&self.0)
+    }
+
+    fn push(&self, handle: JoinHandle<()>) {
+        let mut list = self.list();
+        if list.dropped {
+            handle.abort();
+            return;
+        }
+        list.handles.retain(|h| !h.is_finished());
+        list.handles.push(handle);
+    }
+
+    fn closing(&self) -> bool {
+        let list = self.list();
+        list.closing || list.dropped
+    }
+}
+
+struct Owner(Arc<Tasks>);
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        let mut list = self.0.list();
+        list.dropped = true;
+        for handle in list.handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+/// Where the answers to each message ID go, taken out only briefly so it
+/// can be tidied up from `Drop`.
+type ResponseChannels = std::sync::Mutex<HashMap<String, mpsc::Sender<Value>>>;
+
+/// Removes a message's entry from the response channels when dropped, so a
+/// request given up on (timed out, cancelled, or its future dropped)
+/// leaves nothing behind. [`keep`](Self::keep) is for subscriptions.
+struct PendingResponse {
+    channels: Arc<ResponseChannels>,
+    id: Option<String>,
+}
+
+impl PendingResponse {
+    fn id(&self) -> &str {
+        self.id.as_deref().unwrap_or_default()
+    }
+
+    fn keep(mut self) {
+        self.id = None;
+    }
+}
+
+impl Drop for PendingResponse {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            lock(&self.channels).remove(id);
+        }
+    }
+}
+
+/// Marks the connection `Disconnected` if an attempt to connect fails or
+/// is given up on before [`succeed`](Self::succeed).
+struct Attempt<'a> {
+    state: &'a RwLock<ConnectionState>,
+    done: bool,
+}
+
+impl Attempt<'_> {
+    fn succeed(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            write(self.state, ConnectionState::Disconnected);
+        }
+    }
+}
+
+/// Aborts the tasks of a socket that never got registered, e.g. because
+/// [`LgtvRemote::connect`] was cancelled partway.
+struct AbortOnDrop(Vec<AbortHandle>);
+
+impl AbortOnDrop {
+    fn disarm(mut self) {
+        self.0.clear();
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+/// `lock`, even if a thread panicked while holding it.
+fn lock<T>(lock: &std::sync::Mutex<T>) -> MutexGuard<'_, T> {
+    lock.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// `lock` for reading, even if a thread panicked while writing it.
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>, value: T) {
+    *lock.write().unwrap_or_else(|e| e.into_inner()) = value;
+}
+
+/// Options for a new [`LgtvRemote`], from [`LgtvRemote::builder`]. Only
+/// the client key and an address (IP or hostname) are required.
+#[derive(Debug, Clone, Default)]
+pub struct LgtvRemoteBuilder {
+    name: Option<String>,
+    ip: Option<String>,
+    hostname: Option<String>,
+    mac: Option<String>,
+    client_key: Option<String>,
+    ssl: bool,
+    port: Option<u16>,
+    permissions: PermissionSet,
+    cert_fingerprint: Option<String>,
+    tls: TlsOptions,
+    broadcast_address: Option<String>,
+    timeout: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    cancel: Option<CancellationToken>,
+    queue: Option<QueuePolicy>,
+    rate_limit: Option<RateLimit>,
+}
+
+impl LgtvRemoteBuilder {
+    /// What to call the TV in log messages (default: its address).
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn ip(mut self, ip: impl Into<String>) -> Self {
+        self.ip = Some(ip.into());
+        self
+    }
+
+    /// Looked up when [`build`](Self::build) is called, if no IP is given.
+    pub fn hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// The TV's MAC address, for [`LgtvRemote::on`].
+    pub fn mac(mut self, mac: impl Into<String>) -> Self {
+        self.mac = Some(mac.into());
+        self
+    }
+
+    /// The key the TV handed out when pairing, see [`crate::LgtvAuth`].
+    pub fn client_key(mut self, key: impl Into<String>) -> Self {
+        self.client_key = Some(key.into());
+        self
+    }
+
+    /// Connect with TLS, on port 3001 unless [`port`](Self::port) says
+    /// otherwise.
+    pub fn ssl(mut self, ssl: bool) -> Self {
+        self.ssl = ssl;
+        self
+    }
+
+    /// Connect to this port instead of the default 3000 (or 3001 with SSL).
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// The permissions that were requested during pairing (default: all).
+    pub fn permissions(mut self, permissions: PermissionSet) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Only accept a TLS certificate with this SHA-256 fingerprint.
+    pub fn cert_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.cert_fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Check the TV's certificate as `options` say. A fingerprint pinned
+    /// there is used as if given to [`cert_fingerprint`](Self::cert_fingerprint).
+    pub fn tls(mut self, options: TlsOptions) -> Self {
+        if let Some(fingerprint) = options.fingerprint() {
+            self.cert_fingerprint = Some(fingerprint.to_string());
+        }
+        self.tls = options;
+        self
+    }
+
+    /// Where to send Wake-on-LAN packets, e.g. `192.168.1.255` or
+    /// `192.168.1.255:7` (default: 255.255.255.255:9).
+    pub fn broadcast_address(mut self, address: impl Into<String>) -> Self {
+        self.broadcast_address = Some(address.into());
+        self
+    }
+
+    /// Give up on a request if the TV hasn't answered within `timeout`
+    /// (default [`DEFAULT_COMMAND_TIMEOUT`]); zero waits as long as the
+    /// connection is open.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Reconnect by `policy` when the connection drops, instead of failing
+    /// every command from then on.
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Fail connecting, requests and subscribing with
+    /// [`LgtvError::Cancelled`] once `token` is cancelled, e.g. on shutdown.
+    /// The connection stays open; see [`LgtvRemote::close`].
+    pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Hold commands issued while the connection isn't ready by `policy`
+    /// and send them once it is, instead of failing them straight away.
+    /// Useful together with [`reconnect`](Self::reconnect).
+    pub fn queue(mut self, policy: QueuePolicy) -> Self {
+        self.queue = Some(policy);
+        self
+    }
+
+    /// Space out commands by `limit`, for TVs that drop or reorder
+    /// requests sent in quick succession. Pointer sockets opened through
+    /// the remote keep to it too.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// The remote, not yet connected; see [`LgtvRemote::connect`].
+    pub fn build(self) -> Result<LgtvRemote> {
+        let client_key = self
+            .client_key
+            .ok_or_else(|| LgtvError::AuthError("Client key is required".to_string()))?;
+
+        let ip_addr = match (self.ip, &self.hostname) {
+            (Some(ip), _) => ip,
+            (None, Some(host)) => {
+                let socket_addr =
+                    (host.as_str(), 0)
+                        .to_socket_addrs()?
+                        .next()
+                        .ok_or_else(|| {
+                            LgtvError::ConnectionError(format!(
+                                "Could not resolve hostname: {}",
+                                host
+                            ))
+                        })?;
+                socket_addr.ip().to_string()
+            }
+            (None, None) => {
+                return Err(LgtvError::ConnectionError(
+                    "Either IP or hostname is required".to_string(),
+                ))
+            }
+        };
+
+        let tasks = Arc::new(Tasks::default());
+        Ok(LgtvRemote {
+            client_key,
+            mac_address: self.mac,
+            name: self.name.unwrap_or_else(|| ip_addr.clone()),
+            ip: ip_addr,
+            hostname: self.hostname,
+            command_count: Arc::new(AtomicU32::new(0)),
+            ssl: self.ssl,
+            port: self.port,
+            state: Arc::new(RwLock::new(ConnectionState::Disconnected)),
+            response_channels: Arc::default(),
+            ws_tx: Arc::new(RwLock::new(None)),
+            permissions: self.permissions,
+            cert_fingerprint: Arc::new(RwLock::new(self.cert_fingerprint)),
+            tls: self.tls,
+            broadcast_address: self.broadcast_address,
+            command_timeout: match self.timeout {
+                None => Some(DEFAULT_COMMAND_TIMEOUT),
+                Some(Duration::ZERO) => None,
+                Some(limit) => Some(limit),
+            },
+            reconnect: self.reconnect,
+            cancel: self.cancel,
+            queue: self.queue.map(CommandQueue::new),
+            rate: self
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            _owner: Some(Arc::new(Owner(tasks.clone()))),
+            tasks,
+        })
+    }
+}
+
+/// Try to connect `remote` again by `policy`. Boxed, as it is reached from
+/// the reader task that [`LgtvRemote::connect`] itself starts.
+fn reconnect(
+    remote: LgtvRemote,
+    policy: ReconnectPolicy,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        let mut delay = policy.initial_delay;
+        let mut attempt = 0;
+        loop {
+            if policy.max_attempts.is_some_and(|max| attempt >= max) {
+                let reason = format!("no luck after {} attempts", attempt);
+                let _ = remote.events.send(ConnectionEvent::GaveUp {
+                    attempts: attempt,
+                    reason,
+                });
+                return;
+            }
+            attempt += 1;
+            let _ = remote
+                .events
+                .send(ConnectionEvent::Reconnecting { attempt, delay });
+            tokio::time::sleep(delay).await;
+            match remote.connect().await {
+                Ok(()) => {
+                    log::info!(tv = remote.name.as_str(), attempt; "Reconnected");
+                    return;
+                }
+                Err(e @ LgtvError::KeyRejected) => {
+                    let reason = e.to_string();
+                    let _ = remote.events.send(ConnectionEvent::GaveUp {
+                        attempts: attempt,
+                        reason,
+                    });
+                    return;
+                }
+                Err(e) => {
+                    log::debug!(tv = remote.name.as_str(), attempt, error:% = e; "Reconnect failed")
+                }
+            }
+            delay = (delay * 2).min(policy.max_delay);
+        }
+    })
 }
 
 impl LgtvRemote {
+    /// Start describing a remote, e.g.
+    /// `LgtvRemote::builder().ip("192.168.1.100").client_key(key).build()`.
+    pub fn builder() -> LgtvRemoteBuilder {
+        LgtvRemoteBuilder::default()
+    }
+
+    #[deprecated(note = "use LgtvRemote::builder()")]
     pub fn new(
         name: &str,
         ip: Option<&str>,
@@ -33,62 +693,225 @@ impl LgtvRemote {
         hostname: Option<&str>,
         ssl: bool,
     ) -> Result<Self> {
-        let client_key =
-            key.ok_or_else(|| LgtvError::AuthError("Client key is required".to_string()))?;
-
-        let ip_addr = match ip {
-            Some(ip) => ip.to_string(),
-            None => match hostname {
-                Some(host) => {
-                    let socket_addr = (host, 0).to_socket_addrs()?.next().ok_or_else(|| {
-                        LgtvError::ConnectionError(format!("Could not resolve hostname: {}", host))
-                    })?;
-                    socket_addr.ip().to_string()
-                }
-                None => {
-                    return Err(LgtvError::ConnectionError(
-                        "Either IP or hostname is required".to_string(),
-                    ))
-                }
-            },
-        };
+        let mut builder = Self::builder().name(name).ssl(ssl);
+        builder.ip = ip.map(str::to_string);
+        builder.hostname = hostname.map(str::to_string);
+        builder.mac = mac.map(str::to_string);
+        builder.client_key = key.map(str::to_string);
+        builder.build()
+    }
 
-        Ok(Self {
-            client_key: client_key.to_string(),
-            mac_address: mac.map(|m| m.to_string()),
-            ip: ip_addr,
-            hostname: hostname.map(|h| h.to_string()),
-            name: name.to_string(),
-            command_count: 0,
-            ssl,
-            handshake_done: Arc::new(Mutex::new(false)),
-            response_channels: Arc::new(Mutex::new(HashMap::new())),
-            ws_tx: None,
-        })
+    /// A remote for the TV stored as `name`, with that entry's connection
+    /// options (port, SSL, permissions, certificate, Wake-on-LAN address and
+    /// command timeout) applied.
+    pub fn from_entry(name: &str, entry: &TvConfigEntry) -> Result<Self> {
+        let mut builder = Self::builder().name(name).ssl(entry.ssl);
+        builder.ip = entry.ip.clone();
+        builder.hostname = entry.hostname.clone();
+        builder.mac = entry.mac.clone();
+        builder.client_key = entry.client_key()?;
+        if let Some(list) = &entry.permissions {
+            builder = builder.permissions(
+                list.iter()
+                    .try_fold(PermissionSet::empty(), |set, p| set.with(p))?,
+            );
+        }
+        if let Some(fingerprint) = &entry.cert_fingerprint {
+            builder = builder.cert_fingerprint(fingerprint);
+        }
+        if let Some(port) = entry.port {
+            builder = builder.port(port);
+        }
+        if let Some(address) = &entry.broadcast {
+            builder = builder.broadcast_address(address);
+        }
+        if let Some(secs) = entry.timeout {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        if let Some(limit) = entry.rate_limit {
+            builder = builder.rate_limit(limit);
+        }
+        builder.build()
     }
 
-    pub async fn connect(&mut self) -> Result<()> {
-        let ws_url = if self.ssl {
-            format!("wss://{}:3001/", self.ip)
-        } else {
-            format!("ws://{}:3000/", self.ip)
+    /// The TV stored as `name` in the config file, or the default TV for
+    /// `None`, as [`config::load_entry`] finds it. Not yet connected.
+    pub fn from_config<'a>(name: impl Into<Option<&'a str>>) -> Result<Self> {
+        let (name, entry) = config::load_entry(name.into())?;
+        Self::from_entry(&name, &entry)
+    }
+
+    /// Register with the same permissions that were requested during
+    /// pairing; the TV prompts again if they differ.
+    pub fn set_permissions(&mut self, permissions: PermissionSet) {
+        self.permissions = permissions;
+    }
+
+    /// Only accept the TV's TLS certificate if it has this SHA-256
+    /// fingerprint, as recorded by `auth`.
+    pub fn set_cert_fingerprint(&mut self, fingerprint: Option<&str>) {
+        write(&self.cert_fingerprint, fingerprint.map(|f| f.to_string()));
+    }
+
+    /// Connect to this port instead of the default 3000 (or 3001 with SSL).
+    pub fn set_port(&mut self, port: Option<u16>) {
+        self.port = port;
+    }
+
+    /// Send Wake-on-LAN packets to this address (e.g. `192.168.1.255`, or
+    /// with a port, `192.168.1.255:7`) instead of 255.255.255.255:9.
+    pub fn set_broadcast_address(&mut self, address: Option<&str>) {
+        self.broadcast_address = address.map(|a| a.to_string());
+    }
+
+    /// Give up on a request if the TV hasn't answered within `timeout`.
+    /// Without one, requests wait as long as the connection is open.
+    pub fn set_command_timeout(&mut self, timeout: Option<Duration>) {
+        self.command_timeout = timeout;
+    }
+
+    /// Reconnect by `policy` when the connection drops, or not at all.
+    pub fn set_reconnect(&mut self, policy: Option<ReconnectPolicy>) {
+        self.reconnect = policy;
+    }
+
+    /// Hold commands issued while the connection isn't ready by `policy`,
+    /// or fail them straight away.
+    pub fn set_queue(&mut self, policy: Option<QueuePolicy>) {
+        self.queue = policy.map(CommandQueue::new);
+    }
+
+    /// Space out commands by `limit`, or not at all.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate = limit.map(|limit| Arc::new(RateLimiter::new(limit)));
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate.as_ref().map(|rate| rate.limit())
+    }
+
+    /// Fail operations with [`LgtvError::Cancelled`] once `token` is
+    /// cancelled, or never.
+    pub fn set_cancel_token(&mut self, token: Option<CancellationToken>) {
+        self.cancel = token;
+    }
+
+    /// Run `operation` unless the cancel token fires first. Whatever it had
+    /// under way is dropped, which tidies up after it.
+    async fn cancellable<T>(&self, operation: impl Future<Output = Result<T>>) -> Result<T> {
+        let Some(token) = &self.cancel else {
+            return operation.await;
         };
+        if token.is_cancelled() {
+            return Err(LgtvError::Cancelled);
+        }
+        tokio::select! {
+            result = operation => result,
+            _ = token.cancelled() => Err(LgtvError::Cancelled),
+        }
+    }
+
+    /// What the TV is called in log messages, see
+    /// [`LgtvRemoteBuilder::name`].
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Where the connection is, for every handle of it.
+    pub fn state(&self) -> ConnectionState {
+        *read(&self.state)
+    }
+
+    /// Changes in the connection from now on, for every handle of it.
+    pub fn events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn cert_fingerprint(&self) -> Option<String> {
+        read(&self.cert_fingerprint).clone()
+    }
+
+    /// The certificate checks for this TV, with the pinned or learned
+    /// fingerprint.
+    pub fn tls_options(&self) -> TlsOptions {
+        match self.cert_fingerprint() {
+            Some(fingerprint) => self.tls.clone().pin(fingerprint),
+            None => self.tls.clone(),
+        }
+    }
+
+    pub async fn connect(&self) -> Result<()> {
+        self.cancellable(async {
+            self.tasks.list().closing = false;
+            write(&self.state, ConnectionState::Connecting);
+            let attempt = Attempt {
+                state: &self.state,
+                done: false,
+            };
+            let registered = Arc::new(AtomicBool::new(false));
+            let (response_rx, socket) = self.open_socket(registered.clone()).await?;
+            self.register(response_rx).await?;
+            socket.disarm();
+            attempt.succeed();
+            registered.store(true, Ordering::Relaxed);
+            let _ = self.events.send(ConnectionEvent::Connected);
+            Ok(())
+        })
+        .await
+    }
 
-        let (ws_stream, _) = connect_async(ws_url).await?;
+    /// Open the websocket and start the reader/writer tasks. Messages that
+    /// don't belong to a pending command arrive on the returned receiver.
+    /// Only a connection that gets `registered` is reconnected when it drops,
+    /// and the tasks are stopped if the guard returned is dropped before
+    /// that.
+    async fn open_socket(
+        &self,
+        registered: Arc<AtomicBool>,
+    ) -> Result<(mpsc::Receiver<Value>, AbortOnDrop)> {
+        let default_port = if self.ssl { 3001 } else { 3000 };
+        let ws_url = tls::ws_url(&self.ip, self.port.unwrap_or(default_port), self.ssl);
+
+        let options = self.tls_options();
+        let pinned = options.fingerprint().is_some();
+        if self.ssl && !pinned && !options.checks_certificate() {
+            log::warn!(
+                "No pinned certificate for {}, accepting whatever the TV presents. Re-run auth to pin it.",
+                self.name
+            );
+        }
+        let (ws_stream, fingerprint) = tls::connect(&ws_url, &options).await?;
+        if !pinned {
+            write(&self.cert_fingerprint, fingerprint);
+        }
 
         let (tx, mut rx) = mpsc::channel::<Message>(32);
-        self.ws_tx = Some(tx);
+        let ours = tx.downgrade();
+        write(&self.ws_tx, Some(tx));
 
-        let (response_tx, mut response_rx) = mpsc::channel::<Value>(32);
+        let (response_tx, response_rx) = mpsc::channel::<Value>(32);
 
-        let handshake_done = self.handshake_done.clone();
         let response_channels = self.response_channels.clone();
+        let ws_tx = self.ws_tx.clone();
+        let events = self.events.clone();
+        let tasks = self.tasks.clone();
+        let state = self.state.clone();
+        let tv = self.name.clone();
+        let reconnector = self.reconnect.clone().map(|policy| {
+            let remote = LgtvRemote {
+                _owner: None,
+                ..self.clone()
+            };
+            (remote, policy)
+        });
 
         let (mut ws_writer, mut ws_reader) = ws_stream.split();
+        let peer = ws_url.clone();
 
         // Writer task
-        tokio::spawn(async move {
+        let writer = tokio::spawn(async move {
             while let Some(msg) = rx.recv().await {
+                trace::sent(&peer, &msg);
                 if ws_writer.send(msg).await.is_err() {
                     break;
                 }
@@ -96,59 +919,196 @@ impl LgtvRemote {
         });
 
         // Reader task
-        tokio::spawn(async move {
+        let reader = tokio::spawn(async move {
+            let mut reason = "the connection was lost".to_string();
             while let Some(msg) = ws_reader.next().await {
+                if let Ok(msg) = &msg {
+                    trace::received(&ws_url, msg);
+                }
                 match msg {
                     Ok(Message::Text(text)) => {
                         if let Ok(json) = serde_json::from_str::<Value>(&text) {
-                            log::debug!("Received response: {}", json);
+                            let id = json.get("id").and_then(Value::as_str);
+                            log::debug!(tv = tv.as_str(), id; "Received {}", json);
 
-                            if let Some(id) = json.get("id").and_then(|v| v.as_str()) {
-                                let mut channels = response_channels.lock().await;
-                                if let Some(tx) = channels.get(id) {
+                            let tx = id.and_then(|id| lock(&response_channels).get(id).cloned());
+                            match (tx, id) {
+                                (Some(tx), Some(id)) => {
                                     if tx.send(json.clone()).await.is_err() {
-                                        channels.remove(id);
+                                        lock(&response_channels).remove(id);
                                     }
-                                } else {
+                                }
+                                _ => {
                                     let _ = response_tx.send(json.clone()).await;
                                 }
-                            } else {
-                                let _ = response_tx.send(json.clone()).await;
                             }
                         }
                     }
-                    Ok(Message::Close(_)) => break,
+                    Ok(Message::Close(_)) => {
+                        reason = "the TV closed the connection".to_string();
+                        break;
+                    }
                     Err(e) => {
-                        log::error!("WebSocket error: {}", e);
+                        log::error!(tv = tv.as_str(); "WebSocket error: {}", e);
+                        reason = e.to_string();
                         break;
                     }
                     _ => {}
                 }
             }
+
+            // Unless a newer connection has replaced this one (and owns the
+            // pending requests and reconnecting now), stop the writer, fail
+            // the requests still waiting for an answer and reconnect
+            let replaced = {
+                let mut current = ws_tx.write().unwrap_or_else(|e| e.into_inner());
+                let replaced = match (current.as_ref(), ours.upgrade()) {
+                    (Some(current), Some(ours)) => !current.same_channel(&ours),
+                    (Some(_), None) => true,
+                    // Taken by close(), which sets the state itself
+                    (None, _) => false,
+                };
+                if !replaced && current.take().is_some() {
+                    write(&state, ConnectionState::Disconnected);
+                }
+                replaced
+            };
+            if replaced {
+                log::debug!(tv = tv.as_str(), reason = reason.as_str(); "Replaced connection ended");
+                return;
+            }
+            lock(&response_channels).clear();
+            log::debug!(tv = tv.as_str(), reason = reason.as_str(); "Disconnected");
+            let _ = events.send(ConnectionEvent::Disconnected { reason });
+
+            match reconnector {
+                Some((remote, policy))
+                    if registered.load(Ordering::Relaxed) && !tasks.closing() =>
+                {
+                    reconnect(remote, policy).await
+                }
+                _ => {}
+            }
         });
+        let socket = AbortOnDrop(vec![writer.abort_handle(), reader.abort_handle()]);
+        self.tasks.push(writer);
+        self.tasks.push(reader);
+
+        Ok((response_rx, socket))
+    }
+
+    /// Say goodbye to the TV and stop the connection's tasks, without
+    /// reconnecting. Requests still waiting for an answer fail. Affects
+    /// every handle; [`connect`](Self::connect) opens a new connection.
+    pub async fn close(&self) {
+        let handles = {
+            let mut list = self.tasks.list();
+            list.closing = true;
+            std::mem::take(&mut list.handles)
+        };
+        // The writer sends the Close frame and stops once its last sender
+        // is gone; the reader stops when the TV answers it
+        let tx = self.ws_tx.write().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(tx) = tx {
+            let _ = tx.send(Message::Close(None)).await;
+        }
+        let aborts: Vec<_> = handles.iter().map(JoinHandle::abort_handle).collect();
+        let finished = timeout(CLOSE_TIMEOUT, futures_util::future::join_all(handles)).await;
+        if finished.is_err() {
+            log::debug!(tv = self.name.as_str(); "The TV didn't close the connection in time");
+            for abort in aborts {
+                abort.abort();
+            }
+        }
+        lock(&self.response_channels).clear();
+        write(&self.state, ConnectionState::Closed);
+        if let Some(queue) = &self.queue {
+            queue.wake();
+        }
+    }
+
+    /// Register with the stored client key.
+    async fn register(&self, mut response_rx: mpsc::Receiver<Value>) -> Result<()> {
+        write(&self.state, ConnectionState::Registering);
 
         // Send hello data for handshake
-        let mut hello_data = payload::hello_data();
+        let mut hello_data = payload::hello_data_with(&self.permissions);
         hello_data["payload"]["client-key"] = json!(self.client_key);
         self.send_message(hello_data.to_string()).await?;
 
         // Wait for handshake response
         while let Some(response) = response_rx.recv().await {
+            // A revoked or unknown key gets an error, or a pairing prompt on
+            // the TV that nobody is going to answer
+            let rejected = response.get("type").and_then(|v| v.as_str()) == Some("error")
+                || response
+                    .get("payload")
+                    .and_then(|p| p.get("pairingType"))
+                    .is_some();
+            if rejected {
+                log::debug!(tv = self.name.as_str(); "Registration rejected: {}", response);
+                return Err(LgtvError::KeyRejected);
+            }
+
             if let Some(payload) = response.get("payload") {
                 if payload.get("client-key").is_some() {
-                    log::debug!("Handshake complete");
-                    let mut handshake = handshake_done.lock().await;
-                    *handshake = true;
-                    break;
+                    log::debug!(tv = self.name.as_str(); "Handshake complete");
+                    write(&self.state, ConnectionState::Ready);
+                    if let Some(queue) = &self.queue {
+                        queue.wake();
+                    }
+                    return Ok(());
                 }
             }
         }
 
-        Ok(())
+        Err(LgtvError::ConnectionError(
+            "Connection closed during registration".to_string(),
+        ))
+    }
+
+    /// Check whether the stored client key is still accepted, telling a
+    /// revoked key apart from a TV that is off or unreachable.
+    pub async fn verify(&self) -> Result<VerifyStatus> {
+        write(&self.state, ConnectionState::Connecting);
+        let attempt = Attempt {
+            state: &self.state,
+            done: false,
+        };
+        let (response_rx, socket) =
+            match timeout(VERIFY_TIMEOUT, self.open_socket(Arc::default())).await {
+                Ok(Ok(opened)) => opened,
+                Ok(Err(e @ LgtvError::CertificateMismatch { .. })) => return Err(e),
+                Ok(Err(e)) => return Ok(VerifyStatus::Unreachable(e.to_string())),
+                Err(_) => {
+                    return Ok(VerifyStatus::Unreachable(
+                        "Timed out connecting to the TV".to_string(),
+                    ))
+                }
+            };
+
+        match timeout(VERIFY_TIMEOUT, self.register(response_rx)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(LgtvError::KeyRejected)) => return Ok(VerifyStatus::KeyRejected),
+            Ok(Err(e)) => return Ok(VerifyStatus::Unreachable(e.to_string())),
+            // Some firmware answers an unknown key with nothing at all
+            Err(_) => return Ok(VerifyStatus::KeyRejected),
+        }
+        socket.disarm();
+        attempt.succeed();
+
+        match timeout(VERIFY_TIMEOUT, self.list_services()).await {
+            Ok(Ok(_)) => Ok(VerifyStatus::Valid),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Ok(VerifyStatus::Unreachable(
+                "TV accepted the key but did not answer a request".to_string(),
+            )),
+        }
     }
 
     async fn send_message(&self, message: String) -> Result<()> {
-        if let Some(tx) = &self.ws_tx {
+        let tx = read(&self.ws_tx).clone();
+        if let Some(tx) = tx {
             tx.send(Message::Text(message)).await.map_err(|e| {
                 LgtvError::ConnectionError(format!("Failed to send message: {}", e))
             })?;
@@ -161,24 +1121,51 @@ impl LgtvRemote {
     }
 
     pub async fn send_command(
-        &mut self,
+        &self,
         msg_type: &str,
         uri: &str,
         payload: Option<Value>,
         prefix: Option<&str>,
     ) -> Result<mpsc::Receiver<Value>> {
-        let handshake_done = *self.handshake_done.lock().await;
-        if !handshake_done {
-            return Err(LgtvError::CommandError(
-                "Handshake not completed".to_string(),
-            ));
-        }
+        let (pending, rx) = self
+            .cancellable(self.send_with_id(msg_type, uri, payload, prefix))
+            .await?;
+        // Answers keep coming until the receiver is dropped
+        pending.keep();
+        Ok(rx)
+    }
+
+    /// [`send_command`](Self::send_command), also giving the guard that
+    /// stops routing answers to the receiver.
+    async fn send_with_id(
+        &self,
+        msg_type: &str,
+        uri: &str,
+        payload: Option<Value>,
+        prefix: Option<&str>,
+    ) -> Result<(PendingResponse, mpsc::Receiver<Value>)> {
+        // Held until the command is sent, keeping queued ones in order
+        let _turn = match &self.queue {
+            Some(queue) => {
+                if self.state() != ConnectionState::Ready {
+                    log::debug!(tv = self.name.as_str(), uri; "Queued until the connection is ready");
+                }
+                Some(queue.wait_ready(&self.state).await?)
+            }
+            None => {
+                let state = self.state();
+                if state != ConnectionState::Ready {
+                    return Err(LgtvError::NotConnected(state));
+                }
+                None
+            }
+        };
 
+        let count = self.command_count.fetch_add(1, Ordering::Relaxed);
         let message_id = match prefix {
-            Some(p) => format!("{}_{}", p, self.command_count),
-            None => self.command_count.to_string(),
+            Some(p) => format!("{}_{}", p, count),
+            None => count.to_string(),
         };
-        self.command_count += 1;
 
         let mut message_data = json!({
             "id": message_id,
@@ -191,31 +1178,78 @@ impl LgtvRemote {
         }
 
         let (tx, rx) = mpsc::channel::<Value>(1);
-        self.response_channels
-            .lock()
-            .await
-            .insert(message_id.clone(), tx);
+        lock(&self.response_channels).insert(message_id.clone(), tx);
+        let pending = PendingResponse {
+            channels: self.response_channels.clone(),
+            id: Some(message_id.clone()),
+        };
 
+        if let Some(rate) = &self.rate {
+            rate.wait().await;
+        }
+        log::debug!(tv = self.name.as_str(), id = message_id.as_str(), uri; "Sending {}", msg_type);
         self.send_message(message_data.to_string()).await?;
-
-        Ok(rx)
+        Ok((pending, rx))
     }
 
     /// Send a request and wait for the response payload.
     async fn send_request(
-        &mut self,
+        &self,
         uri: &str,
         payload: Option<Value>,
         prefix: Option<&str>,
     ) -> Result<Value> {
-        let mut rx = self.send_command("request", uri, payload, prefix).await?;
-        match rx.recv().await {
-            Some(response) => {
-                log::debug!("Response: {}", response);
-                Ok(response.get("payload").cloned().unwrap_or(json!({})))
+        self.cancellable(async {
+            let started = std::time::Instant::now();
+            // A request gets one answer; any later one has nowhere to go
+            // once `pending` is dropped
+            let (pending, mut rx) = self.send_with_id("request", uri, payload, prefix).await?;
+            let response = match self.command_timeout {
+                Some(limit) => timeout(limit, rx.recv()).await,
+                None => Ok(rx.recv().await),
+            };
+            let id = pending.id();
+            let response = response.map_err(|_| {
+                log::debug!(tv = self.name.as_str(), id, uri; "No answer");
+                LgtvError::Timeout(self.command_timeout.unwrap_or_default())
+            })?;
+            match response {
+                Some(response) => {
+                    let elapsed_ms = started.elapsed().as_millis() as u64;
+                    log::debug!(
+                        tv = self.name.as_str(), id, uri, elapsed_ms;
+                        "Response: {}", response
+                    );
+                    if let Some(error) = LgtvError::from_response(&response) {
+                        return Err(error);
+                    }
+                    Ok(response.get("payload").cloned().unwrap_or(json!({})))
+                }
+                None => Err(LgtvError::CommandError("No response received".to_string())),
             }
-            None => Err(LgtvError::CommandError("No response received".to_string())),
-        }
+        })
+        .await
+    }
+
+    /// Send a request that only answers whether it worked, failing unless
+    /// the TV says it did.
+    async fn send_action(&self, uri: &str, payload: Option<Value>) -> Result<()> {
+        self.send_request(uri, payload, None).await?;
+        Ok(())
+    }
+
+    /// Send a request to `uri` and decode the TV's answer as `T`: a struct
+    /// with just the fields of interest, say `struct Volume { volume: u32 }`
+    /// for `ssap://audio/getVolume`, or a [`Value`] for all of them. An
+    /// answer with `returnValue: false` is a [`LgtvError::TvError`], as for
+    /// every other method.
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        uri: &str,
+        payload: Option<Value>,
+    ) -> Result<T> {
+        let response = self.send_request(uri, payload, None).await?;
+        Ok(serde_json::from_value(response)?)
     }
 
     // ──────────────────────────────────────────────
@@ -223,22 +1257,34 @@ impl LgtvRemote {
     // ──────────────────────────────────────────────
 
     pub async fn on(&self) -> Result<()> {
-        let mac_str = self.mac_address.as_deref().ok_or_else(|| {
-            LgtvError::CommandError("MAC address is required for power on".to_string())
-        })?;
+        let mac_str = self
+            .mac_address
+            .as_deref()
+            .ok_or(LgtvError::MacAddressRequired)?;
 
         let mac_bytes = Self::parse_mac_address(mac_str)
             .map_err(|e| LgtvError::CommandError(format!("Invalid MAC address format: {}", e)))?;
 
         let magic_packet = MagicPacket::new(&mac_bytes);
-        magic_packet.send().map_err(|e| {
+        let sent = match &self.broadcast_address {
+            Some(address) => {
+                let target = if address.parse::<std::net::SocketAddr>().is_ok() {
+                    address.clone()
+                } else {
+                    format!("{}:9", address)
+                };
+                magic_packet.send_to(target.as_str(), "0.0.0.0:0")
+            }
+            None => magic_packet.send(),
+        };
+        sent.map_err(|e| {
             LgtvError::CommandError(format!("Failed to send Wake-on-LAN packet: {}", e))
         })?;
 
         Ok(())
     }
 
-    fn parse_mac_address(mac_str: &str) -> std::result::Result<[u8; 6], String> {
+    pub(crate) fn parse_mac_address(mac_str: &str) -> std::result::Result<[u8; 6], String> {
         let parts: Vec<&str> = mac_str.split([':', '-']).collect();
         if parts.len() != 6 {
             return Err(format!(
@@ -254,29 +1300,35 @@ impl LgtvRemote {
         Ok(mac_bytes)
     }
 
-    pub async fn off(&mut self) -> Result<Value> {
-        self.send_request("ssap://system/turnOff", None, None).await
+    pub async fn off(&self) -> Result<()> {
+        self.send_action("ssap://system/turnOff", None).await
     }
 
-    pub async fn screen_off(&mut self) -> Result<Value> {
-        self.send_request(
-            "ssap://com.webos.service.tvpower/power/turnOffScreen",
-            None,
-            None,
+    /// Turn the TV off after `minutes`, with its own sleep timer. The timer
+    /// only offers a few steps (10, 20, 30, 60, 90, 120, 180 or 240
+    /// minutes); 0 cancels it.
+    pub async fn set_sleep_timer(&self, minutes: u32) -> Result<()> {
+        self.send_action(
+            "ssap://settings/setSystemSettings",
+            Some(json!({
+                "category": "time",
+                "settings": {"sleepTimer": minutes.to_string()}
+            })),
         )
         .await
     }
 
-    pub async fn screen_on(&mut self) -> Result<Value> {
-        self.send_request(
-            "ssap://com.webos.service.tvpower/power/turnOnScreen",
-            None,
-            None,
-        )
-        .await
+    pub async fn screen_off(&self) -> Result<()> {
+        self.send_action("ssap://com.webos.service.tvpower/power/turnOffScreen", None)
+            .await
+    }
+
+    pub async fn screen_on(&self) -> Result<()> {
+        self.send_action("ssap://com.webos.service.tvpower/power/turnOnScreen", None)
+            .await
     }
 
-    pub async fn get_power_state(&mut self) -> Result<Value> {
+    pub async fn get_power_state(&self) -> Result<Value> {
         self.send_request(
             "ssap://com.webos.service.tvpower/power/getPowerState",
             None,
@@ -289,41 +1341,72 @@ impl LgtvRemote {
     // Audio
     // ──────────────────────────────────────────────
 
-    pub async fn mute(&mut self, muted: bool) -> Result<Value> {
-        self.send_request("ssap://audio/setMute", Some(json!({"mute": muted})), None)
-            .await
+    /// Mute or unmute, returning whether the TV reports being muted after.
+    pub async fn mute(&self, muted: bool) -> Result<bool> {
+        self.send_action("ssap://audio/setMute", Some(json!({"mute": muted})))
+            .await?;
+        self.audio_state().await?.1.ok_or_else(|| {
+            LgtvError::CommandError("The TV didn't report whether it is muted".to_string())
+        })
     }
 
-    pub async fn set_volume(&mut self, level: u32) -> Result<Value> {
-        self.send_request(
-            "ssap://audio/setVolume",
-            Some(json!({"volume": level})),
-            None,
-        )
-        .await
+    /// Set the volume, returning the level the TV reports after, which is
+    /// less than `level` when the TV caps it.
+    pub async fn set_volume(&self, level: u32) -> Result<u32> {
+        self.send_action("ssap://audio/setVolume", Some(json!({"volume": level})))
+            .await?;
+        self.confirmed_volume().await
     }
 
-    pub async fn volume_up(&mut self) -> Result<Value> {
+    /// Turn the volume up a step, returning the new level.
+    pub async fn volume_up(&self) -> Result<u32> {
         self.send_request("ssap://audio/volumeUp", None, Some("volumeup"))
-            .await
+            .await?;
+        self.confirmed_volume().await
     }
 
-    pub async fn volume_down(&mut self) -> Result<Value> {
+    /// Turn the volume down a step, returning the new level.
+    pub async fn volume_down(&self) -> Result<u32> {
         self.send_request("ssap://audio/volumeDown", None, Some("volumedown"))
-            .await
+            .await?;
+        self.confirmed_volume().await
+    }
+
+    async fn confirmed_volume(&self) -> Result<u32> {
+        self.audio_state()
+            .await?
+            .0
+            .ok_or_else(|| LgtvError::CommandError("The TV didn't report its volume".to_string()))
     }
 
-    pub async fn audio_status(&mut self) -> Result<Value> {
+    /// The volume and mute state from `getVolume`, whichever the TV gives.
+    async fn audio_state(&self) -> Result<(Option<u32>, Option<bool>)> {
+        let audio = self.audio_volume().await?;
+        // Newer firmware nests these in volumeStatus
+        let audio = audio.get("volumeStatus").unwrap_or(&audio);
+        let volume = audio
+            .get("volume")
+            .and_then(Value::as_u64)
+            .and_then(|volume| u32::try_from(volume).ok());
+        let muted = audio
+            .get("muted")
+            .or_else(|| audio.get("muteStatus"))
+            .or_else(|| audio.get("mute"))
+            .and_then(Value::as_bool);
+        Ok((volume, muted))
+    }
+
+    pub async fn audio_status(&self) -> Result<Value> {
         self.send_request("ssap://audio/getStatus", None, Some("status"))
             .await
     }
 
-    pub async fn audio_volume(&mut self) -> Result<Value> {
+    pub async fn audio_volume(&self) -> Result<Value> {
         self.send_request("ssap://audio/getVolume", None, Some("volume"))
             .await
     }
 
-    pub async fn get_sound_output(&mut self) -> Result<Value> {
+    pub async fn get_sound_output(&self) -> Result<Value> {
         self.send_request(
             "ssap://com.webos.service.apiadapter/audio/getSoundOutput",
             None,
@@ -332,11 +1415,10 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn set_sound_output(&mut self, output: &str) -> Result<Value> {
-        self.send_request(
+    pub async fn set_sound_output(&self, output: &str) -> Result<()> {
+        self.send_action(
             "ssap://audio/changeSoundOutput",
             Some(json!({"output": output})),
-            None,
         )
         .await
     }
@@ -345,59 +1427,90 @@ impl LgtvRemote {
     // TV Channels
     // ──────────────────────────────────────────────
 
-    pub async fn get_tv_channel(&mut self) -> Result<Value> {
+    pub async fn get_tv_channel(&self) -> Result<Value> {
         self.send_request("ssap://tv/getCurrentChannel", None, None)
             .await
     }
 
-    pub async fn set_tv_channel(&mut self, channel_id: &str) -> Result<Value> {
-        self.send_request(
+    pub async fn set_tv_channel(&self, channel_id: &str) -> Result<()> {
+        self.send_action(
             "ssap://tv/openChannel",
             Some(json!({"channelId": channel_id})),
-            None,
         )
         .await
     }
 
-    pub async fn list_channels(&mut self) -> Result<Value> {
+    /// The ID of the channel `name` refers to: a channel ID, a channel
+    /// number such as "13" or "7-1", or (part of) the channel's name.
+    /// Falls back to `name` itself if the TV won't list its channels.
+    pub async fn find_channel(&self, name: &str) -> Result<String> {
+        let channels = match self.list_channels().await {
+            Ok(channels) => channels,
+            Err(e) => {
+                log::debug!("Could not list channels, using '{}' as is: {}", name, e);
+                return Ok(name.to_string());
+            }
+        };
+        let candidates: Vec<Candidate> = channels
+            .get("channelList")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|channel| {
+                let text = |key: &str| channel.get(key).and_then(Value::as_str);
+                Some(Candidate::channel(
+                    text("channelId")?.to_string(),
+                    text("channelNumber").unwrap_or_default(),
+                    text("channelName").unwrap_or_default(),
+                ))
+            })
+            .collect();
+        resolve_name("channel", name, &candidates)
+    }
+
+    pub async fn list_channels(&self) -> Result<Value> {
         self.send_request("ssap://tv/getChannelList", None, Some("channels"))
             .await
     }
 
-    pub async fn input_channel_up(&mut self) -> Result<Value> {
-        self.send_request("ssap://tv/channelUp", None, None).await
+    /// The programme schedule of a channel (the current one if `None`), as
+    /// far ahead as the TV has it, in `programList`.
+    pub async fn channel_program_info(&self, channel_id: Option<&str>) -> Result<Value> {
+        let payload = channel_id.map(|id| json!({"channelId": id}));
+        self.send_request("ssap://tv/getChannelProgramInfo", payload, None)
+            .await
+    }
+
+    pub async fn input_channel_up(&self) -> Result<()> {
+        self.send_action("ssap://tv/channelUp", None).await
     }
 
-    pub async fn input_channel_down(&mut self) -> Result<Value> {
-        self.send_request("ssap://tv/channelDown", None, None).await
+    pub async fn input_channel_down(&self) -> Result<()> {
+        self.send_action("ssap://tv/channelDown", None).await
     }
 
     // ──────────────────────────────────────────────
     // Media Controls
     // ──────────────────────────────────────────────
 
-    pub async fn input_media_play(&mut self) -> Result<Value> {
-        self.send_request("ssap://media.controls/play", None, None)
-            .await
+    pub async fn input_media_play(&self) -> Result<()> {
+        self.send_action("ssap://media.controls/play", None).await
     }
 
-    pub async fn input_media_pause(&mut self) -> Result<Value> {
-        self.send_request("ssap://media.controls/pause", None, None)
-            .await
+    pub async fn input_media_pause(&self) -> Result<()> {
+        self.send_action("ssap://media.controls/pause", None).await
     }
 
-    pub async fn input_media_stop(&mut self) -> Result<Value> {
-        self.send_request("ssap://media.controls/stop", None, None)
-            .await
+    pub async fn input_media_stop(&self) -> Result<()> {
+        self.send_action("ssap://media.controls/stop", None).await
     }
 
-    pub async fn input_media_rewind(&mut self) -> Result<Value> {
-        self.send_request("ssap://media.controls/rewind", None, None)
-            .await
+    pub async fn input_media_rewind(&self) -> Result<()> {
+        self.send_action("ssap://media.controls/rewind", None).await
     }
 
-    pub async fn input_media_fast_forward(&mut self) -> Result<Value> {
-        self.send_request("ssap://media.controls/fastForward", None, None)
+    pub async fn input_media_fast_forward(&self) -> Result<()> {
+        self.send_action("ssap://media.controls/fastForward", None)
             .await
     }
 
@@ -405,25 +1518,46 @@ impl LgtvRemote {
     // Input switching
     // ──────────────────────────────────────────────
 
-    pub async fn list_inputs(&mut self) -> Result<Value> {
+    pub async fn list_inputs(&self) -> Result<Value> {
         self.send_request("ssap://tv/getExternalInputList", None, None)
             .await
     }
 
-    pub async fn set_input(&mut self, input_id: &str) -> Result<Value> {
-        self.send_request(
-            "ssap://tv/switchInput",
-            Some(json!({"inputId": input_id})),
-            None,
-        )
-        .await
+    pub async fn set_input(&self, input_id: &str) -> Result<()> {
+        self.send_action("ssap://tv/switchInput", Some(json!({"inputId": input_id})))
+            .await
     }
 
-    pub async fn set_device_info(&mut self, id: &str, icon: &str, label: &str) -> Result<Value> {
-        self.send_request(
+    /// The ID of the input `name` refers to: an ID such as `HDMI_1`, a
+    /// looser spelling of one ("hdmi1", "HDMI 2"), or the label given to
+    /// the input on the TV ("PlayStation"). Falls back to `name` itself if
+    /// the TV won't list its inputs.
+    pub async fn find_input(&self, name: &str) -> Result<String> {
+        let inputs = match self.list_inputs().await {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                log::debug!("Could not list inputs, using '{}' as is: {}", name, e);
+                return Ok(name.to_string());
+            }
+        };
+        let candidates: Vec<Candidate> = inputs
+            .get("devices")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|device| {
+                let id = device.get("id")?.as_str()?.to_string();
+                let label = device.get("label").and_then(Value::as_str);
+                Some(Candidate::new(id, label))
+            })
+            .collect();
+        resolve_name("input", name, &candidates)
+    }
+
+    pub async fn set_device_info(&self, id: &str, icon: &str, label: &str) -> Result<()> {
+        self.send_action(
             "luna://com.webos.service.eim/setDeviceInfo",
             Some(json!({"id": id, "icon": icon, "label": label})),
-            None,
         )
         .await
     }
@@ -432,12 +1566,12 @@ impl LgtvRemote {
     // Applications
     // ──────────────────────────────────────────────
 
-    pub async fn list_apps(&mut self) -> Result<Value> {
+    pub async fn list_apps(&self) -> Result<Value> {
         self.send_request("ssap://com.webos.applicationManager/listApps", None, None)
             .await
     }
 
-    pub async fn list_launch_points(&mut self) -> Result<Value> {
+    pub async fn list_launch_points(&self) -> Result<Value> {
         self.send_request(
             "ssap://com.webos.applicationManager/listLaunchPoints",
             None,
@@ -446,7 +1580,32 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn start_app(&mut self, app_id: &str) -> Result<Value> {
+    /// The ID of the app `name` refers to: an app ID, or (part of) the
+    /// app's title as shown in the launcher, such as "netflix" or "prime".
+    /// Falls back to `name` itself if the TV won't list its apps.
+    pub async fn find_app(&self, name: &str) -> Result<String> {
+        let launch_points = match self.list_launch_points().await {
+            Ok(launch_points) => launch_points,
+            Err(e) => {
+                log::debug!("Could not list apps, using '{}' as is: {}", name, e);
+                return Ok(name.to_string());
+            }
+        };
+        let candidates: Vec<Candidate> = launch_points
+            .get("launchPoints")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|app| {
+                let id = app.get("id")?.as_str()?.to_string();
+                let title = app.get("title").and_then(Value::as_str);
+                Some(Candidate::new(id, title))
+            })
+            .collect();
+        resolve_name("app", name, &candidates)
+    }
+
+    pub async fn start_app(&self, app_id: &str) -> Result<Value> {
         self.send_request(
             "ssap://system.launcher/launch",
             Some(json!({"id": app_id})),
@@ -455,16 +1614,12 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn close_app(&mut self, app_id: &str) -> Result<Value> {
-        self.send_request(
-            "ssap://system.launcher/close",
-            Some(json!({"id": app_id})),
-            None,
-        )
-        .await
+    pub async fn close_app(&self, app_id: &str) -> Result<()> {
+        self.send_action("ssap://system.launcher/close", Some(json!({"id": app_id})))
+            .await
     }
 
-    pub async fn open_app_with_payload(&mut self, payload: Value) -> Result<Value> {
+    pub async fn open_app_with_payload(&self, payload: Value) -> Result<Value> {
         self.send_request(
             "ssap://com.webos.applicationManager/launch",
             Some(payload),
@@ -473,7 +1628,7 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn get_foreground_app_info(&mut self) -> Result<Value> {
+    pub async fn get_foreground_app_info(&self) -> Result<Value> {
         self.send_request(
             "ssap://com.webos.applicationManager/getForegroundAppInfo",
             None,
@@ -482,11 +1637,44 @@ impl LgtvRemote {
         .await
     }
 
+    /// Power state, volume, input, app and channel in one go. Queries the
+    /// TV doesn't answer leave their fields empty rather than failing.
+    pub async fn status(&self) -> Result<TvStatus> {
+        let text = |value: &Value, key: &str| value.get(key)?.as_str().map(str::to_string);
+        let mut status = TvStatus::default();
+
+        if let Ok(power) = self.get_power_state().await {
+            status.power = text(&power, "state");
+        }
+        if let Ok((volume, muted)) = self.audio_state().await {
+            status.volume = volume.map(u64::from);
+            status.muted = muted;
+        }
+        if let Ok(app) = self.get_foreground_app_info().await {
+            status.app = text(&app, "appId").filter(|id| !id.is_empty());
+        }
+        status.input = status.app.as_deref().and_then(|app| {
+            if app == "com.webos.app.livetv" {
+                Some("LIVE_TV".to_string())
+            } else {
+                app.strip_prefix("com.webos.app.hdmi")
+                    .map(|n| format!("HDMI_{}", n))
+            }
+        });
+        if status.input.as_deref() == Some("LIVE_TV") {
+            if let Ok(channel) = self.get_tv_channel().await {
+                status.channel_number = text(&channel, "channelNumber");
+                status.channel_name = text(&channel, "channelName");
+            }
+        }
+        Ok(status)
+    }
+
     // ──────────────────────────────────────────────
     // Browser & YouTube
     // ──────────────────────────────────────────────
 
-    pub async fn open_browser_at(&mut self, url: &str) -> Result<Value> {
+    pub async fn open_browser_at(&self, url: &str) -> Result<Value> {
         self.send_request(
             "ssap://system.launcher/open",
             Some(json!({"target": url})),
@@ -495,7 +1683,7 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn open_youtube_id(&mut self, video_id: &str) -> Result<Value> {
+    pub async fn open_youtube_id(&self, video_id: &str) -> Result<Value> {
         self.send_request(
             "ssap://system.launcher/launch",
             Some(json!({"id": "youtube.leanback.v4", "contentId": video_id})),
@@ -504,7 +1692,7 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn open_youtube_url(&mut self, url: &str) -> Result<Value> {
+    pub async fn open_youtube_url(&self, url: &str) -> Result<Value> {
         self.send_request(
             "ssap://system.launcher/launch",
             Some(json!({
@@ -516,7 +1704,7 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn open_youtube_legacy_id(&mut self, video_id: &str) -> Result<Value> {
+    pub async fn open_youtube_legacy_id(&self, video_id: &str) -> Result<Value> {
         self.send_request(
             "ssap://system.launcher/launch",
             Some(json!({"id": "com.webos.app.youtube", "contentId": video_id})),
@@ -525,7 +1713,7 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn open_youtube_legacy_url(&mut self, url: &str) -> Result<Value> {
+    pub async fn open_youtube_legacy_url(&self, url: &str) -> Result<Value> {
         self.send_request(
             "ssap://system.launcher/launch",
             Some(json!({
@@ -541,107 +1729,68 @@ impl LgtvRemote {
     // Notifications
     // ──────────────────────────────────────────────
 
-    pub async fn notification(&mut self, message: &str) -> Result<Value> {
+    /// Show a toast, with `icon` next to the message if given.
+    pub async fn notification(
+        &self,
+        message: &str,
+        icon: Option<&NotificationIcon>,
+    ) -> Result<Value> {
+        let mut payload = json!({"message": message});
+        if let Some(icon) = icon {
+            let (data, extension) = self.icon_data(icon).await?;
+            payload["iconData"] = json!(base64::engine::general_purpose::STANDARD.encode(data));
+            payload["iconExtension"] = json!(extension);
+        }
         self.send_request(
             "ssap://system.notifications/createToast",
-            Some(json!({"message": message})),
+            Some(payload),
             None,
         )
         .await
     }
 
-    pub async fn notification_with_icon(&mut self, message: &str, icon_url: &str) -> Result<Value> {
-        let icon_data = Self::http_get_bytes(icon_url).await?;
-
-        let icon_b64 = base64::engine::general_purpose::STANDARD.encode(&icon_data);
-        let extension = icon_url.rsplit('.').next().unwrap_or("png");
-
-        self.send_request(
-            "ssap://system.notifications/createToast",
-            Some(json!({
-                "message": message,
-                "iconData": icon_b64,
-                "iconExtension": extension
-            })),
-            None,
-        )
-        .await
+    pub async fn notification_with_icon(&self, message: &str, icon_url: &str) -> Result<Value> {
+        self.notification(message, Some(&NotificationIcon::Url(icon_url.to_string())))
+            .await
     }
 
-    async fn http_get_bytes(url: &str) -> Result<Vec<u8>> {
-        let url_body = url
-            .strip_prefix("http://")
-            .or_else(|| url.strip_prefix("https://"))
-            .ok_or_else(|| LgtvError::CommandError("Invalid URL scheme".to_string()))?;
-
-        let (host_port, path) = match url_body.find('/') {
-            Some(i) => (&url_body[..i], &url_body[i..]),
-            None => (url_body, "/"),
-        };
-
-        let host = host_port.split(':').next().unwrap_or(host_port);
-        let port: u16 = if url.starts_with("https://") {
-            443
-        } else {
-            host_port
-                .split(':')
-                .nth(1)
-                .and_then(|p| p.parse().ok())
-                .unwrap_or(80)
+    /// The image bytes of `icon` and their file extension.
+    async fn icon_data(&self, icon: &NotificationIcon) -> Result<(Vec<u8>, String)> {
+        let extension = |name: &str| {
+            name.rsplit_once('.')
+                .map(|(_, ext)| ext.to_lowercase())
+                .filter(|ext| !ext.is_empty() && !ext.contains('/'))
+                .unwrap_or_else(|| "png".to_string())
         };
+        match icon {
+            NotificationIcon::File(path) => {
+                let data = std::fs::read(path).map_err(|e| {
+                    LgtvError::CommandError(format!("Could not read {}: {}", path.display(), e))
+                })?;
+                Ok((data, extension(&path.to_string_lossy())))
+            }
+            NotificationIcon::Url(url) => Ok((http::get(url).await?, extension(url))),
+            NotificationIcon::App(name) => {
+                let app_id = self.find_app(name).await?;
+                let launch_points = self.list_launch_points().await?;
+                let url = launch_points
+                    .get("launchPoints")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .find(|app| app.get("id").and_then(Value::as_str) == Some(&app_id))
+                    .and_then(|app| app.get("icon"))
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| {
+                        LgtvError::CommandError(format!("The TV has no icon for '{}'", app_id))
+                    })?
+                    .to_string();
+                Ok((http::get(&url).await?, extension(&url)))
+            }
+        }
+    }
 
-        let request = format!(
-            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
-            path, host
-        );
-
-        if url.starts_with("https://") {
-            let tcp_stream = tokio::net::TcpStream::connect((host, port))
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("Failed to connect: {}", e)))?;
-            let connector = native_tls::TlsConnector::new()
-                .map_err(|e| LgtvError::CommandError(format!("TLS error: {}", e)))?;
-            let connector = tokio_native_tls::TlsConnector::from(connector);
-            let mut stream = connector
-                .connect(host, tcp_stream)
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("TLS connect error: {}", e)))?;
-            stream
-                .write_all(request.as_bytes())
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("Failed to send request: {}", e)))?;
-            let mut response = Vec::new();
-            stream
-                .read_to_end(&mut response)
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("Failed to read response: {}", e)))?;
-            Self::extract_http_body(response)
-        } else {
-            let mut stream = tokio::net::TcpStream::connect((host, port))
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("Failed to connect: {}", e)))?;
-            stream
-                .write_all(request.as_bytes())
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("Failed to send request: {}", e)))?;
-            let mut response = Vec::new();
-            stream
-                .read_to_end(&mut response)
-                .await
-                .map_err(|e| LgtvError::CommandError(format!("Failed to read response: {}", e)))?;
-            Self::extract_http_body(response)
-        }
-    }
-
-    fn extract_http_body(response: Vec<u8>) -> Result<Vec<u8>> {
-        let header_end = response
-            .windows(4)
-            .position(|w| w == b"\r\n\r\n")
-            .ok_or_else(|| LgtvError::CommandError("Invalid HTTP response".to_string()))?;
-        Ok(response[header_end + 4..].to_vec())
-    }
-
-    pub async fn create_alert(&mut self, message: &str, buttons: Value) -> Result<Value> {
+    pub async fn create_alert(&self, message: &str, buttons: Value) -> Result<Value> {
         self.send_request(
             "ssap://system.notifications/createAlert",
             Some(json!({
@@ -653,11 +1802,10 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn close_alert(&mut self, alert_id: &str) -> Result<Value> {
-        self.send_request(
+    pub async fn close_alert(&self, alert_id: &str) -> Result<()> {
+        self.send_action(
             "ssap://system.notifications/closeAlert",
             Some(json!({"alertId": alert_id})),
-            None,
         )
         .await
     }
@@ -666,13 +1814,13 @@ impl LgtvRemote {
     // 3D Display
     // ──────────────────────────────────────────────
 
-    pub async fn input_3d_on(&mut self) -> Result<Value> {
-        self.send_request("ssap://com.webos.service.tv.display/set3DOn", None, None)
+    pub async fn input_3d_on(&self) -> Result<()> {
+        self.send_action("ssap://com.webos.service.tv.display/set3DOn", None)
             .await
     }
 
-    pub async fn input_3d_off(&mut self) -> Result<Value> {
-        self.send_request("ssap://com.webos.service.tv.display/set3DOff", None, None)
+    pub async fn input_3d_off(&self) -> Result<()> {
+        self.send_action("ssap://com.webos.service.tv.display/set3DOff", None)
             .await
     }
 
@@ -680,26 +1828,45 @@ impl LgtvRemote {
     // Picture Settings
     // ──────────────────────────────────────────────
 
-    pub async fn get_picture_settings(&mut self) -> Result<Value> {
+    pub async fn get_picture_settings(&self) -> Result<Value> {
+        self.picture_settings(&[
+            "contrast",
+            "backlight",
+            "brightness",
+            "color",
+            "pictureMode",
+        ])
+        .await
+    }
+
+    /// Read the picture settings named in `keys`.
+    pub async fn picture_settings(&self, keys: &[&str]) -> Result<Value> {
         self.send_request(
             "ssap://settings/getSystemSettings",
-            Some(json!({
-                "category": "picture",
-                "keys": ["contrast", "backlight", "brightness", "color", "pictureMode"]
-            })),
+            Some(json!({"category": "picture", "keys": keys})),
             None,
         )
         .await
     }
 
-    pub async fn set_picture_mode(&mut self, mode: &str) -> Result<Value> {
-        self.send_request(
+    /// Change several picture settings in one request, so the TV applies
+    /// all of them or none. See [`crate::settings::parse_picture_settings`]
+    /// for checking them first.
+    pub async fn set_picture_settings(&self, settings: Map<String, Value>) -> Result<()> {
+        self.send_action(
+            "ssap://settings/setSystemSettings",
+            Some(json!({"category": "picture", "settings": settings})),
+        )
+        .await
+    }
+
+    pub async fn set_picture_mode(&self, mode: &str) -> Result<()> {
+        self.send_action(
             "ssap://settings/setSystemSettings",
             Some(json!({
                 "category": "picture",
                 "settings": {"pictureMode": mode}
             })),
-            None,
         )
         .await
     }
@@ -708,7 +1875,7 @@ impl LgtvRemote {
     // System Info
     // ──────────────────────────────────────────────
 
-    pub async fn sw_info(&mut self) -> Result<Value> {
+    pub async fn sw_info(&self) -> Result<Value> {
         self.send_request(
             "ssap://com.webos.service.update/getCurrentSWInformation",
             None,
@@ -717,12 +1884,12 @@ impl LgtvRemote {
         .await
     }
 
-    pub async fn get_system_info(&mut self) -> Result<Value> {
+    pub async fn get_system_info(&self) -> Result<Value> {
         self.send_request("ssap://system/getSystemInfo", None, None)
             .await
     }
 
-    pub async fn list_services(&mut self) -> Result<Value> {
+    pub async fn list_services(&self) -> Result<Value> {
         self.send_request("ssap://api/getServiceList", None, None)
             .await
     }
@@ -731,22 +1898,180 @@ impl LgtvRemote {
     // IME
     // ──────────────────────────────────────────────
 
-    pub async fn send_enter_key(&mut self) -> Result<Value> {
-        self.send_request("ssap://com.webos.service.ime/sendEnterKey", None, None)
+    pub async fn send_enter_key(&self) -> Result<()> {
+        self.send_action("ssap://com.webos.service.ime/sendEnterKey", None)
             .await
     }
 
+    pub async fn insert_text(&self, text: &str, replace: bool) -> Result<()> {
+        self.send_action(
+            "ssap://com.webos.service.ime/insertText",
+            Some(json!({"text": text, "replace": replace})),
+        )
+        .await
+    }
+
+    pub async fn delete_characters(&self, count: u32) -> Result<()> {
+        self.send_action(
+            "ssap://com.webos.service.ime/deleteCharacters",
+            Some(json!({"count": count})),
+        )
+        .await
+    }
+
+    /// Subscribe to the remote keyboard service; updates arrive whenever the
+    /// focused input field changes.
+    pub async fn register_remote_keyboard(&self) -> Result<mpsc::Receiver<Value>> {
+        self.send_command(
+            "subscribe",
+            "ssap://com.webos.service.ime/registerRemoteKeyboard",
+            None,
+            Some("keyboard"),
+        )
+        .await
+    }
+
+    /// Check whether the TV accepts remote keyboard input, i.e. whether
+    /// `insert_text` can be used.
+    pub async fn ime_available(&self) -> Result<bool> {
+        let mut rx = self.register_remote_keyboard().await?;
+        match timeout(Duration::from_secs(2), rx.recv()).await {
+            Ok(Some(response)) => {
+                log::debug!("Remote keyboard: {}", response);
+                Ok(LgtvError::from_response(&response).is_none())
+            }
+            Ok(None) | Err(_) => Ok(false),
+        }
+    }
+
     // ──────────────────────────────────────────────
     // Config serialization
     // ──────────────────────────────────────────────
 
+    pub fn config_entry(&self) -> TvConfigEntry {
+        TvConfigEntry {
+            key: Some(self.client_key.clone()),
+            ip: Some(self.ip.clone()),
+            hostname: self.hostname.clone(),
+            mac: self.mac_address.clone(),
+            ssl: self.ssl,
+            port: self.port,
+            cert_fingerprint: self.cert_fingerprint(),
+            broadcast: self.broadcast_address.clone(),
+            timeout: match self.command_timeout {
+                None => Some(0),
+                Some(DEFAULT_COMMAND_TIMEOUT) => None,
+                Some(limit) => Some(limit.as_secs()),
+            },
+            permissions: (!self.permissions.is_all())
+                .then(|| self.permissions.iter().map(String::from).collect()),
+            ..Default::default()
+        }
+    }
+
     pub fn serialise(&self) -> Value {
-        json!({
-            "name": self.name,
-            "ip": self.ip,
-            "mac": self.mac_address,
-            "key": self.client_key,
-            "hostname": self.hostname
-        })
+        let mut entry = self.config_entry().to_value();
+        entry["name"] = json!(self.name);
+        entry
+    }
+}
+
+/// Something the TV lists (an input, an app, a channel) that can be
+/// picked by its ID or by one of its names.
+struct Candidate {
+    id: String,
+    names: Vec<String>,
+    description: String,
+}
+
+impl Candidate {
+    /// Known by its ID and its label, if it has one.
+    fn new(id: String, label: Option<&str>) -> Self {
+        let label = label.filter(|l| !l.is_empty() && *l != id);
+        Self {
+            description: match label {
+                Some(label) => format!("{} ({})", id, label),
+                None => id.clone(),
+            },
+            names: std::iter::once(id.clone())
+                .chain(label.map(str::to_string))
+                .collect(),
+            id,
+        }
+    }
+
+    /// Known by its number and name rather than its opaque ID.
+    fn channel(id: String, number: &str, name: &str) -> Self {
+        Self {
+            description: format!("{} ({})", number, name),
+            names: vec![number.to_string(), name.to_string()],
+            id,
+        }
+    }
+}
+
+/// Choices listed in errors; channel lists in particular can be long.
+const MAX_LISTED: usize = 20;
+
+/// Lowercase letters and digits only, so "HDMI 2", "hdmi2" and "HDMI_2"
+/// compare equal.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Pick the candidate `query` refers to: one with exactly that ID, else
+/// one with a name that matches once normalized, else the only one with a
+/// name containing the query. Numbers only ever match whole names, so "13"
+/// doesn't pick channel 113. Anything else is an error listing the
+/// choices.
+fn resolve_name(kind: &str, query: &str, candidates: &[Candidate]) -> Result<String> {
+    if let Some(exact) = candidates.iter().find(|c| c.id == query) {
+        return Ok(exact.id.clone());
+    }
+
+    let wanted = normalize(query);
+    let names = |c: &&Candidate| c.names.iter().map(|n| normalize(n)).collect::<Vec<_>>();
+    let mut matches: Vec<&Candidate> = candidates
+        .iter()
+        .filter(|c| names(c).contains(&wanted))
+        .collect();
+    let is_number = query
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '-' || c == '.');
+    if matches.is_empty() && !wanted.is_empty() && !is_number {
+        matches = candidates
+            .iter()
+            .filter(|c| names(c).iter().any(|n| n.contains(&wanted)))
+            .collect();
+    }
+
+    let list = |choices: &[&Candidate]| {
+        let mut list: Vec<&str> = choices
+            .iter()
+            .take(MAX_LISTED)
+            .map(|c| c.description.as_str())
+            .collect::<Vec<_>>();
+        let more = format!("and {} more", choices.len().saturating_sub(MAX_LISTED));
+        if choices.len() > MAX_LISTED {
+            list.push(&more);
+        }
+        list.join(", ")
+    };
+    match matches.as_slice() {
+        [only] => Ok(only.id.clone()),
+        [] => Err(LgtvError::CommandError(format!(
+            "No {} matches '{}'; the TV has: {}",
+            kind,
+            query,
+            list(&candidates.iter().collect::<Vec<_>>())
+        ))),
+        several => Err(LgtvError::CommandError(format!(
+            "'{}' could be any of: {}",
+            query,
+            list(several)
+        ))),
     }
 }
@@ -1,15 +1,31 @@
-use crate::error::Result;
+use crate::error::{LgtvError, Result};
+use crate::{http, mdns, neighbor, tls, trace};
+use futures_util::stream::{self, Stream, StreamExt};
+use futures_util::SinkExt;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use serde_json::{json, Value};
+use socket2::{Domain, Socket, Type};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
 use std::str;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{interval, sleep, timeout, timeout_at, Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::Message;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct TvDevice {
     pub uuid: Option<String>,
     pub tv_name: Option<String>,
     pub address: String,
+    /// Details from the UPnP device description, where the TV serves one.
+    pub friendly_name: Option<String>,
+    pub model_name: Option<String>,
+    pub udn: Option<String>,
+    pub mac: Option<String>,
 }
 
 fn extract_uuid(response: &str) -> Option<String> {
@@ -18,6 +34,84 @@ fn extract_uuid(response: &str) -> Option<String> {
     Some(response[start..end].to_string())
 }
 
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Text of the first `<tag>` element, with the predefined entities decoded.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    let text = xml[start..end]
+        .trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Fill in the device details from the description XML at `location`.
+async fn describe(device: &mut TvDevice, location: &str) {
+    let xml = match timeout(DESCRIPTION_TIMEOUT, http::get(location)).await {
+        Ok(Ok(body)) => String::from_utf8_lossy(&body).into_owned(),
+        Ok(Err(e)) => {
+            log::debug!("Failed to fetch {}: {}", location, e);
+            return;
+        }
+        Err(_) => {
+            log::debug!("Timed out fetching {}", location);
+            return;
+        }
+    };
+
+    device.friendly_name = xml_text(&xml, "friendlyName");
+    device.model_name = xml_text(&xml, "modelName");
+    device.udn = xml_text(&xml, "UDN");
+    device.mac = ["macAddress", "wiredMac", "wifiMac"]
+        .iter()
+        .filter_map(|tag| xml_text(&xml, tag))
+        .find_map(|text| neighbor::normalize_mac(&text));
+}
+
+fn search_request(target: &str, mx: u8, group: &SocketAddr) -> String {
+    format!(
+        "M-SEARCH * HTTP/1.1\r\n\
+         HOST: {}\r\n\
+         MAN: \"ssdp:discover\"\r\n\
+         MX: {}\r\n\
+         ST: {}\r\n\r\n",
+        group, mx, target
+    )
+}
+
+/// Whether a search response is for the webOS second-screen service. A
+/// TV answers `ssdp:all` once per service, so the others are skipped.
+fn is_webos_tv(response: &str) -> bool {
+    ["ST", "NT", "USN"]
+        .iter()
+        .any(|name| header(response, name).is_some_and(|value| value.contains(SECOND_SCREEN_ST)))
+}
+
+/// Look the MAC address up in the neighbor table if the description didn't
+/// have one. The TV just answered us, so it should be in there.
+async fn fill_mac(device: &mut TvDevice) {
+    if device.mac.is_some() {
+        return;
+    }
+    let address = device.address.clone();
+    device.mac = tokio::task::spawn_blocking(move || neighbor::lookup_mac(&address))
+        .await
+        .ok()
+        .flatten();
+}
+
 fn extract_tv_name(response: &str) -> Option<String> {
     let marker = "DLNADeviceName.lge.com:";
     let start = response.find(marker)? + marker.len();
@@ -26,58 +120,556 @@ fn extract_tv_name(response: &str) -> Option<String> {
     Some(rest[..end].trim().to_string())
 }
 
-pub async fn scan_for_tvs() -> Result<Vec<TvDevice>> {
-    let ssdp_request = "M-SEARCH * HTTP/1.1\r\n\
-         HOST: 239.255.255.250:1900\r\n\
-         MAN: \"ssdp:discover\"\r\n\
-         MX: 2\r\n\
-         ST: urn:schemas-upnp-org:device:MediaRenderer:1\r\n\r\n"
-        .to_string();
+/// The SSDP multicast group, and its IPv6 link-local equivalent.
+const SSDP_V4: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+const SSDP_V6: SocketAddr = SocketAddr::new(
+    IpAddr::V6(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xc)),
+    1900,
+);
 
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-    socket.set_read_timeout(Some(Duration::from_secs(10)))?;
+/// The service every webOS TV advertises for remote control.
+const SECOND_SCREEN_ST: &str = "urn:lge-com:service:webos-second-screen:1";
 
-    let multicast_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+/// Ask for the second-screen service directly, and for everything in case
+/// a TV only answers the catch-all search.
+const SEARCH_TARGETS: [&str; 2] = [SECOND_SCREEN_ST, "ssdp:all"];
 
-    let mut addresses = Vec::new();
-    let attempts = 4;
+/// Upper bound for fetching one TV's UPnP description.
+const DESCRIPTION_TIMEOUT: Duration = Duration::from_secs(2);
 
-    for _ in 0..attempts {
-        socket.send_to(ssdp_request.as_bytes(), multicast_addr)?;
+/// Tuning for [`scan_with_options`] and [`scan_stream_with_options`].
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    timeout: Duration,
+    attempts: usize,
+    mx: u8,
+    search_targets: Vec<String>,
+    bind_address: IpAddr,
+}
 
-        let mut buf = [0u8; 4096];
-        match socket.recv_from(&mut buf) {
-            Ok((len, addr)) => {
-                let response = str::from_utf8(&buf[..len]).unwrap_or("");
-
-                if response.contains("LG") {
-                    addresses.push(TvDevice {
-                        uuid: extract_uuid(response),
-                        tv_name: extract_tv_name(response),
-                        address: addr.ip().to_string(),
-                    });
-                } else {
-                    log::debug!("Unknown device: {}, {}", response, addr);
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(3),
+            // UDP is lossy, so the search is repeated a few times up front
+            attempts: 3,
+            mx: 2,
+            search_targets: SEARCH_TARGETS.iter().map(|t| t.to_string()).collect(),
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long to collect responses after the first search goes out.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many times each M-SEARCH is sent.
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Maximum seconds a device may wait before answering.
+    pub fn mx(mut self, mx: u8) -> Self {
+        self.mx = mx;
+        self
+    }
+
+    /// SSDP search targets (`ST`) to ask for.
+    pub fn search_targets<I, S>(mut self, targets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.search_targets = targets.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Local address to send from, picking the interface on multi-homed
+    /// hosts.
+    pub fn bind_address(mut self, address: IpAddr) -> Self {
+        self.bind_address = address;
+        self
+    }
+}
+
+/// Collect search responses from `socket` until `deadline`.
+async fn receive_responses(
+    socket: UdpSocket,
+    deadline: Instant,
+    found: mpsc::Sender<(TvDevice, Option<String>)>,
+) {
+    let mut buf = [0u8; 4096];
+    loop {
+        let (len, addr) = match timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            Ok(Ok(received)) => received,
+            Ok(Err(e)) => {
+                log::debug!("Error receiving response: {}", e);
+                continue;
+            }
+            Err(_) => break,
+        };
+
+        let response = str::from_utf8(&buf[..len]).unwrap_or("");
+        if !is_webos_tv(response) {
+            log::debug!("Unknown device: {}, {}", response, addr);
+            continue;
+        }
+
+        let device = TvDevice {
+            uuid: extract_uuid(response),
+            tv_name: extract_tv_name(response),
+            address: addr.ip().to_string(),
+            ..Default::default()
+        };
+        let location = header(response, "LOCATION").map(String::from);
+        if found.send((device, location)).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// A UDP socket bound to `address` whose multicast traffic leaves through
+/// that address's interface, rather than wherever the default route points.
+pub(crate) fn multicast_socket(address: IpAddr) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address((address, 0).into()), Type::DGRAM, None)?;
+    socket.bind(&SocketAddr::new(address, 0).into())?;
+    if let IpAddr::V4(ip) = address {
+        if !ip.is_unspecified() {
+            socket.set_multicast_if_v4(&ip)?;
+        }
+    }
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// The IPv4 address of a network interface such as `eth0`, for
+/// [`ScanOptions::bind_address`].
+#[cfg(unix)]
+pub fn interface_address(name: &str) -> Result<Ipv4Addr> {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs allocates the list, which is only read before
+    // being released with freeifaddrs
+    unsafe {
+        if libc::getifaddrs(&mut addrs) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut found = None;
+        let mut cursor = addrs;
+        while !cursor.is_null() {
+            let entry = &*cursor;
+            cursor = entry.ifa_next;
+            if entry.ifa_addr.is_null()
+                || (*entry.ifa_addr).sa_family as i32 != libc::AF_INET
+                || std::ffi::CStr::from_ptr(entry.ifa_name).to_bytes() != name.as_bytes()
+            {
+                continue;
+            }
+            let sin = &*(entry.ifa_addr as *const libc::sockaddr_in);
+            found = Some(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)));
+            break;
+        }
+        libc::freeifaddrs(addrs);
+        found
+            .ok_or_else(|| LgtvError::ConfigError(format!("No IPv4 address on interface {}", name)))
+    }
+}
+
+/// Interface names can't be looked up here; pass an address instead.
+#[cfg(not(unix))]
+pub fn interface_address(name: &str) -> Result<Ipv4Addr> {
+    Err(LgtvError::ConfigError(format!(
+        "Unknown interface {}, use its IP address instead",
+        name
+    )))
+}
+
+/// Discover TVs over SSDP and mDNS, yielding each one as soon as it
+/// answers. The stream ends once the scan duration has passed.
+pub async fn scan_stream() -> Result<impl Stream<Item = TvDevice>> {
+    scan_stream_with_options(&ScanOptions::default()).await
+}
+
+pub async fn scan_stream_with_options(
+    options: &ScanOptions,
+) -> Result<impl Stream<Item = TvDevice>> {
+    // IPv4 always, IPv6 as well unless a specific IPv4 address was asked for
+    let mut sockets = Vec::new();
+    match options.bind_address {
+        IpAddr::V4(ip) => {
+            sockets.push((multicast_socket(options.bind_address)?, SSDP_V4));
+            if ip.is_unspecified() {
+                match multicast_socket(IpAddr::V6(Ipv6Addr::UNSPECIFIED)) {
+                    Ok(socket) => sockets.push((socket, SSDP_V6)),
+                    Err(e) => log::debug!("No IPv6 discovery: {}", e),
                 }
             }
-            Err(e) => {
-                log::debug!("Error receiving response: {}", e);
+        }
+        IpAddr::V6(_) => sockets.push((multicast_socket(options.bind_address)?, SSDP_V6)),
+    }
+
+    let deadline = Instant::now() + options.timeout;
+    for attempt in 0..options.attempts {
+        if attempt > 0 {
+            sleep(Duration::from_millis(100)).await;
+        }
+        for (socket, group) in &sockets {
+            for target in &options.search_targets {
+                let request = search_request(target, options.mx, group);
+                match socket.send_to(request.as_bytes(), group).await {
+                    Ok(_) => {}
+                    // IPv6 is a bonus, e.g. on IPv4-only networks it has no
+                    // route for the group
+                    Err(e) if sockets.len() > 1 && group.is_ipv6() => {
+                        log::debug!("Failed to send search to {}: {}", group, e)
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+
+    // Both discovery paths report candidates together with the location of
+    // their UPnP description, if any
+    let (candidate_tx, mut candidate_rx) = mpsc::channel::<(TvDevice, Option<String>)>(16);
+
+    for (socket, _) in sockets {
+        tokio::spawn(receive_responses(socket, deadline, candidate_tx.clone()));
+    }
+
+    let (mdns_tx, mut mdns_rx) = mpsc::channel::<(IpAddr, String)>(16);
+    let bind_address = options.bind_address;
+    tokio::spawn(async move {
+        if let Err(e) = mdns::browse(bind_address, deadline, mdns_tx).await {
+            log::debug!("mDNS discovery failed: {}", e);
+        }
+    });
+    tokio::spawn(async move {
+        while let Some((address, name)) = mdns_rx.recv().await {
+            let device = TvDevice {
+                tv_name: Some(name),
+                address: address.to_string(),
+                ..Default::default()
+            };
+            if candidate_tx.send((device, None)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let (tx, rx) = mpsc::channel::<TvDevice>(16);
+    tokio::spawn(async move {
+        // Every search gets its own answer, keep the first per address
+        let mut seen_addresses = HashSet::new();
+        while let Some((mut device, location)) = candidate_rx.recv().await {
+            if !seen_addresses.insert(device.address.clone()) {
+                continue;
             }
+            let tx = tx.clone();
+            // Fetch descriptions alongside receiving, so one slow TV
+            // doesn't hold back the others
+            tokio::spawn(async move {
+                if let Some(location) = location {
+                    describe(&mut device, &location).await;
+                }
+                fill_mac(&mut device).await;
+                let _ = tx.send(device).await;
+            });
         }
+    });
+
+    Ok(stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|device| (device, rx))
+    }))
+}
+
+pub async fn scan_for_tvs() -> Result<Vec<TvDevice>> {
+    scan_with_options(&ScanOptions::default()).await
+}
+
+pub async fn scan_with_options(options: &ScanOptions) -> Result<Vec<TvDevice>> {
+    Ok(scan_stream_with_options(options).await?.collect().await)
+}
 
-        sleep(Duration::from_secs(2)).await;
+/// How long to wait for a TCP connection while sweeping a subnet.
+const SWEEP_CONNECT_TIMEOUT: Duration = Duration::from_millis(400);
+
+/// Hosts probed at the same time while sweeping.
+const SWEEP_CONCURRENCY: usize = 64;
+
+/// Probe every address in the local /24 for an SSAP endpoint on port 3000
+/// or 3001. Much slower than multicast discovery, but works on networks
+/// that filter multicast entirely.
+pub async fn sweep_subnet() -> Result<Vec<TvDevice>> {
+    let local = local_ipv4()?;
+    let [a, b, c, _] = local.octets();
+    log::debug!("Sweeping {}.{}.{}.0/24", a, b, c);
+
+    let permits = Arc::new(Semaphore::new(SWEEP_CONCURRENCY));
+    let mut probes = Vec::new();
+    for host in 1..=254u8 {
+        let ip = Ipv4Addr::new(a, b, c, host);
+        if ip == local {
+            continue;
+        }
+        let permits = permits.clone();
+        probes.push(tokio::spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            probe_ssap(ip).await
+        }));
     }
 
-    // De-duplicate by address
-    let mut unique_addresses = Vec::new();
-    let mut seen_addresses = std::collections::HashSet::new();
+    let mut devices = Vec::new();
+    for probe in probes {
+        if let Ok(Some(mut device)) = probe.await {
+            fill_mac(&mut device).await;
+            devices.push(device);
+        }
+    }
+    Ok(devices)
+}
 
-    for device in addresses {
-        if !seen_addresses.contains(&device.address) {
-            seen_addresses.insert(device.address.clone());
-            unique_addresses.push(device);
+/// The address this host uses towards the LAN, found by routing a UDP
+/// socket at the SSDP group (nothing is sent).
+fn local_ipv4() -> Result<Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect("239.255.255.250:1900")?;
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Ok(ip),
+        other => Err(LgtvError::ConnectionError(format!(
+            "No IPv4 address to sweep from (got {})",
+            other
+        ))),
+    }
+}
+
+/// Whether `ip` answers an SSAP `hello` on either port. The hello
+/// doesn't register anything, so no prompt appears on the TV.
+async fn probe_ssap(ip: Ipv4Addr) -> Option<TvDevice> {
+    for (port, url) in [
+        (3000, tls::ws_url(&ip.to_string(), 3000, false)),
+        (3001, tls::ws_url(&ip.to_string(), 3001, true)),
+    ] {
+        let open = timeout(SWEEP_CONNECT_TIMEOUT, TcpStream::connect((ip, port)))
+            .await
+            .is_ok_and(|connected| connected.is_ok());
+        if !open {
+            continue;
+        }
+
+        let hello = timeout(DESCRIPTION_TIMEOUT, async {
+            let (mut ws, _) = tls::connect(&url, &Default::default()).await.ok()?;
+            let request = json!({"id": "probe", "type": "hello", "payload": {}});
+            let request = Message::Text(request.to_string());
+            trace::sent(&url, &request);
+            ws.send(request).await.ok()?;
+            while let Some(Ok(message)) = ws.next().await {
+                trace::received(&url, &message);
+                if let Message::Text(text) = message {
+                    let _ = ws.close(None).await;
+                    return serde_json::from_str::<Value>(&text).ok();
+                }
+            }
+            None
+        })
+        .await
+        .ok()
+        .flatten();
+
+        if let Some(hello) = hello.filter(|h| h.get("type").is_some()) {
+            log::debug!("SSAP endpoint at {}: {}", url, hello);
+            let payload = hello.get("payload");
+            return Some(TvDevice {
+                address: ip.to_string(),
+                model_name: payload
+                    .and_then(|p| p.get("modelName"))
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                ..Default::default()
+            });
         }
     }
+    None
+}
+
+/// How long [`load_cache`] treats saved scan results as current.
+pub const CACHE_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Serialize, Deserialize)]
+struct ScanCache {
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+    devices: Vec<TvDevice>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Remember the result of a successful scan.
+pub fn save_cache(path: &Path, devices: &[TvDevice]) -> Result<()> {
+    let cache = ScanCache {
+        timestamp: unix_now(),
+        devices: devices.to_vec(),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&cache)?)?;
+    Ok(())
+}
+
+/// The devices saved by [`save_cache`], unless they are older than
+/// `max_age` or there are none.
+pub fn load_cache(path: &Path, max_age: Duration) -> Option<Vec<TvDevice>> {
+    let cache: ScanCache = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    let age = unix_now().saturating_sub(cache.timestamp);
+    if age > max_age.as_secs() || cache.devices.is_empty() {
+        return None;
+    }
+    Some(cache.devices)
+}
+
+/// A change seen by [`watch`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ScanEvent {
+    /// A TV announced itself or answered the initial search.
+    Appeared(TvDevice),
+    /// A TV said goodbye, or its announcement expired without renewal.
+    Disappeared(TvDevice),
+}
+
+/// How often expired announcements are checked for while watching.
+const WATCH_EXPIRY_CHECK: Duration = Duration::from_secs(5);
+
+/// Used when an announcement carries no usable `CACHE-CONTROL: max-age`.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(1800);
+
+fn max_age(response: &str) -> Duration {
+    header(response, "CACHE-CONTROL")
+        .and_then(|value| {
+            value
+                .split(',')
+                .find_map(|p| p.trim().strip_prefix("max-age"))
+        })
+        .and_then(|rest| rest.trim_start_matches([' ', '=']).parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_MAX_AGE)
+}
+
+/// Listen for SSDP announcements indefinitely, reporting TVs as they come
+/// and go. Starts with a search so TVs that are already up are reported
+/// straight away.
+pub async fn watch(options: &ScanOptions) -> Result<impl Stream<Item = ScanEvent>> {
+    let group = Ipv4Addr::new(239, 255, 255, 250);
+    let interface = match options.bind_address {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+    };
+
+    // Announcements go to the group's port, which other SSDP listeners on
+    // this host may share
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1900).into())?;
+    socket.join_multicast_v4(&group, &interface)?;
+    if !interface.is_unspecified() {
+        socket.set_multicast_if_v4(&interface)?;
+    }
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket.into())?;
+
+    for target in &options.search_targets {
+        socket
+            .send_to(
+                search_request(target, options.mx, &SSDP_V4).as_bytes(),
+                SSDP_V4,
+            )
+            .await?;
+    }
+
+    let (tx, rx) = mpsc::channel::<ScanEvent>(16);
+    tokio::spawn(async move {
+        // Keyed by UUID, with the address as fallback
+        let mut present: HashMap<String, (TvDevice, Instant)> = HashMap::new();
+        let mut expiry = interval(WATCH_EXPIRY_CHECK);
+        let mut buf = [0u8; 4096];
+
+        loop {
+            let mut events = Vec::new();
+            tokio::select! {
+                received = socket.recv_from(&mut buf) => {
+                    let (len, addr) = match received {
+                        Ok(received) => received,
+                        Err(e) => {
+                            log::debug!("Error receiving announcement: {}", e);
+                            continue;
+                        }
+                    };
+                    let message = str::from_utf8(&buf[..len]).unwrap_or("");
+                    // Searches, including our own, arrive on the group too
+                    if message.starts_with("M-SEARCH") || !is_webos_tv(message) {
+                        continue;
+                    }
+                    log::debug!("Announcement from {}: {}", addr, message);
+
+                    let device = TvDevice {
+                        uuid: extract_uuid(message),
+                        tv_name: extract_tv_name(message),
+                        address: addr.ip().to_string(),
+                        ..Default::default()
+                    };
+                    let key = device.uuid.clone().unwrap_or_else(|| device.address.clone());
+                    let bye = header(message, "NTS")
+                        .is_some_and(|nts| nts.eq_ignore_ascii_case("ssdp:byebye"));
+
+                    if bye {
+                        if let Some((device, _)) = present.remove(&key) {
+                            events.push(ScanEvent::Disappeared(device));
+                        }
+                    } else {
+                        let expires = Instant::now() + max_age(message);
+                        if present.insert(key, (device.clone(), expires)).is_none() {
+                            events.push(ScanEvent::Appeared(device));
+                        }
+                    }
+                }
+                _ = expiry.tick() => {
+                    let now = Instant::now();
+                    let expired: Vec<String> = present
+                        .iter()
+                        .filter(|(_, (_, expires))| *expires <= now)
+                        .map(|(key, _)| key.clone())
+                        .collect();
+                    for key in expired {
+                        if let Some((device, _)) = present.remove(&key) {
+                            events.push(ScanEvent::Disappeared(device));
+                        }
+                    }
+                }
+            }
+
+            for event in events {
+                if tx.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
 
-    Ok(unique_addresses)
+    Ok(stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (event, rx))
+    }))
 }
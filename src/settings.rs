@@ -0,0 +1,196 @@
+//! The picture settings `lgtv picture` knows about, so names and values can
+//! be checked before anything is sent to the TV.
+//!
+//! Which choices a setting accepts differs between models and firmware
+//! versions, so only numbers are checked strictly; for the rest the table
+//! lists the usual values and leaves the final word to the TV.
+
+use crate::error::{LgtvError, Result};
+use serde_json::{Map, Value};
+
+/// What a setting's value looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    /// A whole number in this range, inclusive.
+    Range(i64, i64),
+    /// `W50`..`W0`..`C50`, warm to cool, or a number from -50 to 50 on
+    /// older models.
+    ColorTemperature,
+    /// One of a model-dependent set of words, usually these.
+    Choice(&'static [&'static str]),
+}
+
+/// One setting of the `picture` category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Setting {
+    pub name: &'static str,
+    pub kind: SettingKind,
+}
+
+const LEVELS: &[&str] = &["off", "low", "medium", "high"];
+
+/// The picture settings known to work on recent webOS versions.
+pub const PICTURE_SETTINGS: &[Setting] = &[
+    Setting {
+        name: "backlight",
+        kind: SettingKind::Range(0, 100),
+    },
+    Setting {
+        name: "contrast",
+        kind: SettingKind::Range(0, 100),
+    },
+    Setting {
+        name: "brightness",
+        kind: SettingKind::Range(0, 100),
+    },
+    Setting {
+        name: "color",
+        kind: SettingKind::Range(0, 100),
+    },
+    Setting {
+        name: "tint",
+        kind: SettingKind::Range(-50, 50),
+    },
+    Setting {
+        name: "sharpness",
+        kind: SettingKind::Range(0, 50),
+    },
+    Setting {
+        name: "colorTemperature",
+        kind: SettingKind::ColorTemperature,
+    },
+    Setting {
+        name: "pictureMode",
+        kind: SettingKind::Choice(&[
+            "vivid",
+            "normal",
+            "eco",
+            "cinema",
+            "sports",
+            "game",
+            "filmMaker",
+            "expert1",
+            "expert2",
+        ]),
+    },
+    Setting {
+        name: "energySaving",
+        kind: SettingKind::Choice(&["auto", "off", "min", "med", "max", "screen_off"]),
+    },
+    Setting {
+        name: "gamma",
+        kind: SettingKind::Choice(&["low", "medium", "high1", "high2"]),
+    },
+    Setting {
+        name: "blackLevel",
+        kind: SettingKind::Choice(&["auto", "low", "high"]),
+    },
+    Setting {
+        name: "colorGamut",
+        kind: SettingKind::Choice(&["auto", "extended", "wide", "native"]),
+    },
+    Setting {
+        name: "dynamicContrast",
+        kind: SettingKind::Choice(LEVELS),
+    },
+    Setting {
+        name: "peakBrightness",
+        kind: SettingKind::Choice(LEVELS),
+    },
+    Setting {
+        name: "localDimming",
+        kind: SettingKind::Choice(LEVELS),
+    },
+    Setting {
+        name: "noiseReduction",
+        kind: SettingKind::Choice(&["off", "low", "medium", "high", "auto"]),
+    },
+    Setting {
+        name: "mpegNoiseReduction",
+        kind: SettingKind::Choice(&["off", "low", "medium", "high", "auto"]),
+    },
+    Setting {
+        name: "truMotionMode",
+        kind: SettingKind::Choice(&["off", "smooth", "clear", "cinemaClear", "natural", "user"]),
+    },
+];
+
+impl Setting {
+    /// The setting called `name`, ignoring case.
+    pub fn find(name: &str) -> Option<&'static Setting> {
+        PICTURE_SETTINGS
+            .iter()
+            .find(|setting| setting.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The JSON value to send for `value`, if it's acceptable.
+    pub fn parse(&self, value: &str) -> Result<Value> {
+        let invalid = |expected: String| {
+            LgtvError::CommandError(format!(
+                "'{}' is not a valid {}; expected {}",
+                value, self.name, expected
+            ))
+        };
+        match self.kind {
+            SettingKind::Range(min, max) => match value.parse::<i64>() {
+                Ok(n) if (min..=max).contains(&n) => Ok(Value::from(n)),
+                _ => Err(invalid(format!("a number from {} to {}", min, max))),
+            },
+            SettingKind::ColorTemperature => {
+                let upper = value.to_ascii_uppercase();
+                let warm_or_cool = upper
+                    .strip_prefix(['W', 'C'])
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .is_some_and(|n| n <= 50);
+                if warm_or_cool {
+                    return Ok(Value::from(upper));
+                }
+                match value.parse::<i64>() {
+                    Ok(n) if (-50..=50).contains(&n) => Ok(Value::from(n)),
+                    _ => Err(invalid("W50 to W0 or C0 to C50".to_string())),
+                }
+            }
+            SettingKind::Choice(choices) => Ok(Value::from(
+                choices
+                    .iter()
+                    .find(|choice| choice.eq_ignore_ascii_case(value))
+                    .copied()
+                    .unwrap_or(value),
+            )),
+        }
+    }
+
+    /// What the value may be, for help and error messages.
+    pub fn describe(&self) -> String {
+        match self.kind {
+            SettingKind::Range(min, max) => format!("{} to {}", min, max),
+            SettingKind::ColorTemperature => "W50 to W0, C0 to C50".to_string(),
+            SettingKind::Choice(choices) => choices.join(", "),
+        }
+    }
+}
+
+/// Check `key=value` pairs against [`PICTURE_SETTINGS`], all of them before
+/// any is used, giving the settings to send in one request.
+pub fn parse_picture_settings<S: AsRef<str>>(pairs: &[S]) -> Result<Map<String, Value>> {
+    let mut settings = Map::new();
+    for pair in pairs {
+        let pair = pair.as_ref();
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(LgtvError::CommandError(format!(
+                "Expected key=value, e.g. backlight=40, not '{}'",
+                pair
+            )));
+        };
+        let setting = Setting::find(key.trim()).ok_or_else(|| {
+            let known: Vec<&str> = PICTURE_SETTINGS.iter().map(|s| s.name).collect();
+            LgtvError::CommandError(format!(
+                "Unknown picture setting '{}'; known settings: {}",
+                key,
+                known.join(", ")
+            ))
+        })?;
+        settings.insert(setting.name.to_string(), setting.parse(value.trim())?);
+    }
+    Ok(settings)
+}
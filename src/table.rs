@@ -0,0 +1,139 @@
+//! Aligned tables for list commands at a terminal, which `--filter` and
+//! `--sort` narrow down and reorder.
+
+use lgtv::error::{LgtvError, Result};
+use std::cmp::Ordering;
+use std::io::{self, IsTerminal};
+
+pub struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Row>,
+}
+
+struct Row {
+    /// Which pushed row this is, to find what it was made from
+    index: usize,
+    cells: Vec<String>,
+}
+
+impl Table {
+    pub fn new(header: &[&'static str]) -> Self {
+        Self {
+            header: header.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row; empty cells are shown as "-".
+    pub fn push(&mut self, cells: Vec<String>) {
+        let cells = cells
+            .into_iter()
+            .map(|cell| {
+                if cell.is_empty() {
+                    "-".to_string()
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        let index = self.rows.len();
+        self.rows.push(Row { index, cells });
+    }
+
+    /// Keep only the rows with a cell containing `text`, ignoring case.
+    pub fn filter(&mut self, text: &str) {
+        let text = text.to_lowercase();
+        self.rows.retain(|row| {
+            row.cells
+                .iter()
+                .any(|cell| cell.to_lowercase().contains(&text))
+        });
+    }
+
+    /// Sort by the column headed `column` (ignoring case), with numbers in
+    /// numeric order, so channel 10 comes after channel 9.
+    pub fn sort(&mut self, column: &str) -> Result<()> {
+        let Some(i) = self
+            .header
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(column))
+        else {
+            return Err(LgtvError::CommandError(format!(
+                "Can't sort by '{}'; the columns are {}",
+                column,
+                self.header.join(", ").to_lowercase()
+            )));
+        };
+        self.rows
+            .sort_by(|a, b| natural_cmp(&a.cells[i], &b.cells[i]));
+        Ok(())
+    }
+
+    /// The pushed rows left after filtering, in their current order.
+    pub fn indices(&self) -> Vec<usize> {
+        self.rows.iter().map(|row| row.index).collect()
+    }
+
+    pub fn print(&self) {
+        let mut widths: Vec<usize> = self.header.iter().map(|name| name.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(&row.cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let line = |cells: &[&str]| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+        let header = line(&self.header);
+        if color() {
+            println!("\x1b[1m{}\x1b[0m", header);
+        } else {
+            println!("{}", header);
+        }
+        for row in &self.rows {
+            let cells: Vec<&str> = row.cells.iter().map(String::as_str).collect();
+            println!("{}", line(&cells));
+        }
+    }
+}
+
+/// Whether to style output: only at a terminal, and not when `NO_COLOR`
+/// asks for plain text.
+fn color() -> bool {
+    io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Compare runs of digits as numbers and everything else ignoring case.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (da, db) = (digits(a), digits(b));
+            let (na, nb) = (
+                a[..da].trim_start_matches('0'),
+                b[..db].trim_start_matches('0'),
+            );
+            let ordering = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+            a = &a[da..];
+            b = &b[db..];
+            ordering
+        } else {
+            let ordering = x.to_lowercase().cmp(y.to_lowercase());
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! A stand-in TV for tests, behind the `testing` feature: a websocket
+//! server on localhost that speaks enough SSAP to register a client and
+//! answer requests with canned payloads, and records what it was sent.
+//!
+//! Point a remote at it with [`MockTv::remote`]. Requests without a canned
+//! answer get `{"returnValue": true}`. The pointer socket works too, with
+//! its buttons recorded. Pairing isn't supported, as [`crate::LgtvAuth`]
+//! always uses the TV's standard ports.
+
+use crate::error::Result;
+use crate::remote::{LgtvRemote, LgtvRemoteBuilder};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+/// The client key the mock accepts unless told otherwise.
+pub const CLIENT_KEY: &str = "mock-client-key";
+
+const POINTER_PATH: &str = "/pointer";
+
+/// A request the mock received, in the order they arrived.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// `request` or `subscribe`
+    pub kind: String,
+    pub uri: String,
+    pub payload: Option<Value>,
+}
+
+#[derive(Default)]
+struct State {
+    client_key: String,
+    answers: HashMap<String, Value>,
+    silent: Vec<String>,
+    requests: Vec<MockRequest>,
+    buttons: Vec<String>,
+}
+
+/// A fake TV listening on a free localhost port until dropped.
+pub struct MockTv {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockTv {
+    /// Start listening, accepting [`CLIENT_KEY`].
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            client_key: CLIENT_KEY.to_string(),
+            ..Default::default()
+        }));
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, addr, shared.clone()));
+            }
+        });
+        Ok(Self { addr, state, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// A builder for a remote connecting to the mock with the key it
+    /// accepts, for more options before [`build`](LgtvRemoteBuilder::build).
+    pub fn remote_builder(&self) -> LgtvRemoteBuilder {
+        let key = self.state().client_key.clone();
+        LgtvRemote::builder()
+            .name("mock")
+            .ip(self.addr.ip().to_string())
+            .port(self.addr.port())
+            .client_key(key)
+    }
+
+    /// A remote for the mock, not yet connected.
+    pub fn remote(&self) -> Result<LgtvRemote> {
+        self.remote_builder().build()
+    }
+
+    /// Accept `key` instead, refusing every other one as a TV refuses a
+    /// revoked key.
+    pub fn set_client_key(&self, key: &str) {
+        self.state().client_key = key.to_string();
+    }
+
+    /// Answer requests to `uri` (e.g. `ssap://audio/getVolume`) with
+    /// `payload`, which may include `returnValue: false` to fail them.
+    pub fn respond(&self, uri: &str, payload: Value) {
+        self.state().answers.insert(uri.to_string(), payload);
+    }
+
+    /// Never answer requests to `uri`, to test timeouts.
+    pub fn silence(&self, uri: &str) {
+        self.state().silent.push(uri.to_string());
+    }
+
+    /// The requests received so far.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The buttons pressed over the pointer socket so far, e.g. `HOME`.
+    pub fn buttons(&self) -> Vec<String> {
+        self.state().buttons.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockTv {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(stream: TcpStream, addr: SocketAddr, state: Arc<Mutex<State>>) {
+    let mut path = String::new();
+    // The callback's signature is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let record_path = |request: &Request, response: Response| {
+        path = request.uri().path().to_string();
+        Ok(response)
+    };
+    let Ok(ws) = tokio_tungstenite::accept_hdr_async(stream, record_path).await else {
+        return;
+    };
+    let (mut writer, mut reader) = ws.split();
+    let lock = || state.lock().unwrap_or_else(|e| e.into_inner());
+
+    while let Some(Ok(message)) = reader.next().await {
+        let Message::Text(text) = message else {
+            if let Message::Close(_) = message {
+                break;
+            }
+            continue;
+        };
+        if path == POINTER_PATH {
+            let name = text
+                .lines()
+                .find_map(|line| line.strip_prefix("name:"))
+                .map(str::to_string);
+            if let Some(name) = name {
+                lock().buttons.push(name);
+            }
+            continue;
+        }
+
+        let Ok(message) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        let id = message.get("id").cloned().unwrap_or(Value::Null);
+        let kind = message.get("type").and_then(Value::as_str).unwrap_or("");
+        let payload = message.get("payload").cloned();
+        let reply = match kind {
+            "register" => {
+                let key = payload
+                    .as_ref()
+                    .and_then(|p| p.get("client-key"))
+                    .and_then(Value::as_str);
+                if key == Some(lock().client_key.as_str()) {
+                    json!({"type": "registered", "id": id, "payload": {"client-key": key}})
+                } else {
+                    json!({"type": "error", "id": id, "error": "403 Error!! Invalid client key"})
+                }
+            }
+            "request" | "subscribe" => {
+                let uri = message.get("uri").and_then(Value::as_str).unwrap_or("");
+                let mut state = lock();
+                state.requests.push(MockRequest {
+                    kind: kind.to_string(),
+                    uri: uri.to_string(),
+                    payload,
+                });
+                if state.silent.iter().any(|silent| silent == uri) {
+                    continue;
+                }
+                let answer = if uri.ends_with("/getPointerInputSocket") {
+                    let socket = format!("ws://{}{}", addr, POINTER_PATH);
+                    json!({"returnValue": true, "socketPath": socket})
+                } else {
+                    state
+                        .answers
+                        .get(uri)
+                        .cloned()
+                        .unwrap_or_else(|| json!({"returnValue": true}))
+                };
+                json!({"type": "response", "id": id, "payload": answer})
+            }
+            _ => continue,
+        };
+        if writer.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,634 @@
+//! Reading and writing the TOML config format.
+//!
+//! Covers the parts of TOML a config file uses: tables, arrays of tables,
+//! dotted and quoted keys, strings, integers, floats, booleans, arrays and
+//! inline tables. Values go through `serde_json::Value` so the same serde
+//! types serve both formats. Dates are not supported.
+//!
+//! Writing produces a whole new document from the values: comments, key
+//! order and layout of a file that was read are not kept.
+
+use crate::error::{LgtvError, Result};
+use serde_json::{Map, Number, Value};
+use std::collections::HashSet;
+
+/// Parse a TOML document into a JSON object.
+pub fn from_str(text: &str) -> Result<Value> {
+    Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    }
+    .document()
+}
+
+/// Write a JSON object as a TOML document. Nulls have no TOML form and are
+/// left out. Nothing of the document it was read from is kept.
+pub fn to_string(value: &Value) -> Result<String> {
+    let table = value
+        .as_object()
+        .ok_or_else(|| LgtvError::ConfigError("TOML documents must be tables".to_string()))?;
+    let mut out = String::new();
+    write_table(&mut out, &[], table);
+    Ok(out)
+}
+
+fn write_table(out: &mut String, path: &[String], table: &Map<String, Value>) {
+    let is_table = |v: &Value| v.is_object();
+    let is_table_array = |v: &Value| {
+        v.as_array()
+            .is_some_and(|a| !a.is_empty() && a.iter().all(is_table))
+    };
+
+    for (key, value) in table {
+        if value.is_null() || is_table(value) || is_table_array(value) {
+            continue;
+        }
+        out.push_str(&format!("{} = {}\n", format_key(key), inline(value)));
+    }
+
+    for (key, value) in table {
+        let mut child = path.to_vec();
+        child.push(format_key(key));
+        if let Value::Object(sub) = value {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", child.join(".")));
+            write_table(out, &child, sub);
+        } else if is_table_array(value) {
+            for item in value.as_array().into_iter().flatten() {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                out.push_str(&format!("[[{}]]\n", child.join(".")));
+                if let Value::Object(sub) = item {
+                    write_table(out, &child, sub);
+                }
+            }
+        }
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Null => "\"\"".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => quote(s),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().filter(|v| !v.is_null()).map(inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(table) => {
+            let items: Vec<String> = table
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| format!("{} = {}", format_key(k), inline(v)))
+                .collect();
+            if items.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", items.join(", "))
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> LgtvError {
+        Self::error_at(self.line, message)
+    }
+
+    fn error_at(line: usize, message: &str) -> LgtvError {
+        LgtvError::ConfigError(format!("TOML line {}: {}", line, message))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.peek() == Some(c) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    /// Skip spaces and tabs.
+    fn skip_inline_space(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    /// Skip whitespace, newlines and comments.
+    fn skip_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while !matches!(self.peek(), None | Some('\n')) {
+            self.bump();
+        }
+    }
+
+    /// After a key/value or table header only a comment may follow.
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_inline_space();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+        if self.peek() == Some('\r') {
+            self.bump();
+        }
+        match self.bump() {
+            None | Some('\n') => Ok(()),
+            Some(_) => Err(self.error("expected end of line")),
+        }
+    }
+
+    fn document(mut self) -> Result<Value> {
+        let mut root = Value::Object(Map::new());
+        let mut current: Vec<String> = Vec::new();
+        // Tables given a header or an inline value, which can't be opened
+        // again
+        let mut defined: HashSet<Vec<String>> = HashSet::new();
+
+        loop {
+            self.skip_space();
+            // Checks made once the line is read still report this one
+            let line = self.line;
+            match self.peek() {
+                None => return Ok(root),
+                Some('[') => {
+                    self.bump();
+                    let array = self.peek() == Some('[');
+                    if array {
+                        self.bump();
+                    }
+                    self.skip_inline_space();
+                    let path = self.key_path()?;
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                    }
+                    self.end_of_line()?;
+
+                    let (last, parent) = path.split_last().expect("key paths are never empty");
+                    let parent = self.table_at(&mut root, parent)?;
+                    if array {
+                        let list = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Array(Vec::new()));
+                        list.as_array_mut()
+                            .ok_or_else(|| self.error(&format!("{} is not an array", last)))?
+                            .push(Value::Object(Map::new()));
+                        // The new element's tables are its own
+                        defined.retain(|table| !table.starts_with(&path));
+                    } else {
+                        if !defined.insert(path.clone()) {
+                            let message = format!("table {} is defined twice", path.join("."));
+                            return Err(Self::error_at(line, &message));
+                        }
+                        let table = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Object(Map::new()));
+                        if !table.is_object() {
+                            return Err(self.error(&format!("{} is not a table", last)));
+                        }
+                    }
+                    current = path;
+                }
+                Some(_) => {
+                    let path = self.key_path()?;
+                    self.expect('=')?;
+                    self.skip_inline_space();
+                    let value = self.value()?;
+                    self.end_of_line()?;
+
+                    let mut full = current.clone();
+                    full.extend(path);
+                    if value.is_object() {
+                        defined.insert(full.clone());
+                    }
+                    let (last, parent) = full.split_last().expect("key paths are never empty");
+                    let table = self.table_at(&mut root, parent)?;
+                    if table.insert(last.clone(), value).is_some() {
+                        return Err(Self::error_at(line, &format!("duplicate key {}", last)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The table at `path`, creating missing ones. Arrays of tables
+    /// resolve to their last element.
+    fn table_at<'a>(
+        &self,
+        root: &'a mut Value,
+        path: &[String],
+    ) -> Result<&'a mut Map<String, Value>> {
+        let mut table = root;
+        for key in path {
+            let map = table
+                .as_object_mut()
+                .ok_or_else(|| self.error(&format!("{} is not a table", key)))?;
+            let next = map
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            table = match next {
+                Value::Array(items) => items
+                    .last_mut()
+                    .ok_or_else(|| self.error(&format!("{} is an empty array", key)))?,
+                other => other,
+            };
+        }
+        table
+            .as_object_mut()
+            .ok_or_else(|| self.error("not a table"))
+    }
+
+    fn key_path(&mut self) -> Result<Vec<String>> {
+        let mut path = vec![self.key()?];
+        self.skip_inline_space();
+        while self.peek() == Some('.') {
+            self.bump();
+            self.skip_inline_space();
+            path.push(self.key()?);
+            self.skip_inline_space();
+        }
+        Ok(path)
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.bump();
+                }
+                if self.pos == start {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some('f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_space();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut root = Value::Object(Map::new());
+        self.skip_inline_space();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(root);
+        }
+        loop {
+            self.skip_inline_space();
+            let path = self.key_path()?;
+            self.expect('=')?;
+            self.skip_inline_space();
+            let value = self.value()?;
+            let (last, parent) = path.split_last().expect("key paths are never empty");
+            self.table_at(&mut root, parent)?
+                .insert(last.clone(), value);
+            self.skip_inline_space();
+            match self.bump() {
+                Some(',') => {}
+                Some('}') => return Ok(root),
+                _ => return Err(self.error("expected ',' or '}' in inline table")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+        {
+            self.bump();
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+
+        let integer = match text.get(..2) {
+            Some("0x") => i64::from_str_radix(&text[2..], 16).ok(),
+            Some("0o") => i64::from_str_radix(&text[2..], 8).ok(),
+            Some("0b") => i64::from_str_radix(&text[2..], 2).ok(),
+            _ => text.parse::<i64>().ok(),
+        };
+        if let Some(n) = integer {
+            return Ok(Value::Number(n.into()));
+        }
+        text.parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| self.error(&format!("invalid number {}", text)))
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        let multiline = self.starts_with("\"\"\"");
+        if multiline {
+            self.pos += 3;
+            // A newline right after the opening quotes is trimmed
+            if self.peek() == Some('\r') {
+                self.bump();
+            }
+            if self.peek() == Some('\n') {
+                self.bump();
+            }
+        } else {
+            self.expect('"')?;
+        }
+
+        let mut out = String::new();
+        loop {
+            if multiline && self.starts_with("\"\"\"") {
+                self.pos += 3;
+                return Ok(out);
+            }
+            if !multiline && self.peek() == Some('\n') {
+                return Err(self.error("unterminated string"));
+            }
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') if !multiline => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some(u @ ('u' | 'U')) => {
+                        let len = if u == 'u' { 4 } else { 8 };
+                        let hex: String = (0..len).filter_map(|_| self.bump()).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        out.push(c);
+                    }
+                    // Line-ending backslash in multi-line strings
+                    Some(c) if multiline && c.is_whitespace() => {
+                        while self.peek().is_some_and(char::is_whitespace) {
+                            self.bump();
+                        }
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        let multiline = self.starts_with("'''");
+        if multiline {
+            self.pos += 3;
+            if self.peek() == Some('\r') {
+                self.bump();
+            }
+            if self.peek() == Some('\n') {
+                self.bump();
+            }
+        } else {
+            self.expect('\'')?;
+        }
+
+        let mut out = String::new();
+        loop {
+            if multiline && self.starts_with("'''") {
+                self.pos += 3;
+                return Ok(out);
+            }
+            if !multiline && self.peek() == Some('\n') {
+                return Err(self.error("unterminated string"));
+            }
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('\'') if !multiline => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn round_trip() {
+        let value = json!({
+            "_default": "living-room",
+            "living-room": {
+                "ip": "192.168.1.20",
+                "ssl": true,
+                "port": 3001,
+                "ratio": 1.5,
+                "permissions": ["CONTROL_AUDIO", "READ_INPUT"],
+                "button_delays": {"default": 100, "channel_up": 500},
+                "path": "C:\\tv \"main\"\n",
+            },
+            "my tv": {"key": "abc"},
+            "remotes": [{"name": "a"}, {"name": "b", "extra": {"x": 1}}],
+        });
+        let text = to_string(&value).unwrap();
+        assert_eq!(from_str(&text).unwrap(), value);
+    }
+
+    #[test]
+    fn nulls_are_left_out() {
+        let text = to_string(&json!({"a": null, "b": [1, null]})).unwrap();
+        assert_eq!(from_str(&text).unwrap(), json!({"b": [1]}));
+    }
+
+    #[test]
+    fn parses_the_supported_syntax() {
+        let text = r#"
+# A comment
+top = 'literal \n'   # trailing comment
+"quoted key" = 1
+dotted.key = "yes"
+numbers = [0x1f, 0o17, 0b101, 1_000, -3, +4, 2.5, 1e3]
+multi = """
+first\
+   second"""
+raw = '''
+C:\path'''
+
+[tv]
+inline = { a = 1, b.c = "d" }
+nested = [[1, 2], [
+  3,
+]]
+
+[[tv.items]]
+n = 1
+
+[[tv.items]]
+n = 2
+"#;
+        assert_eq!(
+            from_str(text).unwrap(),
+            json!({
+                "top": "literal \\n",
+                "quoted key": 1,
+                "dotted": {"key": "yes"},
+                "numbers": [31, 15, 5, 1000, -3, 4, 2.5, 1000.0],
+                "multi": "firstsecond",
+                "raw": "C:\\path",
+                "tv": {
+                    "inline": {"a": 1, "b": {"c": "d"}},
+                    "nested": [[1, 2], [3]],
+                    "items": [{"n": 1}, {"n": 2}],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn subtables_of_each_array_element() {
+        let text = "[[tv]]\n[tv.opts]\na = 1\n[[tv]]\n[tv.opts]\na = 2\n";
+        assert_eq!(
+            from_str(text).unwrap(),
+            json!({"tv": [{"opts": {"a": 1}}, {"opts": {"a": 2}}]})
+        );
+    }
+
+    fn error(text: &str) -> String {
+        from_str(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        assert!(error("a = 1\na = 2\n").contains("line 2: duplicate key a"));
+        assert!(error("[tv]\na = 1\n[tv]\nb = 2\n").contains("line 3: table tv is defined twice"));
+        assert!(error("tv = { a = 1 }\n[tv]\nb = 2\n").contains("table tv is defined twice"));
+        // A table only created implicitly by a sub-table may be opened
+        assert!(from_str("[a.b]\nx = 1\n[a]\ny = 2\n").is_ok());
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        assert!(error("a = 1\nb = \"open\n").contains("line 2: unterminated string"));
+        assert!(error("a = 1 b\n").contains("line 1: expected end of line"));
+        assert!(error("a = \n").contains("expected a value"));
+        assert!(error("a = 1\n[a]\n").contains("a is not a table"));
+    }
+}
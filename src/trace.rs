@@ -0,0 +1,143 @@
+//! A log of every websocket frame exchanged with TVs, exactly as sent and
+//! received, for debugging what the TV actually saw. Off until
+//! [`to_writer`] or [`to_file`] is called; client keys are redacted.
+
+use crate::error::Result;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+/// Shown instead of client keys.
+const REDACTED: &str = "<redacted>";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SINK: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Trace frames to `writer`, such as `std::io::stderr()`.
+pub fn to_writer(writer: impl Write + Send + 'static) {
+    *SINK.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(writer));
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Trace frames to the file at `path`, appending to it.
+pub fn to_file(path: &Path) -> Result<()> {
+    let file = File::options().create(true).append(true).open(path)?;
+    to_writer(file);
+    Ok(())
+}
+
+/// Whether frames are being traced.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record a frame sent to `peer`.
+pub(crate) fn sent(peer: &str, message: &Message) {
+    if enabled() {
+        write(">>", peer, message);
+    }
+}
+
+/// Record a frame received from `peer`.
+pub(crate) fn received(peer: &str, message: &Message) {
+    if enabled() {
+        write("<<", peer, message);
+    }
+}
+
+fn write(direction: &str, peer: &str, message: &Message) {
+    let frame = match message {
+        // Pointer socket frames are several lines each
+        Message::Text(text) => format!("text {}", redact(text).replace('\n', "\\n")),
+        Message::Binary(data) => format!("binary ({} bytes)", data.len()),
+        Message::Ping(data) => format!("ping ({} bytes)", data.len()),
+        Message::Pong(data) => format!("pong ({} bytes)", data.len()),
+        Message::Close(Some(close)) => format!("close {} {}", u16::from(close.code), close.reason),
+        Message::Close(None) => "close".to_string(),
+        Message::Frame(_) => "raw frame".to_string(),
+    };
+    let line = format!("{} {} {} {}\n", timestamp(), direction, peer, frame);
+    if let Some(sink) = SINK.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        let _ = sink.write_all(line.as_bytes());
+        let _ = sink.flush();
+    }
+}
+
+/// `text` with the values of any `client-key` fields replaced. Frames that
+/// aren't JSON are kept as they are.
+fn redact(text: &str) -> String {
+    fn walk(value: &mut Value) -> bool {
+        let mut changed = false;
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if key == "client-key" && value.is_string() {
+                        *value = Value::from(REDACTED);
+                        changed = true;
+                    } else {
+                        changed |= walk(value);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    changed |= walk(item);
+                }
+            }
+            _ => {}
+        }
+        changed
+    }
+
+    match serde_json::from_str::<Value>(text) {
+        Ok(mut value) => {
+            if walk(&mut value) {
+                value.to_string()
+            } else {
+                text.to_string()
+            }
+        }
+        Err(_) => text.to_string(),
+    }
+}
+
+/// The current UTC time as `2024-01-31T12:34:56.789Z`.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (hour, minute, second) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        now.subsec_millis()
+    )
+}
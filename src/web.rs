@@ -0,0 +1,391 @@
+//! `lgtv serve-web`: a remote control page for phones and other browsers
+//! on the LAN, running commands over one connection to the TV.
+//!
+//! The page posts `{"command": ...}` objects to `/api`, the same as `exec`
+//! takes, and gets `exec`'s replies back. Requests are answered one at a
+//! time; a remote only ever has one person pressing its buttons.
+//!
+//! Only remote-control commands are run, and only for callers presenting
+//! the token printed at startup from this server's own origin.
+
+use super::{
+    command_words, exec_reply, exec_request, parse_command, AppCommands, Commands, InputCommands,
+    Session,
+};
+use lgtv::error::{LgtvError, Result};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const PAGE: &str = include_str!("web.html");
+/// Largest request head and body accepted.
+const MAX_HEAD: usize = 16 * 1024;
+const MAX_BODY: usize = 64 * 1024;
+/// How long a browser gets to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the TV gets to carry out a command. One that has gone away
+/// without closing the connection never answers.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Request {
+    method: String,
+    path: String,
+    /// Header names in lower case
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        let (_, query) = self.path.split_once('?')?;
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Whether a browser sent this from a page other than ours. Browsers
+    /// always send `Origin` on a cross-origin POST.
+    fn cross_origin(&self) -> bool {
+        match (self.header("origin"), self.header("host")) {
+            (None, _) => false,
+            (Some(origin), Some(host)) => origin != format!("http://{}", host),
+            (Some(_), None) => true,
+        }
+    }
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: &'static str, value: &Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self::json(status, &json!({"ok": false, "error": message}))
+    }
+}
+
+/// Serve the remote on `addr` until interrupted, to callers presenting
+/// `token` (a random one if `None`).
+pub async fn serve(
+    session: &mut Session<'_>,
+    addr: SocketAddr,
+    token: Option<String>,
+) -> Result<()> {
+    let token = match token {
+        Some(token) if token.is_empty() || !token.bytes().all(|b| b.is_ascii_alphanumeric()) => {
+            return Err(LgtvError::CommandError(
+                "The serve-web token must be letters and digits".to_string(),
+            ))
+        }
+        Some(token) => token,
+        None => random_token()?,
+    };
+    let listener = TcpListener::bind(addr).await?;
+    let page = PAGE
+        .replace("{tv}", &escape_html(session.tv_name))
+        .replace("{token}", &token);
+    eprintln!(
+        "Serving a remote for '{}' on {}?token={}",
+        session.tv_name,
+        page_url(addr),
+        token
+    );
+
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+            Ok(Ok(request)) => request,
+            Ok(Err(e)) => {
+                log::debug!("Bad request from {}: {}", peer, e);
+                let _ = write_response(
+                    &mut stream,
+                    Response::error("400 Bad Request", &e.to_string()),
+                )
+                .await;
+                continue;
+            }
+            Err(_) => {
+                log::debug!("{} took too long to send its request", peer);
+                continue;
+            }
+        };
+        log::debug!("{} {} from {}", request.method, request.path, peer);
+        let response = respond(session, &page, &token, request).await;
+        if let Err(e) = write_response(&mut stream, response).await {
+            log::debug!("Could not answer {}: {}", peer, e);
+        }
+    }
+}
+
+async fn respond(session: &mut Session<'_>, page: &str, token: &str, request: Request) -> Response {
+    // The page and the API are both relative to wherever it is served
+    let path = request.path.split('?').next().unwrap_or("/");
+    if request.cross_origin() {
+        return Response::error("403 Forbidden", "Cross-origin requests are not allowed");
+    }
+    let presented = match path {
+        "/api" => request.header("x-lgtv-token"),
+        _ => request.query("token"),
+    };
+    if matches!(path, "/" | "/index.html" | "/api")
+        && !presented.is_some_and(|presented| same_token(presented, token))
+    {
+        return Response::error(
+            "401 Unauthorized",
+            "Open the address with the token lgtv serve-web printed",
+        );
+    }
+    match (request.method.as_str(), path) {
+        ("GET", "/" | "/index.html") => Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body: page.as_bytes().to_vec(),
+        },
+        ("POST", "/api") => {
+            // Also keeps out plain HTML forms, which can't send JSON
+            let json = request
+                .header("content-type")
+                .and_then(|t| t.split(';').next())
+                .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/json"));
+            if !json {
+                return Response::error(
+                    "415 Unsupported Media Type",
+                    "Expected Content-Type: application/json",
+                );
+            }
+            let body = match serde_json::from_slice::<Value>(&request.body) {
+                Ok(body) => body,
+                Err(e) => {
+                    return Response::error("400 Bad Request", &format!("Invalid JSON: {}", e))
+                }
+            };
+            // Only commands: raw SSAP requests stay with `exec` and `raw`
+            let Some(command) = body.get("command") else {
+                return Response::error("400 Bad Request", "Expected a \"command\"");
+            };
+            let id = body.get("id").cloned().unwrap_or(Value::Null);
+            let request = json!({"command": command});
+            match command_words(&request).and_then(|words| parse_command(&words, "serve-web")) {
+                Ok(parsed) if allowed(&parsed) => {}
+                Ok(_) => {
+                    return Response::json(
+                        "200 OK",
+                        &exec_reply(
+                            &id,
+                            Err(LgtvError::CommandError(
+                                "Only remote-control commands can be run from serve-web"
+                                    .to_string(),
+                            )),
+                        ),
+                    )
+                }
+                Err(e) => return Response::json("200 OK", &exec_reply(&id, Err(e))),
+            }
+            let result =
+                tokio::time::timeout(COMMAND_TIMEOUT, exec_request(session, &request, &id))
+                    .await
+                    .unwrap_or(Err(LgtvError::Timeout(COMMAND_TIMEOUT)));
+            if let Err(
+                LgtvError::WebSocketError(_)
+                | LgtvError::ConnectionError(_)
+                | LgtvError::Timeout(_)
+                | LgtvError::NotConnected(_),
+            ) = &result
+            {
+                // Reconnect on the next press, e.g. once the TV is back on
+                session.remote = None;
+                session.cursor = None;
+            }
+            Response::json("200 OK", &exec_reply(&id, result))
+        }
+        (_, "/" | "/index.html" | "/api") => {
+            Response::error("405 Method Not Allowed", "Method not allowed")
+        }
+        _ => Response::error("404 Not Found", "Not found"),
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let invalid = |message: &str| LgtvError::CommandError(message.to_string());
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i;
+        }
+        if data.len() > MAX_HEAD {
+            return Err(invalid("Request head too large"));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(invalid("Connection closed before the request ended"));
+        }
+        data.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(invalid("Malformed request line"));
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .map(|(_, value)| value.parse::<usize>())
+        .transpose()
+        .map_err(|_| invalid("Invalid Content-Length"))?
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(invalid("Request body too large"));
+    }
+
+    let mut body = data.split_off(head_end + 4);
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(invalid("Connection closed before the body ended"));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Whether the page may run `command`: buttons, power, volume, channels,
+/// inputs and apps. Nothing that reads or writes the config (`serialise`
+/// prints the client key), reads local files (notification icons) or sends
+/// the TV payloads of the caller's choosing.
+fn allowed(command: &Commands) -> bool {
+    match command {
+        Commands::Input { command } => !matches!(command, InputCommands::SetDeviceInfo { .. }),
+        Commands::App { command } => !matches!(command, AppCommands::LaunchWithPayload { .. }),
+        Commands::Power { .. }
+        | Commands::Audio { .. }
+        | Commands::Channel { .. }
+        | Commands::On { .. }
+        | Commands::Off { .. }
+        | Commands::ScreenOff { .. }
+        | Commands::ScreenOn
+        | Commands::GetPowerState
+        | Commands::Mute { .. }
+        | Commands::SetVolume { .. }
+        | Commands::VolumeUp
+        | Commands::VolumeDown
+        | Commands::AudioStatus
+        | Commands::AudioVolume
+        | Commands::GetSoundOutput
+        | Commands::SetSoundOutput { .. }
+        | Commands::GetTvChannel
+        | Commands::SetTvChannel { .. }
+        | Commands::ListChannels { .. }
+        | Commands::ChannelNumber { .. }
+        | Commands::InputChannelUp
+        | Commands::InputChannelDown
+        | Commands::InputMediaPlay
+        | Commands::InputMediaPause
+        | Commands::InputMediaStop
+        | Commands::InputMediaRewind
+        | Commands::InputMediaFastForward
+        | Commands::ListInputs { .. }
+        | Commands::SetInput { .. }
+        | Commands::ListApps { .. }
+        | Commands::ListLaunchPoints
+        | Commands::StartApp { .. }
+        | Commands::CloseApp { .. }
+        | Commands::GetForegroundAppInfo
+        | Commands::SendEnterKey
+        | Commands::Type { .. }
+        | Commands::SendButton { .. }
+        | Commands::Drag { .. } => true,
+        _ => false,
+    }
+}
+
+fn random_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| LgtvError::CommandError(format!("No random numbers available: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compare tokens in constant time.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Where to point a browser: this host's LAN address when listening on
+/// all of them.
+fn page_url(addr: SocketAddr) -> String {
+    let host = if addr.ip().is_unspecified() {
+        lan_address().unwrap_or_else(|| addr.ip().to_string())
+    } else {
+        addr.ip().to_string()
+    };
+    match addr {
+        SocketAddr::V6(_) if host.contains(':') => format!("http://[{}]:{}/", host, addr.port()),
+        _ => format!("http://{}:{}/", host, addr.port()),
+    }
+}
+
+/// The address this host uses towards the LAN (nothing is sent).
+fn lan_address() -> Option<String> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("239.255.255.250:1900").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then(|| ip.to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,191 @@
+//! The remote, cursor and client against [`MockTv`], without a TV.
+
+use lgtv::testing::MockTv;
+use lgtv::{ConnectionState, LgtvClient, LgtvCursor, LgtvError, LgtvGroup, QueuePolicy, RateLimit};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn registers_with_the_client_key() {
+    let tv = MockTv::start().await.unwrap();
+    let remote = tv.remote().unwrap();
+    assert_eq!(remote.state(), ConnectionState::Disconnected);
+    remote.connect().await.unwrap();
+    assert_eq!(remote.state(), ConnectionState::Ready);
+    remote.close().await;
+    assert_eq!(remote.state(), ConnectionState::Closed);
+}
+
+#[tokio::test]
+async fn a_refused_key_is_key_rejected() {
+    let tv = MockTv::start().await.unwrap();
+    let remote = tv.remote().unwrap();
+    tv.set_client_key("another-key");
+    assert!(matches!(
+        remote.connect().await,
+        Err(LgtvError::KeyRejected)
+    ));
+}
+
+#[tokio::test]
+async fn canned_answers_are_returned() {
+    let tv = MockTv::start().await.unwrap();
+    tv.respond(
+        "ssap://audio/getVolume",
+        json!({"returnValue": true, "volume": 12, "muted": false}),
+    );
+    let remote = tv.remote().unwrap();
+    remote.connect().await.unwrap();
+
+    let volume: Value = remote
+        .request("ssap://audio/getVolume", None)
+        .await
+        .unwrap();
+    assert_eq!(volume["volume"], 12);
+    assert_eq!(remote.set_volume(12).await.unwrap(), 12);
+
+    let request = tv
+        .requests()
+        .into_iter()
+        .find(|r| r.uri == "ssap://audio/setVolume")
+        .unwrap();
+    assert_eq!(request.payload, Some(json!({"volume": 12})));
+}
+
+#[tokio::test]
+async fn return_value_false_is_a_tv_error() {
+    let tv = MockTv::start().await.unwrap();
+    tv.respond(
+        "ssap://system/turnOff",
+        json!({"returnValue": false, "errorCode": 401, "errorText": "insufficient permissions"}),
+    );
+    let remote = tv.remote().unwrap();
+    remote.connect().await.unwrap();
+
+    match remote.off().await {
+        Err(LgtvError::TvError { code, text }) => {
+            assert_eq!(code, Some(401));
+            assert_eq!(text, "insufficient permissions");
+        }
+        other => panic!("expected a TvError, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn unanswered_requests_time_out() {
+    let tv = MockTv::start().await.unwrap();
+    tv.silence("ssap://system/turnOff");
+    let remote = tv
+        .remote_builder()
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    remote.connect().await.unwrap();
+    assert!(matches!(remote.off().await, Err(LgtvError::Timeout(_))));
+}
+
+/// The buttons the mock has recorded once there are `count`; it records
+/// them on its own socket task.
+async fn buttons(tv: &MockTv, count: usize) -> Vec<String> {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while tv.buttons().len() < count && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    tv.buttons()
+}
+
+#[tokio::test]
+async fn pointer_buttons_reach_the_tv() {
+    let tv = MockTv::start().await.unwrap();
+    let remote = tv.remote().unwrap();
+    remote.connect().await.unwrap();
+
+    let mut cursor = LgtvCursor::from_connected(remote).await.unwrap();
+    cursor.button("HOME").await.unwrap();
+    cursor.button("UP").await.unwrap();
+    assert_eq!(buttons(&tv, 2).await, ["HOME", "UP"]);
+
+    let client = LgtvClient::new(tv.remote().unwrap());
+    client.connect().await.unwrap();
+    client.pointer().button("ENTER").await.unwrap();
+    assert_eq!(buttons(&tv, 3).await, ["HOME", "UP", "ENTER"]);
+}
+
+#[tokio::test]
+async fn commands_wait_in_the_queue_until_connected() {
+    let tv = MockTv::start().await.unwrap();
+    let remote = tv
+        .remote_builder()
+        .queue(QueuePolicy::new())
+        .build()
+        .unwrap();
+
+    let queued = tokio::spawn({
+        let remote = remote.clone();
+        async move { remote.screen_off().await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(tv.requests().is_empty());
+
+    remote.connect().await.unwrap();
+    queued.await.unwrap().unwrap();
+    assert!(tv
+        .requests()
+        .iter()
+        .any(|r| r.uri.ends_with("/turnOffScreen")));
+}
+
+#[tokio::test]
+async fn without_a_queue_commands_fail_when_disconnected() {
+    let tv = MockTv::start().await.unwrap();
+    let remote = tv.remote().unwrap();
+    assert!(matches!(
+        remote.screen_off().await,
+        Err(LgtvError::NotConnected(ConnectionState::Disconnected))
+    ));
+}
+
+#[tokio::test]
+async fn the_rate_limit_spaces_requests_out() {
+    let tv = MockTv::start().await.unwrap();
+    let remote = tv
+        .remote_builder()
+        .rate_limit(RateLimit::new(Duration::from_millis(100)))
+        .build()
+        .unwrap();
+    remote.connect().await.unwrap();
+
+    let started = Instant::now();
+    for _ in 0..4 {
+        remote.screen_on().await.unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn a_group_reports_every_tv() {
+    let first = MockTv::start().await.unwrap();
+    first.respond(
+        "ssap://audio/getVolume",
+        json!({"returnValue": true, "volume": 7, "muted": false}),
+    );
+    let second = MockTv::start().await.unwrap();
+    second.respond(
+        "ssap://audio/setVolume",
+        json!({"returnValue": false, "errorCode": 500, "errorText": "busy"}),
+    );
+    let group = LgtvGroup::new([
+        first.remote_builder().name("first").build().unwrap(),
+        second.remote_builder().name("second").build().unwrap(),
+    ]);
+
+    let results = group.set_volume(7).await;
+    let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["first", "second"]);
+    assert_eq!(results[0].result.as_ref().unwrap(), &7);
+    assert!(matches!(results[1].result, Err(LgtvError::TvError { .. })));
+    assert!(first
+        .requests()
+        .iter()
+        .any(|r| r.uri == "ssap://audio/setVolume"));
+}